@@ -1,22 +1,26 @@
 use std::collections::{HashMap, HashSet};
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+// `ConvertSaveload` derive expects `NoError` in scope
+use std::convert::Infallible as NoError;
 
-use specs_derive::Component;
+use specs_derive::{Component, ConvertSaveload};
 
 use crate::{graphics::gui::menus::TextCol, spawner::spawn_tables::SpawnEntry};
 pub use rltk::{VirtualKeyCode, RGB};
 
 use super::systems::player::{Dir, InputType};
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
     pub level: usize,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Renderable {
     pub texture: Option<usize>, // texture index
     /// If more then one entities are on same pos, entity with lowest order is drawn.
@@ -24,59 +28,64 @@ pub struct Renderable {
     pub render_order: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
     pub input: Option<InputType>,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Movable {
     pub move_dir: Option<Dir>,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct AI {}
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct View {
     pub range: usize,
+    #[serde(default)]
     pub visible_tiles: HashSet<rltk::Point>,
     pub should_update: bool,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct ViewMemory {
     /// indexed by level
     pub seen_tiles: HashMap<usize, HashSet<rltk::Point>>,
     pub should_update: bool,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Name {
     pub name: String,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct BlocksTile {}
 
-#[derive(Component, Debug, Clone)]
+/// Entity can open closed (not locked) doors, AI paths through them.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct OpensDoors {}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Hp {
     pub max_hp: i32,
     pub hp: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct CombatBaseStats {
     pub attack: i32,
     pub defense: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToMeleeAtack {
     pub target: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct SufferDamage {
     pub amount: Vec<i32>,
 }
@@ -94,15 +103,15 @@ impl SufferDamage {
     }
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Item {}
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Heal {
     pub heal_power: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct HealEffect {
     pub heal_power: i32,
 }
@@ -118,78 +127,110 @@ impl Inventory {
     }
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InventorySaveloadData<M> {
+    pub items: Vec<M>,
+}
+
+/// `items` may still hold entities that were destroyed on use, those are skipped.
+impl<M: Marker + Serialize> ConvertSaveload<M> for Inventory
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = InventorySaveloadData<M>;
+    type Error = NoError;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(InventorySaveloadData {
+            items: self.items.iter().filter_map(|item| ids(*item)).collect(),
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Inventory {
+            items: data.items.into_iter().filter_map(ids).collect(),
+        })
+    }
+}
+
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct InInventory {
     pub owner: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToPickupItem {
     pub item: Entity,
 }
 
 /// if target is None, then target is user
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToUseItem {
     pub item: Entity,
     pub target: Option<rltk::Point>,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToDropItem {
     pub item: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Usable {
     pub destoyed_on_use: bool,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Ranged {
     pub range: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct InflictsDamage {
     pub damage: i32,
 }
 
 /// AOE effect (circle area for now only)
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct AreaOfEffect {
     pub radius: i32,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Sleeping {
     pub duration: usize,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct SleepingEffect {
     pub duration: usize,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Teleporting {}
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct TeleportingEffect {
     pub target_pos: (usize, usize),
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct SpawnsAfterDeath {
     pub spawns: Vec<SpawnEntry>,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Spawn {
     pub names_nums: Vec<(String, usize)>,
 }
 
-#[derive(Copy, PartialEq, Eq, Debug, Clone, Hash)]
+#[derive(Copy, PartialEq, Eq, Debug, Clone, Hash, Serialize, Deserialize)]
 pub enum BodyPart {
     /// manly used for weapons (don't use as actual body part in `BodyParts`)
     OneHanded,
@@ -247,42 +288,84 @@ impl BodyParts {
     }
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BodyPartsSaveloadData<M> {
+    pub parts_with_equipped: Vec<(BodyPart, Option<M>)>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for BodyParts
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = BodyPartsSaveloadData<M>;
+    type Error = NoError;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(BodyPartsSaveloadData {
+            parts_with_equipped: self
+                .parts_with_equipped
+                .iter()
+                .map(|(part, item)| (*part, item.and_then(&mut ids)))
+                .collect(),
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(BodyParts {
+            parts_with_equipped: data
+                .parts_with_equipped
+                .into_iter()
+                .map(|(part, item)| (part, item.and_then(&mut ids)))
+                .collect(),
+        })
+    }
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Equippable {
     pub body_part: BodyPart,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct Equipped {
     pub owner: Entity,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToEquip {
     pub item: Entity,
     pub target_body_part: BodyPart,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct WantsToUnEquip {
     pub item: Entity,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct MeleeDamageBonus {
     pub power: i32,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct DefenseBonus {
     pub defense: i32,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct Description {
     pub title: TextCol,
     pub text: TextCol,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub struct FinalBoss {}
+
+/// Marks entities which should be written into save file.
+pub struct SerializeMe;
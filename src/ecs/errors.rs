@@ -1,6 +1,6 @@
 use snafu::Snafu;
 
-use crate::levels;
+use crate::{levels, maps, spawner};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -10,4 +10,66 @@ pub enum Error {
     #[snafu(context(false))]
     #[snafu(display("Error in levels: {:?}", source.to_string()))]
     LevelError { source: levels::Error },
+
+    #[snafu(context(false))]
+    #[snafu(display("Error in maps: {:?}", source.to_string()))]
+    MapError { source: maps::Error },
+
+    #[snafu(context(false))]
+    #[snafu(display("Error in spawner: {:?}", source.to_string()))]
+    SpawnerError { source: spawner::Error },
+
+    #[snafu(display("Unable to access save file {}: {}", path, source))]
+    SaveFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to (de)serialize save file: {}", source))]
+    SaveFileSerde { source: serde_json::Error },
+
+    #[snafu(display("Save file references unknown entity (marker id: {})", marker_id))]
+    SaveFileUnknownEntity { marker_id: u64 },
+
+    #[snafu(display("Unable to access replay file {}: {}", path, source))]
+    ReplayFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display(
+        "Unable to (de)serialize replay file {} (line {}): {}",
+        path,
+        line,
+        source
+    ))]
+    ReplayFileSerde {
+        source: serde_json::Error,
+        path: String,
+        line: usize,
+    },
+
+    #[snafu(display("Replay file {} is empty", path))]
+    ReplayFileEmpty { path: String },
+
+    #[snafu(display("Item {:?} is not in player's inventory", item))]
+    ItemNotInInventory { item: specs::Entity },
+
+    #[snafu(display("Item {:?} can't be used without target", item))]
+    ItemNeedsTarget { item: specs::Entity },
+
+    #[snafu(display("Item {:?} can't be equipped", item))]
+    ItemNotEquippable { item: specs::Entity },
+
+    #[snafu(display("Run state {} can't be handled without rltk context", run_state))]
+    UnexpectedRunState { run_state: String },
+
+    #[snafu(display("{} actions in a row didn't take a turn", actions))]
+    NoTurnProgress { actions: usize },
+
+    #[snafu(display("Unable to write exported map to {}: {}", path, source))]
+    AsciiMapExportIo {
+        source: std::io::Error,
+        path: String,
+    },
 }
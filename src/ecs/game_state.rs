@@ -1,9 +1,17 @@
 use lazy_static::__Deref;
 use rltk::{DrawBatch, GameState, Point, Rltk};
+use serde::{Deserialize, Serialize};
+use snafu::OptionExt;
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs_derive::ConvertSaveload;
+// `ConvertSaveload` derive expects `NoError` in scope
+use std::convert::Infallible as NoError;
 
 use crate::ecs::components;
 use crate::ecs::errors::Result;
+use crate::ecs::replay::{Replay, REPLAY_FILE_PATH};
+use crate::ecs::saveload;
 use crate::ecs::systems;
 use crate::graphics::gui::menus::main_menu::MainMenu;
 use crate::graphics::gui::menus::WindowOptionSelector;
@@ -14,40 +22,51 @@ use crate::graphics::window::{
     CHAR_CONSOLE_INDEX, SPRITE_16X16_CONSOLE_INDEX, SPRITE_32X32_CONSOLE_INDEX,
 };
 use crate::graphics::{self, gui, GuiDrawer};
+use crate::levels;
+use crate::levels::dungeon_layout::{DungeonLayout, LevelKey, DUNGEON_LAYOUT_PATH, MAIN_BRANCH};
 use crate::levels::level::{Level, LevelType};
 use crate::levels::level_manager::LevelManager;
-use crate::maps::{Map, TileType};
+use crate::levels::presets;
+use crate::maps::{
+    ascii::{self, MapEntity},
+    vaults, Map, TileType,
+};
 use crate::rng;
+use crate::spawner::errors::UnknownEntityReference;
 use crate::spawner::player::spawn_player;
-use crate::spawner::spawn_from_spawn_table;
-use crate::spawner::spawn_tables::SpawnTable;
+use crate::spawner::{raws, spawn_tables};
+use crate::spawner::{spawn_entity, spawn_from_spawn_table};
 
 #[cfg(feature = "map_gen_testing")]
 use crate::graphics::gui::menus::map_testing::GuiMapGenTestingManager;
 #[cfg(feature = "map_gen_testing")]
-use crate::maps::generators::basic_dungeon::{BasicDungeonMap, BasicDungeonMapConfig};
-#[cfg(feature = "map_gen_testing")]
-use crate::maps::generators::cellular_automata::CAMapGen;
+use gui::menus::map_editor::MapEditorAction;
 #[cfg(feature = "map_gen_testing")]
 use gui::menus::map_testing::MapGenTestingMenuAction;
 
 use super::components::BodyPart;
 
+/// Level with at least this many elite monsters feels dangerous.
+const DANGEROUS_LEVEL_ELITES: usize = 3;
+
+#[derive(Serialize, Deserialize)]
 pub struct GameLog {
     pub entries: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ConvertSaveload)]
 pub enum TargetingAction {
     TargetingFromItem(Entity, usize),
     Looking,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ConvertSaveload)]
 pub enum RunState {
     MainMenu,
     #[cfg(feature = "map_gen_testing")]
     MapGenTesting(bool),
+    #[cfg(feature = "map_gen_testing")]
+    MapEditor,
     AwaitingInput,
     PreRun,
     PlayerTurn,
@@ -56,7 +75,8 @@ pub enum RunState {
     ShowEquipment,
     ShowItemActions(Entity),
     Targeting(TargetingAction),
-    MoveLevel(usize),
+    MoveLevel(LevelKey),
+    SaveGame,
 
     GameOver,
     Controls,
@@ -86,9 +106,18 @@ pub struct State {
 
     pub targeting_pos: Point,
 
-    pub level_order: Vec<LevelType>,
+    pub dungeon_layout: DungeonLayout,
+
+    /// seed of current run, every level is generated from it (see `rng::seed_for_branch_depth`)
+    pub seed: u64,
+
+    /// records new runs, or plays recorded one
+    pub replay: Replay,
 
     pub game_won: bool,
+
+    /// turns played in current run, used for catching up levels player returns to
+    pub turn: usize,
 }
 
 impl State {
@@ -110,15 +139,11 @@ impl State {
             gui_drawer,
             main_menu: MainMenu::new(),
             targeting_pos: Point::new(0, 0),
-            level_order: vec![
-                LevelType::BasicDungeon,
-                LevelType::BSPDungeon,
-                LevelType::Cave,
-                LevelType::DrunkardWalk,
-                LevelType::BSPInterior,
-                LevelType::TestLevel,
-            ],
+            dungeon_layout: DungeonLayout::default(),
+            seed: 0,
+            replay: Replay::new(),
             game_won: false,
+            turn: 0,
         }
     }
 
@@ -132,6 +157,7 @@ impl State {
         self.ecs.register::<components::ViewMemory>();
         self.ecs.register::<components::Name>();
         self.ecs.register::<components::BlocksTile>();
+        self.ecs.register::<components::OpensDoors>();
         self.ecs.register::<components::Hp>();
         self.ecs.register::<components::CombatBaseStats>();
         self.ecs.register::<components::WantsToMeleeAtack>();
@@ -162,6 +188,10 @@ impl State {
         self.ecs.register::<components::DefenseBonus>();
         self.ecs.register::<components::Inventory>();
         self.ecs.register::<components::FinalBoss>();
+        self.ecs.register::<components::Description>();
+
+        self.ecs.register::<saveload::SaveMarker>();
+        self.ecs.insert(saveload::SaveMarkerAllocator::new());
     }
 
     pub fn reset_gui_inv_manager(&mut self) {
@@ -198,79 +228,292 @@ impl State {
 
     pub fn create_new_level(
         &mut self,
-        level_type: LevelType,
+        level_type: &LevelType,
+        spawn_table_name: &str,
         width: usize,
         height: usize,
-        depth: usize,
-        prev_down_stairs_pos: Option<(usize, usize)>,
+        key: LevelKey,
+        parent: Option<(LevelKey, (usize, usize))>,
     ) -> Result<usize> {
-        let index = self.level_manager.crete_new_level(
-            level_type,
-            width,
-            height,
-            depth,
-            prev_down_stairs_pos.map(|pos| Point::new(pos.0, pos.1)),
-        )?;
-
-        let spawn_table = match level_type {
-            LevelType::Cave => SpawnTable::caves(),
-            LevelType::BasicDungeon => SpawnTable::basic_dungeon(),
-            LevelType::BSPDungeon => SpawnTable::bsp_dungeon(),
-            LevelType::BSPInterior => SpawnTable::bsp_interior(),
-            LevelType::DrunkardWalk => SpawnTable::drunkard_walk(),
-            LevelType::TestLevel => SpawnTable::boss_level(),
-        };
+        let spawn_table = spawn_tables::get_spawn_table(spawn_table_name)?;
+
+        let exits = self.dungeon_layout.exits_plan(key);
+        let (index, entities) = self
+            .level_manager
+            .crete_new_level(level_type, width, height, key, parent, &exits)?;
+        let level = &mut self.level_manager.levels[index];
+        level.spawn_table = spawn_table_name.to_string();
+        level.last_active_turn = self.turn;
+        level.level_weight = self
+            .dungeon_layout
+            .depth_layout(key)
+            .and_then(|depth_layout| depth_layout.level_weight)
+            .unwrap_or(key.depth);
+        if key == LevelKey::main(0) {
+            level.start = Some(random_start_position(&level.map));
+        }
+        let spawn_table = spawn_table.with_level_weight(level.level_weight);
 
-        spawn_from_spawn_table(
+        for entity in entities {
+            spawn_entity(&mut self.ecs, &entity.name, entity.x, entity.y, index).context(
+                UnknownEntityReference {
+                    name: &entity.name,
+                    referenced_by: format!("{:?}", level_type),
+                },
+            )?;
+        }
+        let arrivals = self.arrival_points(index);
+        let report = spawn_from_spawn_table(
             &mut self.ecs,
             &self.level_manager.levels[index],
             spawn_table,
+            &arrivals,
         );
+        let level = &mut self.level_manager.levels[index];
+        level.dangerous = report.elites >= DANGEROUS_LEVEL_ELITES;
+        level.spawn_report = report;
         Ok(index)
     }
 
-    pub fn set_level_as_curent(&mut self, level_index: usize) {
-        // TODO level_index to high error
+    /// Points where player can arrive on level apart from its up stairs: start of the run
+    /// and landings below chasms and trapdoors leading to it. These are on levels above it
+    /// (created before it), so arrival points don't depend on order of visiting levels.
+    fn arrival_points(&self, index: usize) -> Vec<(usize, usize)> {
+        let level = &self.level_manager.levels[index];
+        let map = &level.map;
+        let mut arrivals = level.start.into_iter().collect::<Vec<_>>();
+        let mut ancestor = self.dungeon_layout.parent(level.key());
+        while let Some(key) = ancestor {
+            ancestor = self.dungeon_layout.parent(key);
+            let above = match self.level_manager.level_index(key) {
+                Some(above_index) => &self.level_manager.levels[above_index],
+                None => continue,
+            };
+            for (i, tile) in above.map.tiles.iter().enumerate() {
+                let (x, y) = above.map.index_to_xy(i);
+                if !matches!(tile, TileType::Chasm | TileType::Trapdoor)
+                    || above.exit_destination(x, y) != Some(level.key())
+                {
+                    continue;
+                }
+                // the same landing as in `player_move_level` and `move_player_to_safe_position`
+                let pos = above
+                    .exit_at(x, y)
+                    .and_then(|exit| exit.arrival)
+                    .unwrap_or((x.min(map.width_max()), y.min(map.height_max())));
+                if let Some(landing) = map.closest_safe_position(pos) {
+                    if !arrivals.contains(&landing) {
+                        arrivals.push(landing);
+                    }
+                }
+            }
+        }
+        arrivals
+    }
+
+    /// Creates level using generator picked from dungeon layout,
+    /// `parent` is level with stairs down to it and position of these stairs.
+    pub fn create_level(
+        &mut self,
+        key: LevelKey,
+        parent: Option<(LevelKey, (usize, usize))>,
+    ) -> Result<usize> {
+        rng::reseed(rng::seed_for_branch_depth(self.seed, key.branch, key.depth));
+
+        let generator = self.dungeon_layout.roll_generator(key)?.clone();
+        let depth_layout = self.dungeon_layout.depth_layout(key).unwrap();
+        let width = depth_layout.map_width.unwrap_or(self.map_width);
+        let height = depth_layout.map_height.unwrap_or(self.map_height);
 
+        self.create_new_level(
+            &generator.level_type,
+            &generator.spawn_table,
+            width,
+            height,
+            key,
+            parent,
+        )
+    }
+
+    /// Index of level, which is created (with levels above, leading to it) if needed.
+    /// Exits of levels in level manager can change, so current level has to be stored first.
+    pub fn get_or_create_level(&mut self, key: LevelKey) -> Result<usize> {
+        if let Some(index) = self.level_manager.level_index(key) {
+            return Ok(index);
+        }
+
+        let mut parent = None;
+        if let Some(parent_key) = self.dungeon_layout.parent(key) {
+            let parent_index = self.get_or_create_level(parent_key)?;
+            let parent_level = &self.level_manager.levels[parent_index];
+            parent = parent_level
+                .exits
+                .iter()
+                .find(|exit| {
+                    exit.destination == key
+                        && parent_level.map.tile_at_xy(exit.x, exit.y) == TileType::StairsDown
+                })
+                .map(|exit| (parent_index, parent_key, (exit.x, exit.y)));
+        }
+
+        let index = self.create_level(
+            key,
+            parent.map(|(_index, parent_key, stairs)| (parent_key, stairs)),
+        )?;
+
+        // stairs of parent lead to up stairs of new level
+        if let Some((parent_index, parent_key, stairs)) = parent {
+            let arrival = self.level_manager.levels[index]
+                .exits
+                .iter()
+                .find(|exit| exit.destination == parent_key)
+                .map(|exit| (exit.x, exit.y));
+            for exit in self.level_manager.levels[parent_index].exits.iter_mut() {
+                if (exit.x, exit.y) == stairs {
+                    exit.arrival = arrival;
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    /// Current level (changed during play) is copied back to level manager.
+    fn store_current_level(&mut self) {
         if let Some(current_level) = self.ecs.try_fetch::<Level>() {
             let level = current_level.deref().clone();
             self.level_manager.levels[self.current_level] = level;
         }
+    }
 
+    fn load_level(&mut self, level_index: usize) {
         self.current_level = level_index;
+        self.level_manager.current_level_index = level_index;
         self.ecs
             .insert(self.level_manager.levels[level_index].clone());
     }
 
-    pub fn player_move_level(&mut self, next_level: usize) {
-        if next_level < self.level_manager.levels.len() {
-            self.set_level_as_curent(next_level);
+    pub fn set_level_as_curent(&mut self, level_index: usize) {
+        // TODO level_index to high error
+        self.store_current_level();
+        self.load_level(level_index);
+    }
+
+    /// Entities standing on level (player and items in inventories are skipped).
+    pub fn level_entities(&self, level_index: usize) -> Vec<MapEntity> {
+        let positions = self.ecs.read_storage::<components::Position>();
+        let names = self.ecs.read_storage::<components::Name>();
+        let players = self.ecs.read_storage::<components::Player>();
+        let in_inventory = self.ecs.read_storage::<components::InInventory>();
+        (&positions, &names, !&players, !&in_inventory)
+            .join()
+            .filter(|(pos, _name, _player, _in_inv)| pos.level == level_index)
+            .map(|(pos, name, _player, _in_inv)| MapEntity {
+                name: name.name.clone(),
+                x: pos.x,
+                y: pos.y,
+            })
+            .collect()
+    }
+
+    /// Level as ascii text (see `maps::ascii`).
+    pub fn level_to_ascii(&self, level_index: usize, with_entities: bool) -> Result<String> {
+        let entities = if with_entities {
+            self.level_entities(level_index)
+        } else {
+            vec![]
+        };
+        if level_index == self.current_level {
+            Ok(ascii::map_to_ascii(
+                &self.ecs.fetch::<Level>().map,
+                &entities,
+            )?)
         } else {
-            let mut current_depth = None;
-            let mut prev_down_stairs_pos = None;
-            if let Some(current_level) = self.ecs.try_fetch::<Level>() {
-                current_depth = Some(current_level.depth);
-                prev_down_stairs_pos = current_level
-                    .map
-                    .tiles
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, t)| (*t == TileType::StairsDown).then(|| i))
-                    .map(|i| current_level.map.index_to_xy(i));
+            Ok(ascii::map_to_ascii(
+                &self.level_manager.levels[level_index].map,
+                &entities,
+            )?)
+        }
+    }
+
+    /// Moves player to level `key` through exit (or chasm) player stands on,
+    /// level is created if it wasn't visited yet.
+    pub fn player_move_level(&mut self, key: LevelKey) -> Result<()> {
+        let player = *self.ecs.fetch::<Entity>();
+        let (x, y) = {
+            let positions = self.ecs.read_storage::<components::Position>();
+            let pos = positions.get(player).unwrap();
+            (pos.x, pos.y)
+        };
+
+        self.store_current_level();
+        let from = &mut self.level_manager.levels[self.current_level];
+        from.last_active_turn = self.turn;
+        let from = from.key();
+        let visited = self.level_manager.level_index(key).is_some();
+        let index = self.get_or_create_level(key)?;
+        let arrival = self.level_manager.levels[self.current_level]
+            .exit_at(x, y)
+            .filter(|exit| exit.destination == key)
+            .and_then(|exit| exit.arrival);
+        self.load_level(index);
+
+        let map = &self.level_manager.levels[index].map;
+        let (x, y) = arrival.unwrap_or((x.min(map.width_max()), y.min(map.height_max())));
+        {
+            let mut positions = self.ecs.write_storage::<components::Position>();
+            let mut views = self.ecs.write_storage::<components::View>();
+            let mut views_memories = self.ecs.write_storage::<components::ViewMemory>();
+            let pos = positions.get_mut(player).unwrap();
+            pos.level = index;
+            pos.x = x;
+            pos.y = y;
+            if let Some(view) = views.get_mut(player) {
+                view.should_update = true;
+            }
+            if let Some(view_mem) = views_memories.get_mut(player) {
+                view_mem.should_update = true;
             }
+        }
+        *self.ecs.write_resource::<rltk::Point>() = rltk::Point::new(x, y);
+
+        let elapsed = self
+            .turn
+            .saturating_sub(self.level_manager.levels[index].last_active_turn);
+        systems::ai::off_screen::catch_up_level(self, elapsed);
 
-            let new_level_type = self.level_order[next_level];
-
-            let new_level_index = self
-                .create_new_level(
-                    new_level_type,
-                    self.map_width,
-                    self.map_height,
-                    current_depth.map(|d| d + 1).unwrap_or(0),
-                    prev_down_stairs_pos,
-                )
-                .unwrap();
-            self.set_level_as_curent(new_level_index);
+        let mut gamelog = self.ecs.write_resource::<GameLog>();
+        if key.branch != from.branch {
+            let name = self.dungeon_layout.branch_name(key.branch);
+            gamelog.entries.push(format!("You enter the {}", name));
+        }
+        if !visited && self.level_manager.levels[index].dangerous {
+            gamelog
+                .entries
+                .push("You feel a sense of dread. This place is dangerous!".to_string());
+        }
+        Ok(())
+    }
+
+    /// Player arriving by stairs stands on them, but falling through chasm can end in wall
+    /// or lava, then closest safe tile is used.
+    fn move_player_to_safe_position(&mut self) {
+        let player = *self.ecs.fetch::<Entity>();
+        let mut positions = self.ecs.write_storage::<components::Position>();
+        let mut views = self.ecs.write_storage::<components::View>();
+        let level = self.ecs.fetch::<Level>();
+        let pos = positions.get_mut(player).unwrap();
+
+        let tile = level.map.tile_at_xy(pos.x, pos.y);
+        if !tile.blocks_movement() && !tile.is_door() && !tile.is_hazard() {
+            return;
+        }
+        if let Some((x, y)) = level.map.closest_safe_position((pos.x, pos.y)) {
+            pos.x = x;
+            pos.y = y;
+            *self.ecs.write_resource::<rltk::Point>() = rltk::Point::new(x, y);
+            if let Some(view) = views.get_mut(player) {
+                view.should_update = true;
+            }
         }
     }
 
@@ -323,10 +566,10 @@ impl State {
 
     fn draw_game_graphics(&self, ctx: &mut Rltk) {
         graphics::draw_map_and_entities_with_fov_and_camera(self, ctx);
-        self.gui_drawer.draw_ui(&self.ecs, ctx);
+        self.gui_drawer.draw_ui(&self.ecs, ctx, self.seed);
     }
 
-    fn use_item(&mut self, item: Entity, targeted: bool) {
+    pub fn use_item(&mut self, item: Entity, targeted: bool) {
         let mut items_uses = self.ecs.write_storage::<components::WantsToUseItem>();
         let player = *self.ecs.fetch::<Entity>();
         items_uses
@@ -340,7 +583,18 @@ impl State {
             .expect("Unable to insert intent to use item");
     }
 
-    fn equip_item(&mut self, item: Entity, target_body_part: BodyPart) {
+    /// Body part used when equipping `item` from inventory menu.
+    pub fn default_body_part(&self, item: Entity) -> BodyPart {
+        let equipables = self.ecs.read_storage::<components::Equippable>();
+        let equipable = equipables.get(item).unwrap();
+        if equipable.body_part == BodyPart::OneHanded {
+            BodyPart::HandRight
+        } else {
+            equipable.body_part
+        }
+    }
+
+    pub fn equip_item(&mut self, item: Entity, target_body_part: BodyPart) {
         let mut wants_eq = self.ecs.write_storage::<components::WantsToEquip>();
         let player = *self.ecs.fetch::<Entity>();
         wants_eq
@@ -354,7 +608,7 @@ impl State {
             .expect("Unable to insert intent to equip item");
     }
 
-    fn unequip_item(&mut self, item: Entity) {
+    pub fn unequip_item(&mut self, item: Entity) {
         let mut wants_uneq = self.ecs.write_storage::<components::WantsToUnEquip>();
         let player = *self.ecs.fetch::<Entity>();
         wants_uneq
@@ -362,7 +616,7 @@ impl State {
             .expect("Unable to insert intent to unequip item");
     }
 
-    fn drop_item(&mut self, item: Entity) {
+    pub fn drop_item(&mut self, item: Entity) {
         let mut items_drops = self.ecs.write_storage::<components::WantsToDropItem>();
         let player = *self.ecs.fetch::<Entity>();
         items_drops
@@ -370,8 +624,7 @@ impl State {
             .expect("Unable to insert intent to drop item");
     }
 
-    fn game_over_cleanup(&mut self) {
-        // Delete everything
+    pub fn delete_all_entities(&mut self) {
         let mut to_delete = Vec::new();
         for e in self.ecs.entities().join() {
             to_delete.push(e);
@@ -379,6 +632,13 @@ impl State {
         for del in to_delete.iter() {
             self.ecs.delete_entity(*del).expect("Deletion failed");
         }
+    }
+
+    /// Resets world to start of new run with `self.seed`.
+    fn game_over_cleanup(&mut self) {
+        // Delete everything
+        self.delete_all_entities();
+        rng::reseed(self.seed);
 
         let new_gamelog = GameLog {
             entries: vec![
@@ -388,16 +648,11 @@ impl State {
         };
         self.ecs.remove::<Level>();
         self.ecs.insert(new_gamelog);
+        self.turn = 0;
 
         // Build a new map and place the player
         self.level_manager.reset();
-        let test = self.create_new_level(
-            self.level_order[0],
-            self.map_width,
-            self.map_height,
-            0,
-            None,
-        );
+        let test = self.get_or_create_level(LevelKey::main(0));
 
         match test {
             Ok(_) => (),
@@ -408,19 +663,123 @@ impl State {
         }
         self.set_level_as_curent(0);
 
-        let mut p_x = 0;
-        let mut p_y = 0;
-        while self.current_map().tile_at_xy(p_x, p_y).blocks_movement() {
-            p_x = rng::range(2, self.current_map().width_max() as i32 - 2) as usize;
-            p_y = rng::range(2, self.current_map().height_max() as i32 - 2) as usize;
-        }
-
+        // start is picked when level is created, so nothing is spawned next to it
+        let (p_x, p_y) = self
+            .current_level()
+            .start
+            .unwrap_or_else(|| random_start_position(self.current_map()));
         self.ecs.insert(rltk::Point::new(p_x, p_y));
         let player = spawn_player(&mut self.ecs, p_x, p_y);
 
         self.ecs.insert(player);
     }
 
+    /// Advances run states which don't wait for player (PreRun, PlayerTurn, MonsterTurn, MoveLevel),
+    /// other run states are returned unchanged. Doesn't need rltk context.
+    pub fn run_turn_state(&mut self, run_state: RunState) -> RunState {
+        let mut run_state = run_state;
+        match run_state {
+            RunState::PreRun => {
+                self.run_all_gameplay_systems();
+                run_state = RunState::AwaitingInput;
+            }
+
+            RunState::PlayerTurn => {
+                self.run_all_gameplay_systems();
+                let run_state_check = *self.ecs.fetch::<RunState>();
+
+                if run_state_check != RunState::GameOver {
+                    run_state = RunState::MonsterTurn;
+                } else {
+                    run_state = RunState::GameOver;
+                }
+            }
+
+            RunState::MonsterTurn => {
+                self.run_all_gameplay_systems();
+                self.turn += 1;
+                let run_state_check = *self.ecs.fetch::<RunState>();
+                if run_state_check != RunState::GameOver {
+                    run_state = RunState::AwaitingInput;
+                } else {
+                    run_state = RunState::GameOver;
+                }
+            }
+
+            RunState::MoveLevel(next_level) => {
+                let beyond_dungeon = next_level.branch == MAIN_BRANCH
+                    && next_level.depth >= self.dungeon_layout.depth_num();
+                if beyond_dungeon {
+                    if self.game_won {
+                        run_state = RunState::Won;
+                    } else {
+                        let mut gamelog = self.ecs.write_resource::<GameLog>();
+                        gamelog
+                            .entries
+                            .push("You must kill the Mighty Slime to finish the game!".to_string());
+                        run_state = RunState::PlayerTurn;
+                    }
+                } else if let Err(e) = self.player_move_level(next_level) {
+                    println!("ERROR: {}", e);
+                    let mut gamelog = self.ecs.write_resource::<GameLog>();
+                    gamelog
+                        .entries
+                        .push("Something blocks the stairs".to_string());
+                    run_state = RunState::AwaitingInput;
+                } else {
+                    self.move_player_to_safe_position();
+                    run_state = RunState::PreRun;
+                }
+            }
+
+            _ => {}
+        }
+        run_state
+    }
+
+    /// Loads raws, spawn tables, vaults, generator presets and dungeon layout from default paths.
+    pub fn load_game_data(&mut self) -> Result<()> {
+        raws::load_raws(raws::RAWS_DIR)?;
+        spawn_tables::load_spawn_tables(spawn_tables::SPAWN_TABLES_PATH)?;
+        vaults::load_vaults(vaults::VAULTS_PATH).map_err(levels::Error::from)?;
+        presets::load_presets(presets::PRESETS_PATH)?;
+        self.dungeon_layout = DungeonLayout::load_from_file(DUNGEON_LAYOUT_PATH)?;
+        Ok(())
+    }
+
+    /// Resets world to start of new run with given seed (game data has to be loaded).
+    pub fn start_new_run(&mut self, seed: u64) {
+        self.seed = seed;
+        self.game_over_cleanup();
+        *self.ecs.write_resource::<RunState>() = RunState::PreRun;
+    }
+
+    /// Starts new run with seed from replay file, recorded input is used instead of player's one.
+    pub fn start_replay(&mut self, path: &str) -> Result<()> {
+        let seed = self.replay.start_playing(path)?;
+        self.start_new_run(seed);
+        Ok(())
+    }
+
+    /// true if input (keys and mouse clicks) is read in this run state,
+    /// only input from these ticks is recorded (and replayed)
+    fn waits_for_player_input(&self, run_state: RunState) -> bool {
+        match run_state {
+            RunState::AwaitingInput => {
+                let player = *self.ecs.fetch::<Entity>();
+                let sleeping_effects = self.ecs.read_storage::<components::SleepingEffect>();
+                !sleeping_effects.contains(player)
+            }
+            RunState::ShowInventory
+            | RunState::ShowEquipment
+            | RunState::ShowItemActions(_)
+            | RunState::Targeting(_)
+            | RunState::GameOver
+            | RunState::Won => true,
+            _ => false,
+        }
+    }
+
     pub fn draw_graphics(&self, ctx: &mut Rltk) {
         let run_state = *self.ecs.fetch::<RunState>();
 
@@ -440,7 +799,7 @@ impl State {
         match run_state {
             RunState::MainMenu | RunState::Controls => {}
             #[cfg(feature = "map_gen_testing")]
-            RunState::MapGenTesting(_) => {}
+            RunState::MapGenTesting(_) | RunState::MapEditor => {}
             _ => {
                 self.draw_game_graphics(ctx);
             }
@@ -482,10 +841,18 @@ impl GameState for State {
 
         self.draw_graphics(ctx);
 
+        if self.waits_for_player_input(run_state) {
+            if let Some(target) = self.replay.process_input_tick(ctx) {
+                self.targeting_pos = target;
+            }
+        }
+
         match run_state {
-            RunState::PreRun => {
-                self.run_all_gameplay_systems();
-                run_state = RunState::AwaitingInput;
+            RunState::PreRun
+            | RunState::PlayerTurn
+            | RunState::MonsterTurn
+            | RunState::MoveLevel(_) => {
+                run_state = self.run_turn_state(run_state);
             }
 
             RunState::AwaitingInput => {
@@ -545,17 +912,7 @@ impl GameState for State {
                     }
                     ItemMenuAction::Equip(item) => {
                         // TODO add limb selection menu here
-                        let target;
-                        {
-                            let equipables = self.ecs.read_storage::<components::Equippable>();
-                            let equipable = equipables.get(item).unwrap();
-                            target = if equipable.body_part == BodyPart::OneHanded {
-                                BodyPart::HandRight
-                            } else {
-                                equipable.body_part
-                            };
-                        }
-
+                        let target = self.default_body_part(item);
                         self.equip_item(item, target);
                         run_state = RunState::PlayerTurn;
                     }
@@ -581,27 +938,6 @@ impl GameState for State {
                 }
             }
 
-            RunState::PlayerTurn => {
-                self.run_all_gameplay_systems();
-                let run_state_check = *self.ecs.fetch::<RunState>();
-
-                if run_state_check != RunState::GameOver {
-                    run_state = RunState::MonsterTurn;
-                } else {
-                    run_state = RunState::GameOver;
-                }
-            }
-
-            RunState::MonsterTurn => {
-                self.run_all_gameplay_systems();
-                let run_state_check = *self.ecs.fetch::<RunState>();
-                if run_state_check != RunState::GameOver {
-                    run_state = RunState::AwaitingInput;
-                } else {
-                    run_state = RunState::GameOver;
-                }
-            }
-
             RunState::MainMenu => {
                 let main_menu_action = self.main_menu.draw(ctx);
                 match main_menu_action {
@@ -609,12 +945,30 @@ impl GameState for State {
                         run_state = RunState::MainMenu;
                     }
                     gui::MainMenuAction::Selected(selected) => match selected {
-                        gui::MainMenuSelection::NewGame => {
+                        gui::MainMenuSelection::NewGame | gui::MainMenuSelection::Seed => {
                             run_state = {
+                                self.seed = self.main_menu.seed().unwrap_or_else(rng::random_seed);
                                 self.game_over_cleanup();
+                                if let Err(e) =
+                                    self.replay.start_recording(REPLAY_FILE_PATH, self.seed)
+                                {
+                                    println!("ERROR: {}", e);
+                                }
                                 RunState::PreRun
                             }
                         }
+                        gui::MainMenuSelection::Continue => {
+                            if saveload::save_file_exists() {
+                                match saveload::load_game(self) {
+                                    Ok(()) => run_state = *self.ecs.fetch::<RunState>(),
+                                    Err(e) => {
+                                        println!("ERROR: {}", e);
+                                        self.game_over_cleanup();
+                                        run_state = RunState::MainMenu;
+                                    }
+                                }
+                            }
+                        }
                         #[cfg(feature = "map_gen_testing")]
                         gui::MainMenuSelection::MapGenTesting => {
                             self.gui_drawer.map_gen_testing_manager.reset();
@@ -628,33 +982,26 @@ impl GameState for State {
                 }
             }
 
-            RunState::MoveLevel(next_level) => {
-                if next_level == self.level_order.len() {
-                    if self.game_won {
-                        run_state = RunState::Won;
-                    } else {
-                        let mut gamelog = self.ecs.write_resource::<GameLog>();
-                        gamelog
-                            .entries
-                            .push("You must kill the Mighty Slime to finish the game!".to_string());
-                        run_state = RunState::PlayerTurn;
-
-                        let mut positions = self.ecs.write_storage::<components::Position>();
-                        let player = self.ecs.fetch::<Entity>();
-                        let mut pos = positions.get_mut(*player).unwrap();
-                        pos.level -= 1;
+            RunState::SaveGame => {
+                // game will be continued from awaiting player input
+                *self.ecs.write_resource::<RunState>() = RunState::AwaitingInput;
+                // replayed run would overwrite save of player's own run
+                if !self.replay.is_replayed_run() {
+                    if let Err(e) = saveload::save_game(self) {
+                        println!("ERROR: {}", e);
                     }
-                } else {
-                    self.player_move_level(next_level);
-                    run_state = RunState::PreRun;
                 }
+                run_state = RunState::MainMenu;
             }
 
             RunState::GameOver => {
-                let result = self.gui_drawer.game_over(ctx);
+                let result = self.gui_drawer.game_over(ctx, self.seed);
                 match result {
                     PopuSelection::NoSelection => {}
                     PopuSelection::QuitToMenu => {
+                        if !self.replay.is_replayed_run() {
+                            saveload::delete_save_file();
+                        }
                         self.game_over_cleanup();
                         run_state = RunState::MainMenu;
                     }
@@ -662,10 +1009,13 @@ impl GameState for State {
             }
 
             RunState::Won => {
-                let result = self.gui_drawer.game_won(ctx);
+                let result = self.gui_drawer.game_won(ctx, self.seed);
                 match result {
                     PopuSelection::NoSelection => {}
                     PopuSelection::QuitToMenu => {
+                        if !self.replay.is_replayed_run() {
+                            saveload::delete_save_file();
+                        }
                         self.game_over_cleanup();
                         run_state = RunState::MainMenu;
                     }
@@ -694,6 +1044,22 @@ impl GameState for State {
                     run_state = print_map_testing_menu(self, ctx);
                 }
             }
+
+            #[cfg(feature = "map_gen_testing")]
+            RunState::MapEditor => {
+                run_state = match self
+                    .gui_drawer
+                    .map_gen_testing_manager
+                    .map_editor
+                    .update(ctx, self.window_height)
+                {
+                    MapEditorAction::Close => RunState::MapGenTesting(true),
+                    MapEditorAction::NoResponse => RunState::MapEditor,
+                };
+            }
+        }
+        if run_state == RunState::MainMenu {
+            self.replay.stop();
         }
         *self.ecs.write_resource::<RunState>() = run_state;
 
@@ -701,79 +1067,62 @@ impl GameState for State {
     }
 }
 
+/// Random tile inside of map, where player can stand without harm.
+fn random_start_position(map: &Map) -> (usize, usize) {
+    let mut p_x = 0;
+    let mut p_y = 0;
+    while map.tile_at_xy(p_x, p_y).blocks_movement() || map.tile_at_xy(p_x, p_y).is_hazard() {
+        p_x = rng::range(2, map.width_max() as i32 - 2) as usize;
+        p_y = rng::range(2, map.height_max() as i32 - 2) as usize;
+    }
+    (p_x, p_y)
+}
+
 #[cfg(feature = "map_gen_testing")]
 fn print_map_testing_menu(state: &mut State, ctx: &mut Rltk) -> RunState {
-    use crate::maps::generators::{
-        bsp::{interior::BSPInteriorGen, BSPConfig, BSPDungeonGen},
-        drunkard_walk::{DrunkardWalkConfig, DrunkardWalkGen},
-    };
-
-    let mut run_state = RunState::MapGenTesting(false);
+    use crate::maps::generators::{dla::DLAConfig, voronoi::VoronoiConfig, wfc::WFC_SAMPLE_PREFAB};
 
     let map_testing_action = state.gui_drawer.map_gen_testing_manager.update(ctx);
-    match map_testing_action {
-        MapGenTestingMenuAction::NoResponse => (),
-        MapGenTestingMenuAction::Cancel => run_state = RunState::MainMenu,
+    let level_type = match map_testing_action {
+        MapGenTestingMenuAction::NoResponse => return RunState::MapGenTesting(false),
+        MapGenTestingMenuAction::Cancel => return RunState::MainMenu,
         MapGenTestingMenuAction::SwitchShowSteps => {
             state.gui_drawer.map_gen_testing_manager.switch_show_steps();
             state.gui_drawer.map_gen_testing_manager.reset();
+            return RunState::MapGenTesting(false);
         }
         MapGenTestingMenuAction::TestBasicDungeonGenerator => {
-            state
-                .gui_drawer
-                .map_gen_testing_manager
-                .reset_map_gen(Box::new(BasicDungeonMap::new(
-                    state.window_width - 4,
-                    state.map_height - 4,
-                    BasicDungeonMapConfig::default(),
-                )));
-            run_state = RunState::MapGenTesting(true);
+            LevelType::BasicDungeon(Default::default())
         }
-        MapGenTestingMenuAction::TestCaMapGen => {
-            state
-                .gui_drawer
-                .map_gen_testing_manager
-                .reset_map_gen(Box::new(
-                    CAMapGen::new(state.window_width - 4, state.map_height - 4).unwrap(),
-                ));
-            run_state = RunState::MapGenTesting(true);
+        MapGenTestingMenuAction::TestCaMapGen => LevelType::Cave(Default::default()),
+        MapGenTestingMenuAction::TestBSPDungeonGen => LevelType::BSPDungeon(Default::default()),
+        MapGenTestingMenuAction::TestBSPInteriorGen => LevelType::BSPInterior(Default::default()),
+        MapGenTestingMenuAction::TestDrunkardWalkGen => LevelType::DrunkardWalk(Default::default()),
+        MapGenTestingMenuAction::TestMazeGen => LevelType::Maze(Default::default()),
+        MapGenTestingMenuAction::TestDLAGen => LevelType::DLA(DLAConfig::random_preset()),
+        MapGenTestingMenuAction::TestVoronoiGen => {
+            LevelType::Voronoi(VoronoiConfig::random_preset())
         }
-        MapGenTestingMenuAction::TestBSPDungeonGen => {
-            state
-                .gui_drawer
-                .map_gen_testing_manager
-                .reset_map_gen(Box::new(BSPDungeonGen::new(
-                    state.window_width - 4,
-                    state.map_height - 4,
-                    BSPConfig::default(),
-                )));
-            run_state = RunState::MapGenTesting(true);
+        MapGenTestingMenuAction::TestWfcGenWithBSPInteriorSample => LevelType::Wfc {
+            sample: Box::new(LevelType::BSPInterior(Default::default())),
+            config: Default::default(),
+        },
+        MapGenTestingMenuAction::TestWfcGenWithAsciiSample => LevelType::Wfc {
+            sample: Box::new(LevelType::Prefab(WFC_SAMPLE_PREFAB.to_string())),
+            config: Default::default(),
+        },
+        MapGenTestingMenuAction::TestPipelineGen => LevelType::eroded_bsp_dungeon(),
+        MapGenTestingMenuAction::TestTerrainGen => {
+            LevelType::Cave(Default::default()).with_terrain(Default::default())
         }
-        MapGenTestingMenuAction::TestBSPInteriorGen => {
-            state
-                .gui_drawer
-                .map_gen_testing_manager
-                .reset_map_gen(Box::new(BSPInteriorGen::new(
-                    state.window_width - 4,
-                    state.map_height - 4,
-                    BSPConfig::default(),
-                )));
-            run_state = RunState::MapGenTesting(true);
-        }
-        MapGenTestingMenuAction::TestDrunkardWalkGen => {
-            state
-                .gui_drawer
-                .map_gen_testing_manager
-                .reset_map_gen(Box::new(DrunkardWalkGen::new(
-                    state.window_width - 4,
-                    state.map_height - 4,
-                    DrunkardWalkConfig::default(),
-                )));
-            run_state = RunState::MapGenTesting(true);
-        }
-    }
+    };
 
-    run_state
+    state.gui_drawer.map_gen_testing_manager.test_level_type(
+        level_type,
+        state.window_width - 4,
+        state.map_height - 4,
+    );
+    RunState::MapGenTesting(true)
 }
 
 #[cfg(feature = "map_gen_testing")]
@@ -804,7 +1153,12 @@ fn print_tested_map(
         manager.current_history_index = current_index;
     }
 
-    draw_map_without_fov(&history[current_index].0, ctx);
+    let hidden_area = if manager.editing_config {
+        Some(manager.config_editor.area())
+    } else {
+        None
+    };
+    draw_map_without_fov(&history[current_index].0, hidden_area, ctx);
 
     let mut draw_batch = DrawBatch::new();
     draw_batch.target(SPRITE_16X16_CONSOLE_INDEX);
@@ -820,6 +1174,21 @@ fn print_tested_map(
         " ---- Generating Map Done. Press Spacebar generate new map. ----"
     };
 
+    let settings = &manager.settings;
+    ctx.print_color(
+        1,
+        window_height - 5,
+        rltk::RGB::named(rltk::WHITE),
+        rltk::RGB::named(rltk::BLACK),
+        format!(
+            "Seed: {}  Size: {}x{}  {}",
+            settings.seed,
+            settings.width,
+            settings.height,
+            manager.message.as_deref().unwrap_or("")
+        ),
+    );
+
     ctx.print_color(
         1,
         window_height - 4,
@@ -849,9 +1218,19 @@ fn print_tested_map(
         window_height - 1,
         rltk::RGB::named(rltk::WHITE),
         rltk::RGB::named(rltk::BLACK),
-        "Press EESCAPE to return to menu",
+        "Press ENTER to change generator settings, E to edit map, EESCAPE to return to menu",
     );
 
+    if manager.editing_config {
+        manager.update_config_editor(ctx);
+        return RunState::MapGenTesting(true);
+    }
+
+    if ctx.key == Some(rltk::VirtualKeyCode::E) {
+        manager.open_map_editor();
+        return RunState::MapEditor;
+    }
+
     let input = get_input(ctx);
     if let Some(key) = input {
         match key {
@@ -859,6 +1238,10 @@ fn print_tested_map(
                 manager.current_history_index += 1;
                 RunState::MapGenTesting(true)
             }
+            InputType::Enter => {
+                manager.open_config_editor();
+                RunState::MapGenTesting(true)
+            }
             InputType::Escape => RunState::MapGenTesting(false),
             _ => RunState::MapGenTesting(true),
         }
@@ -866,3 +1249,87 @@ fn print_tested_map(
         RunState::MapGenTesting(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use crate::ecs::{components, headless::HeadlessGame};
+    use crate::levels::dungeon_layout::LevelKey;
+    use crate::maps::TileType;
+    use crate::spawner::{placement::SpawnPlacement, spawn_tables};
+
+    #[test]
+    fn monsters_dont_spawn_next_to_start_of_run() {
+        for seed in 0..5 {
+            let game = HeadlessGame::new(80, 52, seed).unwrap();
+            let ecs = &game.state.ecs;
+            let level = &game.state.level_manager.levels[0];
+            let start = level.start.unwrap();
+            let player = *ecs.fetch::<Entity>();
+            let positions = ecs.read_storage::<components::Position>();
+            let player_pos = positions.get(player).unwrap();
+            assert_eq!((player_pos.x, player_pos.y), start);
+
+            let rules = spawn_tables::get_spawn_table(&level.spawn_table)
+                .unwrap()
+                .placement;
+            let placement = SpawnPlacement::new(&level.map, &rules, &[start]);
+            let ais = ecs.read_storage::<components::AI>();
+            for (pos, _ai) in (&positions, &ais).join() {
+                if pos.level == 0 {
+                    assert!(!placement.is_safe_zone(pos.x, pos.y), "seed {}", seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn landings_below_chasms_are_arrival_points() {
+        let mut game = HeadlessGame::new(80, 52, 3).unwrap();
+        let map = &mut game.state.level_manager.levels[0].map;
+        let chasm = (0..map.tiles.len())
+            .map(|i| map.index_to_xy(i))
+            .find(|&(x, y)| map.tile_at_xy(x, y) == TileType::Floor)
+            .unwrap();
+        let index = map.xy_to_index(chasm.0, chasm.1);
+        map.tiles[index] = TileType::Chasm;
+
+        let index = game.state.get_or_create_level(LevelKey::main(1)).unwrap();
+        let level = &game.state.level_manager.levels[index];
+        let landing = level.map.closest_safe_position(chasm).unwrap();
+
+        assert_eq!(level.start, None);
+        assert_eq!(game.state.arrival_points(index), vec![landing]);
+    }
+
+    #[test]
+    fn level_doesnt_depend_on_order_of_visiting_levels() {
+        let key = LevelKey::main(2);
+
+        let mut direct = HeadlessGame::new(80, 52, 42).unwrap();
+        let index = direct.state.get_or_create_level(key).unwrap();
+        let direct_level = direct.state.level_manager.levels[index].clone();
+
+        let mut through_branch = HeadlessGame::new(80, 52, 42).unwrap();
+        through_branch
+            .state
+            .get_or_create_level(LevelKey {
+                branch: 1,
+                depth: 3,
+            })
+            .unwrap();
+        let index = through_branch.state.get_or_create_level(key).unwrap();
+        let level = &through_branch.state.level_manager.levels[index];
+
+        assert_eq!(level.map.tiles, direct_level.map.tiles);
+        assert_eq!(level.spawn_areas, direct_level.spawn_areas);
+        assert_eq!(level.spawn_report, direct_level.spawn_report);
+
+        let other_seed = HeadlessGame::new(80, 52, 43).unwrap();
+        assert_ne!(
+            other_seed.state.level_manager.levels[0].map.tiles,
+            direct.state.level_manager.levels[0].map.tiles
+        );
+    }
+}
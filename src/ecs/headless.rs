@@ -0,0 +1,310 @@
+use rltk::Point;
+use snafu::ensure;
+use specs::prelude::*;
+
+use crate::ecs::{
+    components::{self, BodyPart},
+    errors::{
+        ItemNeedsTarget, ItemNotEquippable, ItemNotInInventory, NoTurnProgress, Result,
+        UnexpectedRunState,
+    },
+    game_state::{GameLog, RunState},
+    systems::player::{self, input, Dir, InputType},
+    State,
+};
+use crate::graphics::GuiDrawer;
+use crate::levels::level::Level;
+use crate::CONSOLE_BOX_HEIGHT;
+
+/// Action player can take in one turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerAction {
+    Move(Dir),
+    Wait,
+    PickUp,
+    GoDown,
+    GoUp,
+    /// `target` is needed for ranged items
+    UseItem {
+        item: Entity,
+        target: Option<Point>,
+    },
+    DropItem(Entity),
+    /// if `body_part` is None, the same one as in inventory menu is used
+    Equip {
+        item: Entity,
+        body_part: Option<BodyPart>,
+    },
+    UnEquip(Entity),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// action didn't take a turn (e.g. moving into wall), game still waits for action
+    NoTurn,
+    /// player and monsters took their turns, game waits for next action
+    TurnPassed,
+    /// player was sleeping, turn passed without performing action
+    Slept,
+    GameOver,
+    Won,
+}
+
+/// `step` fails after this many actions in a row which didn't take a turn,
+/// so driver (e.g. random bot) which is stuck doesn't loop forever.
+pub const MAX_ACTIONS_WITHOUT_TURN: usize = 1000;
+
+/// Runs game without rltk window (and without `GameState::tick`).
+/// Gameplay is driven by `step`, which plays whole turn for given player action.
+pub struct HeadlessGame {
+    pub state: State,
+    actions_without_turn: usize,
+}
+
+impl HeadlessGame {
+    /// Loads game data from default paths and starts new run.
+    pub fn new(map_width: usize, map_height: usize, seed: u64) -> Result<HeadlessGame> {
+        let window_height = map_height + CONSOLE_BOX_HEIGHT;
+        let gui_drawer = GuiDrawer::new(map_width, window_height, CONSOLE_BOX_HEIGHT);
+        let mut state = State::new(map_width, window_height, map_width, map_height, gui_drawer);
+        state.register_all_components();
+        state.load_game_data()?;
+
+        state.ecs.insert(RunState::PreRun);
+        state.start_new_run(seed);
+
+        let mut game = HeadlessGame {
+            state,
+            actions_without_turn: 0,
+        };
+        game.run_until_player_input()?;
+        Ok(game)
+    }
+
+    pub fn run_state(&self) -> RunState {
+        *self.state.ecs.fetch::<RunState>()
+    }
+
+    pub fn player(&self) -> Entity {
+        *self.state.ecs.fetch::<Entity>()
+    }
+
+    pub fn player_pos(&self) -> Point {
+        *self.state.ecs.fetch::<Point>()
+    }
+
+    pub fn player_hp(&self) -> Option<components::Hp> {
+        self.state
+            .ecs
+            .read_storage::<components::Hp>()
+            .get(self.player())
+            .cloned()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.state.ecs.fetch::<Level>().depth
+    }
+
+    pub fn game_log(&self) -> Vec<String> {
+        self.state.ecs.fetch::<GameLog>().entries.clone()
+    }
+
+    /// Items in player's inventory (equipped ones included).
+    pub fn inventory_items(&self) -> Vec<Entity> {
+        let player = self.player();
+        let entities = self.state.ecs.entities();
+        let in_inventory = self.state.ecs.read_storage::<components::InInventory>();
+        (&entities, &in_inventory)
+            .join()
+            .filter(|(_e, in_inv)| in_inv.owner == player)
+            .map(|(e, _in_inv)| e)
+            .collect()
+    }
+
+    /// Performs action and runs game until it waits for next player action.
+    /// Action which doesn't take a turn (e.g. going up where there are no stairs)
+    /// returns `StepResult::NoTurn`, after `MAX_ACTIONS_WITHOUT_TURN` of them in a row
+    /// step fails with `Error::NoTurnProgress`.
+    pub fn step(&mut self, action: PlayerAction) -> Result<StepResult> {
+        match self.run_state() {
+            RunState::AwaitingInput => {}
+            RunState::GameOver => return Ok(StepResult::GameOver),
+            RunState::Won => return Ok(StepResult::Won),
+            run_state => {
+                return UnexpectedRunState {
+                    run_state: format!("{:?}", run_state),
+                }
+                .fail()
+            }
+        }
+
+        if player::try_sleep_turn(&mut self.state) {
+            self.set_run_state(RunState::PlayerTurn);
+            return match self.run_until_player_input()? {
+                StepResult::TurnPassed => Ok(StepResult::Slept),
+                result => Ok(result),
+            };
+        }
+
+        let run_state = self.perform_action(action)?;
+        self.set_run_state(run_state);
+        if run_state == RunState::AwaitingInput {
+            self.actions_without_turn += 1;
+            ensure!(
+                self.actions_without_turn <= MAX_ACTIONS_WITHOUT_TURN,
+                NoTurnProgress {
+                    actions: self.actions_without_turn
+                }
+            );
+            return Ok(StepResult::NoTurn);
+        }
+        self.actions_without_turn = 0;
+        self.run_until_player_input()
+    }
+
+    fn perform_action(&mut self, action: PlayerAction) -> Result<RunState> {
+        let input = match action {
+            PlayerAction::Move(dir) => dir_to_input(dir),
+            PlayerAction::Wait => InputType::Center,
+            PlayerAction::PickUp => InputType::PickUpItem,
+            PlayerAction::GoDown => InputType::DownLevel,
+            PlayerAction::GoUp => InputType::UpLevel,
+            PlayerAction::UseItem { item, target } => {
+                self.ensure_in_inventory(item)?;
+                let is_ranged = self
+                    .state
+                    .ecs
+                    .read_storage::<components::Ranged>()
+                    .contains(item);
+                match target {
+                    Some(target) => {
+                        self.state.targeting_pos = target;
+                        self.state.use_item(item, true);
+                    }
+                    None => {
+                        ensure!(!is_ranged, ItemNeedsTarget { item });
+                        self.state.use_item(item, false);
+                    }
+                }
+                return Ok(RunState::PlayerTurn);
+            }
+            PlayerAction::DropItem(item) => {
+                self.ensure_in_inventory(item)?;
+                self.state.drop_item(item);
+                return Ok(RunState::PlayerTurn);
+            }
+            PlayerAction::Equip { item, body_part } => {
+                self.ensure_in_inventory(item)?;
+                let is_equippable = self
+                    .state
+                    .ecs
+                    .read_storage::<components::Equippable>()
+                    .contains(item);
+                ensure!(is_equippable, ItemNotEquippable { item });
+                let body_part = body_part.unwrap_or_else(|| self.state.default_body_part(item));
+                self.state.equip_item(item, body_part);
+                return Ok(RunState::PlayerTurn);
+            }
+            PlayerAction::UnEquip(item) => {
+                self.ensure_in_inventory(item)?;
+                self.state.unequip_item(item);
+                return Ok(RunState::PlayerTurn);
+            }
+        };
+
+        {
+            let player = self.player();
+            let mut players = self.state.ecs.write_storage::<components::Player>();
+            players.get_mut(player).unwrap().input = Some(input);
+        }
+        Ok(input::try_handle_input(&mut self.state))
+    }
+
+    fn ensure_in_inventory(&self, item: Entity) -> Result<()> {
+        let in_inventory = self.state.ecs.read_storage::<components::InInventory>();
+        ensure!(
+            in_inventory
+                .get(item)
+                .is_some_and(|in_inv| in_inv.owner == self.player()),
+            ItemNotInInventory { item }
+        );
+        Ok(())
+    }
+
+    /// Same as end of `tick`.
+    fn set_run_state(&mut self, run_state: RunState) {
+        *self.state.ecs.write_resource::<RunState>() = run_state;
+        self.state.ecs.maintain();
+    }
+
+    fn run_until_player_input(&mut self) -> Result<StepResult> {
+        loop {
+            match self.run_state() {
+                RunState::AwaitingInput => return Ok(StepResult::TurnPassed),
+                RunState::GameOver => return Ok(StepResult::GameOver),
+                RunState::Won => return Ok(StepResult::Won),
+                run_state @ (RunState::PreRun
+                | RunState::PlayerTurn
+                | RunState::MonsterTurn
+                | RunState::MoveLevel(_)) => {
+                    let next_run_state = self.state.run_turn_state(run_state);
+                    self.set_run_state(next_run_state);
+                }
+                run_state => {
+                    return UnexpectedRunState {
+                        run_state: format!("{:?}", run_state),
+                    }
+                    .fail()
+                }
+            }
+        }
+    }
+}
+
+fn dir_to_input(dir: Dir) -> InputType {
+    match dir {
+        Dir::Center => InputType::Center,
+        Dir::Left => InputType::Left,
+        Dir::Right => InputType::Right,
+        Dir::Up => InputType::Up,
+        Dir::Down => InputType::Down,
+        Dir::UpLeft => InputType::UpLeft,
+        Dir::UpRight => InputType::UpRight,
+        Dir::DownLeft => InputType::DownLeft,
+        Dir::DownRight => InputType::DownRight,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::errors::Error;
+
+    #[test]
+    fn actions_without_turn_fail_after_limit() {
+        let mut game = HeadlessGame::new(80, 52, 7).unwrap();
+        // there are no up stairs on the first level
+        for _ in 0..MAX_ACTIONS_WITHOUT_TURN {
+            assert_eq!(game.step(PlayerAction::GoUp).unwrap(), StepResult::NoTurn);
+        }
+
+        assert!(matches!(
+            game.step(PlayerAction::GoUp),
+            Err(Error::NoTurnProgress { .. })
+        ));
+    }
+
+    #[test]
+    fn turn_resets_actions_without_turn() {
+        let mut game = HeadlessGame::new(80, 52, 7).unwrap();
+        for _ in 0..MAX_ACTIONS_WITHOUT_TURN {
+            game.step(PlayerAction::GoUp).unwrap();
+        }
+        assert_eq!(
+            game.step(PlayerAction::Wait).unwrap(),
+            StepResult::TurnPassed
+        );
+
+        assert_eq!(game.step(PlayerAction::GoUp).unwrap(), StepResult::NoTurn);
+    }
+}
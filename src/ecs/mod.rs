@@ -1,6 +1,9 @@
 pub mod components;
 pub mod errors;
 pub mod game_state;
+pub mod headless;
+pub mod replay;
+pub mod saveload;
 pub mod systems;
 
 pub use game_state::State;
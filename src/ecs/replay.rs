@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::Write;
+
+use rltk::{Point, Rltk, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+
+use crate::ecs::{
+    errors::{ReplayFileEmpty, ReplayFileIo, ReplayFileSerde, Result},
+    systems::player::{input::get_input, InputType},
+};
+
+/// Every new run is recorded here.
+pub const REPLAY_FILE_PATH: &str = "./replay.jsonl";
+
+/// Replay file is json lines: header first, then one event per line
+/// (so file is usable even if game crashed in the middle of run).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReplayHeader {
+    seed: u64,
+}
+
+/// Input read by game in tick which was waiting for player input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    /// `letter` is set for letter keys (they are used to select options in menus)
+    Key {
+        input: InputType,
+        letter: Option<i32>,
+    },
+    /// targeting position selected with mouse
+    Target { x: i32, y: i32 },
+}
+
+impl ReplayEvent {
+    pub fn from_key(ctx: &mut Rltk) -> Option<ReplayEvent> {
+        let key = ctx.key?;
+        let input = get_input(ctx)?;
+        let letter = rltk::letter_to_option(key);
+        Some(ReplayEvent::Key {
+            input,
+            letter: (letter >= 0).then_some(letter),
+        })
+    }
+
+    /// Key which is read as this event.
+    pub fn to_key(self) -> Option<VirtualKeyCode> {
+        match self {
+            ReplayEvent::Key {
+                letter: Some(letter),
+                ..
+            } => letter_to_key(letter),
+            ReplayEvent::Key { input, .. } => input_to_key(input),
+            ReplayEvent::Target { .. } => Some(VirtualKeyCode::Return),
+        }
+    }
+}
+
+fn input_to_key(input: InputType) -> Option<VirtualKeyCode> {
+    match input {
+        InputType::Up => Some(VirtualKeyCode::Up),
+        InputType::Down => Some(VirtualKeyCode::Down),
+        InputType::Left => Some(VirtualKeyCode::Left),
+        InputType::Right => Some(VirtualKeyCode::Right),
+        InputType::UpLeft => Some(VirtualKeyCode::Numpad7),
+        InputType::UpRight => Some(VirtualKeyCode::Numpad9),
+        InputType::DownLeft => Some(VirtualKeyCode::Numpad1),
+        InputType::DownRight => Some(VirtualKeyCode::Numpad3),
+        InputType::Center => Some(VirtualKeyCode::Numpad5),
+        InputType::PickUpItem => Some(VirtualKeyCode::G),
+        InputType::ShowInventory => Some(VirtualKeyCode::I),
+        InputType::ShowEquipment => Some(VirtualKeyCode::E),
+        InputType::Escape => Some(VirtualKeyCode::Escape),
+        InputType::Enter => Some(VirtualKeyCode::Return),
+        InputType::Spacebar => Some(VirtualKeyCode::Space),
+        InputType::DownLevel => Some(VirtualKeyCode::Period),
+        InputType::UpLevel => Some(VirtualKeyCode::Comma),
+        InputType::CloseDoor => Some(VirtualKeyCode::C),
+        InputType::D => Some(VirtualKeyCode::D),
+        InputType::U => Some(VirtualKeyCode::U),
+        // any key without meaning in game
+        InputType::UnhandledInput => Some(VirtualKeyCode::F12),
+        InputType::NoInput => None,
+    }
+}
+
+fn letter_to_key(letter: i32) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    LETTERS.get(letter as usize).copied()
+}
+
+pub enum ReplayMode {
+    Off,
+    Recording {
+        file: File,
+    },
+    Playing {
+        events: VecDeque<ReplayEvent>,
+    },
+    /// all events were played, replayed run continues with player's input (not recorded)
+    Finished,
+}
+
+pub struct Replay {
+    pub mode: ReplayMode,
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay {
+            mode: ReplayMode::Off,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.mode, ReplayMode::Playing { .. })
+    }
+
+    /// Run was started from replay file (its events can be all played already).
+    pub fn is_replayed_run(&self) -> bool {
+        matches!(self.mode, ReplayMode::Playing { .. } | ReplayMode::Finished)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, ReplayMode::Recording { .. })
+    }
+
+    pub fn stop(&mut self) {
+        self.mode = ReplayMode::Off;
+    }
+
+    /// Creates new replay file (previous one is overwritten).
+    pub fn start_recording(&mut self, path: &str, seed: u64) -> Result<()> {
+        let mut file = File::create(path).context(ReplayFileIo { path })?;
+        let header = serde_json::to_string(&ReplayHeader { seed }).context(ReplayFileSerde {
+            path,
+            line: 1_usize,
+        })?;
+        writeln!(file, "{}", header).context(ReplayFileIo { path })?;
+        self.mode = ReplayMode::Recording { file };
+        Ok(())
+    }
+
+    pub fn record(&mut self, event: ReplayEvent) {
+        if let ReplayMode::Recording { file } = &mut self.mode {
+            let written = serde_json::to_string(&event)
+                .map_err(|e| e.to_string())
+                .and_then(|line| writeln!(file, "{}", line).map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                println!(
+                    "ERROR: Unable to record replay event, recording stopped: {}",
+                    e
+                );
+                self.stop();
+            }
+        }
+    }
+
+    /// Returns seed of recorded run, events are played with `next_event`.
+    pub fn start_playing(&mut self, path: &str) -> Result<u64> {
+        let contents = fs::read_to_string(path).context(ReplayFileIo { path })?;
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_i, l)| !l.trim().is_empty());
+
+        let (_i, header) = lines.next().context(ReplayFileEmpty { path })?;
+        let header: ReplayHeader = serde_json::from_str(header).context(ReplayFileSerde {
+            path,
+            line: 1_usize,
+        })?;
+
+        let mut events = VecDeque::new();
+        for (i, line) in lines {
+            let event =
+                serde_json::from_str(line).context(ReplayFileSerde { path, line: i + 1 })?;
+            events.push_back(event);
+        }
+
+        self.mode = ReplayMode::Playing { events };
+        Ok(header.seed)
+    }
+
+    /// Next recorded event, replay is finished after last one.
+    pub fn next_event(&mut self) -> Option<ReplayEvent> {
+        if let ReplayMode::Playing { events } = &mut self.mode {
+            let event = events.pop_front();
+            if events.is_empty() {
+                self.mode = ReplayMode::Finished;
+            }
+            event
+        } else {
+            None
+        }
+    }
+
+    /// Sets input of `ctx` for tick which waits for player input.
+    /// While recording current input is saved, while playing it is replaced by recorded one
+    /// (returned target has to be set as `State::targeting_pos`).
+    pub fn process_input_tick(&mut self, ctx: &mut Rltk) -> Option<Point> {
+        match self.mode {
+            ReplayMode::Off | ReplayMode::Finished => None,
+            ReplayMode::Recording { .. } => {
+                if let Some(event) = ReplayEvent::from_key(ctx) {
+                    self.record(event);
+                }
+                None
+            }
+            ReplayMode::Playing { .. } => {
+                ctx.left_click = false;
+                let event = self.next_event();
+                ctx.key = event.and_then(|e| e.to_key());
+                match event {
+                    Some(ReplayEvent::Target { x, y }) => Some(Point::new(x, y)),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::errors::Error;
+
+    fn temp_replay_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("roguelike_{}_{}.jsonl", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn empty_ctx() -> Rltk {
+        Rltk {
+            width_pixels: 0,
+            height_pixels: 0,
+            original_height_pixels: 0,
+            original_width_pixels: 0,
+            fps: 0.0,
+            frame_time_ms: 0.0,
+            active_console: 0,
+            key: None,
+            mouse_pos: (0, 0),
+            left_click: false,
+            shift: false,
+            control: false,
+            alt: false,
+            web_button: None,
+            quitting: false,
+            post_scanlines: false,
+            post_screenburn: false,
+        }
+    }
+
+    #[test]
+    fn played_replay_gives_recorded_input() {
+        let path = temp_replay_path("playback");
+        let events = [
+            ReplayEvent::Key {
+                input: InputType::UpLeft,
+                letter: None,
+            },
+            ReplayEvent::Key {
+                input: InputType::DownLevel,
+                letter: None,
+            },
+            ReplayEvent::Key {
+                input: InputType::ShowInventory,
+                letter: Some(8),
+            },
+            ReplayEvent::Key {
+                input: InputType::UnhandledInput,
+                letter: Some(1),
+            },
+            ReplayEvent::Target { x: 3, y: 7 },
+            ReplayEvent::Key {
+                input: InputType::Escape,
+                letter: None,
+            },
+        ];
+        let mut recording = Replay::new();
+        recording.start_recording(&path, 42).unwrap();
+        for event in events.iter() {
+            recording.record(*event);
+        }
+        recording.stop();
+
+        let mut replay = Replay::new();
+        assert_eq!(replay.start_playing(&path).unwrap(), 42);
+        let _ = fs::remove_file(&path);
+        let mut ctx = empty_ctx();
+        for event in events.iter() {
+            let target = replay.process_input_tick(&mut ctx);
+            match event {
+                ReplayEvent::Target { x, y } => assert_eq!(target, Some(Point::new(*x, *y))),
+                // keys are read by the same function as while recording
+                _ => assert_eq!(ReplayEvent::from_key(&mut ctx).as_ref(), Some(event)),
+            }
+        }
+
+        assert!(!replay.is_playing());
+        assert!(replay.is_replayed_run());
+        assert_eq!(replay.process_input_tick(&mut ctx), None);
+    }
+
+    #[test]
+    fn invalid_replay_line_is_reported() {
+        let path = temp_replay_path("invalid");
+        fs::write(
+            &path,
+            "{\"seed\":1}\n{\"Target\":{\"x\":1,\"y\":2}}\nnot json\n",
+        )
+        .unwrap();
+
+        let result = Replay::new().start_playing(&path);
+        let _ = fs::remove_file(&path);
+
+        assert!(matches!(
+            result,
+            Err(Error::ReplayFileSerde { line: 3, .. })
+        ));
+    }
+}
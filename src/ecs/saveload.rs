@@ -0,0 +1,339 @@
+use std::convert::Infallible;
+use std::fs;
+use std::path::Path;
+
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+use specs::prelude::*;
+use specs::saveload::{
+    ConvertSaveload, DeserializeComponents, Marker, MarkerAllocator, SerializeComponents,
+    SimpleMarker, SimpleMarkerAllocator,
+};
+
+use crate::ecs::{
+    components::{self, SerializeMe},
+    errors::{Result, SaveFileIo, SaveFileSerde, SaveFileUnknownEntity},
+    game_state::{GameLog, RunState},
+    systems, State,
+};
+use crate::levels::{level::Level, level_manager::LevelManager};
+
+pub const SAVE_FILE_PATH: &str = "./savegame.json";
+
+pub type SaveMarker = SimpleMarker<SerializeMe>;
+pub type SaveMarkerAllocator = SimpleMarkerAllocator<SerializeMe>;
+
+/// Calls `$callback` with list of all saved components.
+/// Order of components is order of storages in save file, so only add new ones at the end.
+macro_rules! with_saved_components {
+    ($callback:ident, $($arg:expr),*) => {
+        $callback!($($arg),*;
+            components::Position,
+            components::Renderable,
+            components::Player,
+            components::Movable,
+            components::AI,
+            components::View,
+            components::ViewMemory,
+            components::Name,
+            components::BlocksTile,
+            components::Hp,
+            components::CombatBaseStats,
+            components::WantsToMeleeAtack,
+            components::SufferDamage,
+            components::Item,
+            components::Heal,
+            components::InInventory,
+            components::WantsToPickupItem,
+            components::WantsToUseItem,
+            components::WantsToDropItem,
+            components::Usable,
+            components::Ranged,
+            components::InflictsDamage,
+            components::AreaOfEffect,
+            components::SleepingEffect,
+            components::Sleeping,
+            components::HealEffect,
+            components::Teleporting,
+            components::TeleportingEffect,
+            components::SpawnsAfterDeath,
+            components::Spawn,
+            components::Equippable,
+            components::Equipped,
+            components::WantsToEquip,
+            components::WantsToUnEquip,
+            components::BodyParts,
+            components::MeleeDamageBonus,
+            components::DefenseBonus,
+            components::Inventory,
+            components::FinalBoss,
+            components::Description,
+            // new storages go last, so older save files load with them empty
+            components::OpensDoors
+        )
+    };
+}
+
+macro_rules! serialize_storages {
+    ($ecs:expr; $($type:ty),*) => {{
+        let entities = $ecs.entities();
+        let markers = $ecs.read_storage::<SaveMarker>();
+        vec![$(
+            SerializeComponents::<Infallible, SaveMarker>::serialize(
+                &($ecs.read_storage::<$type>(),),
+                &entities,
+                &markers,
+                serde_json::value::Serializer,
+            )
+            .context(SaveFileSerde)?,
+        )*]
+    }};
+}
+
+macro_rules! deserialize_storages {
+    ($ecs:expr, $storages:expr; $($type:ty),*) => {{
+        let mut storages = $storages.into_iter();
+        $(
+            // storages missing in older save files are treated as empty
+            let data = storages
+                .next()
+                .unwrap_or_else(|| serde_json::Value::Array(vec![]));
+            DeserializeComponents::<Infallible, SaveMarker>::deserialize(
+                &mut ($ecs.write_storage::<$type>(),),
+                &$ecs.entities(),
+                &mut $ecs.write_storage::<SaveMarker>(),
+                &mut $ecs.write_resource::<SaveMarkerAllocator>(),
+                data,
+            )
+            .context(SaveFileSerde)?;
+        )*
+    }};
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveGame {
+    /// serialized component storages, in order of `with_saved_components`
+    storages: Vec<serde_json::Value>,
+    /// marker id of player entity
+    player: u64,
+    player_pos: Point,
+    run_state: <RunState as ConvertSaveload<SaveMarker>>::Data,
+    game_log: GameLog,
+    level_manager: LevelManager,
+    current_level: usize,
+    game_won: bool,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    turn: usize,
+}
+
+pub fn save_file_exists() -> bool {
+    Path::new(SAVE_FILE_PATH).exists()
+}
+
+pub fn delete_save_file() {
+    if save_file_exists() {
+        if let Err(e) = fs::remove_file(SAVE_FILE_PATH) {
+            println!("Unable to delete save file: {}", e);
+        }
+    }
+}
+
+pub fn save_game(gs: &mut State) -> Result<()> {
+    save_game_to(gs, SAVE_FILE_PATH)
+}
+
+pub fn save_game_to(gs: &mut State, path: &str) -> Result<()> {
+    // `Level` resource holds newest state of current level
+    if let Some(current_level) = gs.ecs.try_fetch::<Level>() {
+        gs.level_manager.levels[gs.current_level] = (*current_level).clone();
+    }
+
+    {
+        let entities = gs.ecs.entities();
+        let mut markers = gs.ecs.write_storage::<SaveMarker>();
+        let mut allocator = gs.ecs.write_resource::<SaveMarkerAllocator>();
+        for entity in entities.join() {
+            allocator.mark(entity, &mut markers);
+        }
+    }
+
+    let storages = with_saved_components!(serialize_storages, gs.ecs);
+
+    let save_game;
+    {
+        let markers = gs.ecs.read_storage::<SaveMarker>();
+        let player = *gs.ecs.fetch::<Entity>();
+        let run_state = *gs.ecs.fetch::<RunState>();
+
+        save_game = SaveGame {
+            storages,
+            player: markers.get(player).unwrap().id(),
+            player_pos: *gs.ecs.fetch::<Point>(),
+            run_state: run_state
+                .convert_into(|e| markers.get(e).cloned())
+                .unwrap_or_else(|e| match e {}),
+            game_log: GameLog {
+                entries: gs.ecs.fetch::<GameLog>().entries.clone(),
+            },
+            level_manager: gs.level_manager.clone(),
+            current_level: gs.current_level,
+            game_won: gs.game_won,
+            seed: gs.seed,
+            turn: gs.turn,
+        };
+    }
+
+    let contents = serde_json::to_string(&save_game).context(SaveFileSerde)?;
+    fs::write(path, contents).context(SaveFileIo { path })?;
+    Ok(())
+}
+
+pub fn load_game(gs: &mut State) -> Result<()> {
+    load_game_from(gs, SAVE_FILE_PATH)
+}
+
+pub fn load_game_from(gs: &mut State, path: &str) -> Result<()> {
+    let contents = fs::read_to_string(path).context(SaveFileIo { path })?;
+    let save_game: SaveGame = serde_json::from_str(&contents).context(SaveFileSerde)?;
+
+    gs.delete_all_entities();
+    gs.ecs.maintain();
+    gs.ecs.insert(SaveMarkerAllocator::new());
+
+    with_saved_components!(deserialize_storages, gs.ecs, save_game.storages);
+
+    let player;
+    let run_state;
+    {
+        let allocator = gs.ecs.fetch::<SaveMarkerAllocator>();
+        player = allocator
+            .retrieve_entity_internal(save_game.player)
+            .context(SaveFileUnknownEntity {
+                marker_id: save_game.player,
+            })?;
+        run_state = RunState::convert_from(save_game.run_state, |m| {
+            allocator.retrieve_entity_internal(m.id())
+        })
+        .unwrap_or_else(|e| match e {});
+    }
+
+    let mut level_manager = save_game.level_manager;
+    for level in level_manager.levels.iter_mut() {
+        level.map.tile_content = vec![vec![]; level.map.index_max()];
+    }
+    gs.level_manager = level_manager;
+    gs.ecs.remove::<Level>();
+    gs.set_level_as_curent(save_game.current_level);
+    gs.game_won = save_game.game_won;
+    gs.seed = save_game.seed;
+    gs.turn = save_game.turn;
+
+    gs.ecs.insert(player);
+    gs.ecs.insert(save_game.player_pos);
+    gs.ecs.insert(save_game.game_log);
+    gs.ecs.insert(run_state);
+
+    systems::map::MapIndexingSystem {}.run_now(&gs.ecs);
+    gs.ecs.maintain();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::BodyPart;
+    use crate::test_support::TestWorld;
+
+    fn temp_save_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("roguelike_{}_{}.json", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn entity_references_are_remapped_on_load() {
+        let path = temp_save_path("remap");
+        let mut world = TestWorld::new(20, 20);
+        let player = world.player();
+        let sword = world.spawn_equipped(player, "Short sword");
+        let hand = [BodyPart::HandRight, BodyPart::HandLeft]
+            .iter()
+            .copied()
+            .find(|&hand| world.equipped_in(player, hand) == Some(sword))
+            .unwrap();
+        world.spawn_in_inventory(player, "Health potion");
+        let orc = world.spawn("Orc", 2, 1);
+        world.want_melee(player, orc);
+        save_game_to(&mut world.gs, &path).unwrap();
+
+        // entities of fresh world get different ids
+        let mut loaded = TestWorld::new(20, 20);
+        for _ in 0..5 {
+            loaded.spawn("Goblin", 5, 5);
+        }
+        load_game_from(&mut loaded.gs, &path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let player = loaded.player();
+        let sword = loaded.named("Short sword")[0];
+        let potion = loaded.named("Health potion")[0];
+        let orc = loaded.named("Orc")[0];
+        assert!(loaded.named("Goblin").is_empty());
+        assert_eq!(loaded.equipped_in(player, hand), Some(sword));
+        let equipped = loaded.gs.ecs.read_storage::<components::Equipped>();
+        assert_eq!(equipped.get(sword).unwrap().owner, player);
+        let in_inventory = loaded.gs.ecs.read_storage::<components::InInventory>();
+        assert_eq!(in_inventory.get(potion).unwrap().owner, player);
+        let melee = loaded
+            .gs
+            .ecs
+            .read_storage::<components::WantsToMeleeAtack>();
+        assert_eq!(melee.get(player).unwrap().target, orc);
+    }
+
+    #[test]
+    fn save_without_newer_storages_is_loaded() {
+        let path = temp_save_path("old_save");
+        let mut world = TestWorld::new(20, 20);
+        world.spawn("Goblin", 3, 3);
+        save_game_to(&mut world.gs, &path).unwrap();
+
+        let mut loaded = TestWorld::new(20, 20);
+        load_game_from(&mut loaded.gs, &path).unwrap();
+        let goblin = loaded.named("Goblin")[0];
+        assert!(loaded
+            .gs
+            .ecs
+            .read_storage::<components::OpensDoors>()
+            .contains(goblin));
+
+        // save made before `OpensDoors` was saved
+        let mut save: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        save["storages"].as_array_mut().unwrap().pop();
+        fs::write(&path, save.to_string()).unwrap();
+
+        let mut loaded = TestWorld::new(20, 20);
+        load_game_from(&mut loaded.gs, &path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let goblin = loaded.named("Goblin")[0];
+        let positions = loaded.gs.ecs.read_storage::<components::Position>();
+        let position = positions.get(goblin).unwrap();
+        assert_eq!((position.x, position.y), (3, 3));
+        assert!(loaded
+            .gs
+            .ecs
+            .read_storage::<components::Hp>()
+            .contains(goblin));
+        assert!(!loaded
+            .gs
+            .ecs
+            .read_storage::<components::OpensDoors>()
+            .contains(goblin));
+    }
+}
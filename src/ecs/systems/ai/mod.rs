@@ -1,10 +1,12 @@
 pub mod ai_random_mov;
+pub mod off_screen;
 
 use specs::{Entities, Entity, Join, ReadExpect, ReadStorage, System, WriteExpect, WriteStorage};
 
 use crate::{
     ecs::{components, game_state::RunState},
     levels::level::Level,
+    maps::{DoorOpenerMap, DoorState, TerrainEffect, TileType},
 };
 
 pub struct AISystem {}
@@ -18,11 +20,13 @@ impl<'a> System<'a> for AISystem {
         ReadStorage<'a, components::AI>,
         ReadStorage<'a, components::Name>,
         ReadStorage<'a, components::BlocksTile>,
+        ReadStorage<'a, components::OpensDoors>,
         ReadExpect<'a, rltk::Point>,
         WriteExpect<'a, Level>,
         ReadExpect<'a, Entity>,
         WriteStorage<'a, components::WantsToMeleeAtack>,
         WriteStorage<'a, components::SleepingEffect>,
+        WriteStorage<'a, components::SufferDamage>,
         ReadExpect<'a, RunState>,
     );
 
@@ -34,11 +38,13 @@ impl<'a> System<'a> for AISystem {
             ais,
             names,
             tiles_blocks,
+            door_openers,
             player_position,
             mut current_level,
             player,
             mut wants_to_melee,
             mut sleeping_effects,
+            mut suffer_damage,
             runstate,
         ) = data;
 
@@ -46,10 +52,13 @@ impl<'a> System<'a> for AISystem {
             return;
         }
 
+        let mut door_opened = false;
+
         for (entity, mut view, mut pos, _ai, _name) in
             (&entities, &mut views, &mut positions, &ais, &names).join()
         {
             let mut can_act = true;
+            // other levels are caught up when player returns (see `off_screen`)
             if pos.level != current_level.level_index {
                 continue;
             }
@@ -67,13 +76,15 @@ impl<'a> System<'a> for AISystem {
             if can_act {
                 if view.visible_tiles.contains(&player_position) {
                     // following player
-                    let path = rltk::a_star_search(
-                        current_level.map.xy_to_index(pos.x, pos.y),
-                        current_level
-                            .map
-                            .xy_to_index(player_position.x as usize, player_position.y as usize),
-                        &current_level.map,
-                    );
+                    let start = current_level.map.xy_to_index(pos.x, pos.y);
+                    let end = current_level
+                        .map
+                        .xy_to_index(player_position.x as usize, player_position.y as usize);
+                    let path = if door_openers.contains(entity) {
+                        rltk::a_star_search(start, end, &DoorOpenerMap(&current_level.map))
+                    } else {
+                        rltk::a_star_search(start, end, &current_level.map)
+                    };
 
                     let distance = rltk::DistanceAlg::Pythagoras
                         .distance2d(rltk::Point::new(pos.x, pos.y), *player_position);
@@ -81,6 +92,14 @@ impl<'a> System<'a> for AISystem {
                         wants_to_melee
                             .insert(entity, components::WantsToMeleeAtack { target: *player })
                             .expect("Unable to insert attack on player!");
+                    } else if path.success
+                        && path.steps.len() > 1
+                        && current_level.map.tiles[path.steps[1]]
+                            == TileType::Door(DoorState::Closed)
+                    {
+                        // opening door takes whole turn
+                        current_level.map.tiles[path.steps[1]] = TileType::Door(DoorState::Open);
+                        door_opened = true;
                     } else if path.success && path.steps.len() > 1 {
                         let x = path.steps[1] % current_level.map.width;
                         let y = path.steps[1] / current_level.map.width;
@@ -94,10 +113,28 @@ impl<'a> System<'a> for AISystem {
                         pos.x = x;
                         pos.y = y;
 
+                        // chasms aren't in paths, only damage is left
+                        if let Some(TerrainEffect::Damage(damage)) =
+                            current_level.map.tiles[path.steps[1]].on_enter_effect()
+                        {
+                            components::SufferDamage::new_damage(
+                                &mut suffer_damage,
+                                entity,
+                                damage,
+                            );
+                        }
+
                         view.should_update = true;
                     }
                 }
             }
         }
+
+        // door changes what everyone sees
+        if door_opened {
+            for view in (&mut views).join() {
+                view.should_update = true;
+            }
+        }
     }
 }
@@ -0,0 +1,261 @@
+//! Levels which player left aren't run by `AISystem`, so their monsters are frozen.
+//! When player returns, turns which passed are caught up at once: sleeping effects expire,
+//! monsters heal, wander and regroup, and wandering monsters can come to the level.
+
+use std::collections::HashSet;
+
+use specs::prelude::*;
+
+use crate::{
+    ecs::{components, State},
+    levels::level::Level,
+    maps::{DoorState, Map, TileType},
+    rng,
+    spawner::{raws, spawn_entity, spawn_tables, spawn_tables::SpawnTable},
+};
+
+/// Monsters make at most this many moves, no matter how long player was away.
+pub const MAX_CATCH_UP_TURNS: usize = 100;
+/// Monsters heal 1 hp per this many turns.
+const HEAL_TURNS: usize = 10;
+/// One wandering monster can come per this many turns.
+const WANDERER_TURNS: usize = 200;
+const MAX_WANDERERS: usize = 3;
+/// Wandering monsters don't appear closer to player.
+const WANDERER_MIN_DISTANCE: usize = 10;
+/// Monsters farther from the nearest monster of the same kind go towards it.
+const REGROUP_DISTANCE: usize = 3;
+
+struct OffScreenMonster {
+    entity: Entity,
+    name: String,
+    pos: (usize, usize),
+    /// moves left after sleeping
+    moves: usize,
+    opens_doors: bool,
+    blocks_tile: bool,
+}
+
+/// Simulates `elapsed` turns of current level, player has to be already placed on it.
+pub fn catch_up_level(gs: &mut State, elapsed: usize) {
+    if elapsed == 0 {
+        return;
+    }
+    let mut monsters = rest_and_heal(gs, elapsed);
+    wander(gs, &mut monsters);
+    add_wandering_monsters(gs, elapsed);
+}
+
+/// Sleeping effects expire and monsters heal, returns monsters of current level.
+fn rest_and_heal(gs: &mut State, elapsed: usize) -> Vec<OffScreenMonster> {
+    let level_index = gs.ecs.fetch::<Level>().level_index;
+    let entities = gs.ecs.entities();
+    let positions = gs.ecs.read_storage::<components::Position>();
+    let ais = gs.ecs.read_storage::<components::AI>();
+    let names = gs.ecs.read_storage::<components::Name>();
+    let door_openers = gs.ecs.read_storage::<components::OpensDoors>();
+    let blockers = gs.ecs.read_storage::<components::BlocksTile>();
+    let mut hps = gs.ecs.write_storage::<components::Hp>();
+    let mut sleeping_effects = gs.ecs.write_storage::<components::SleepingEffect>();
+
+    let mut monsters = vec![];
+    for (entity, pos, _ai, name) in (&entities, &positions, &ais, &names).join() {
+        if pos.level != level_index {
+            continue;
+        }
+
+        let mut slept = 0;
+        if let Some(sleep) = sleeping_effects.get_mut(entity) {
+            slept = sleep.duration.min(elapsed);
+            sleep.duration -= slept;
+            if sleep.duration < 1 {
+                sleeping_effects.remove(entity);
+            }
+        }
+
+        if let Some(hp) = hps.get_mut(entity) {
+            if hp.hp < hp.max_hp {
+                hp.hp = (hp.hp + (elapsed / HEAL_TURNS) as i32).min(hp.max_hp);
+            }
+        }
+
+        monsters.push(OffScreenMonster {
+            entity,
+            name: name.name.clone(),
+            pos: (pos.x, pos.y),
+            moves: elapsed.min(MAX_CATCH_UP_TURNS).saturating_sub(slept),
+            opens_doors: door_openers.contains(entity),
+            blocks_tile: blockers.contains(entity),
+        });
+    }
+    monsters
+}
+
+/// Monsters move in turns, each goes towards the nearest monster of the same kind
+/// if it's too far, otherwise it wanders randomly. Hazards are avoided.
+fn wander(gs: &mut State, monsters: &mut [OffScreenMonster]) {
+    let turns = monsters.iter().map(|m| m.moves).max().unwrap_or(0);
+    let mut occupied = occupied_positions(gs);
+    {
+        let mut level = gs.ecs.fetch_mut::<Level>();
+        let map = &mut level.map;
+
+        for turn in 0..turns {
+            for i in 0..monsters.len() {
+                if monsters[i].moves <= turn {
+                    continue;
+                }
+                let pos = monsters[i].pos;
+                let steps = possible_steps(map, pos, monsters[i].opens_doors, &occupied);
+                if steps.is_empty() {
+                    continue;
+                }
+
+                let ally = monsters
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, other)| *j != i && other.name == monsters[i].name)
+                    .map(|(_j, other)| other.pos)
+                    .min_by_key(|&ally| distance(pos, ally));
+                let next = match ally {
+                    Some(ally) if distance(pos, ally) > REGROUP_DISTANCE => steps
+                        .iter()
+                        .copied()
+                        .min_by_key(|&step| distance(step, ally))
+                        .filter(|&step| distance(step, ally) < distance(pos, ally)),
+                    // index out of steps means waiting
+                    _ => steps
+                        .get(rng::range(0, steps.len() as i32) as usize)
+                        .copied(),
+                };
+
+                if let Some((x, y)) = next {
+                    let index = map.xy_to_index(x, y);
+                    if map.tiles[index] == TileType::Door(DoorState::Closed) {
+                        // opening door takes whole turn
+                        map.tiles[index] = TileType::Door(DoorState::Open);
+                    } else {
+                        if monsters[i].blocks_tile {
+                            occupied.remove(&pos);
+                            occupied.insert((x, y));
+                        }
+                        monsters[i].pos = (x, y);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut positions = gs.ecs.write_storage::<components::Position>();
+    let mut views = gs.ecs.write_storage::<components::View>();
+    for monster in monsters.iter() {
+        if let Some(pos) = positions.get_mut(monster.entity) {
+            pos.x = monster.pos.0;
+            pos.y = monster.pos.1;
+        }
+        if let Some(view) = views.get_mut(monster.entity) {
+            view.should_update = true;
+        }
+    }
+}
+
+/// Monsters from spawn table of level come from elsewhere, away from player.
+fn add_wandering_monsters(gs: &mut State, elapsed: usize) {
+    let num = (elapsed / WANDERER_TURNS).min(MAX_WANDERERS);
+    if num == 0 {
+        return;
+    }
+
+    let occupied = occupied_positions(gs);
+    let (level_index, names, mut places) = {
+        let level = gs.ecs.fetch::<Level>();
+        let player_pos = *gs.ecs.fetch::<rltk::Point>();
+        let player_pos = (player_pos.x as usize, player_pos.y as usize);
+        let names = match spawn_tables::get_spawn_table(&level.spawn_table) {
+            Ok(spawn_table) => monster_names(&spawn_table.with_level_weight(level.level_weight)),
+            Err(_) => return,
+        };
+        let places = level
+            .spawn_areas
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&(x, y)| {
+                level.map.tile_at_xy(x, y) == TileType::Floor
+                    && !occupied.contains(&(x, y))
+                    && distance((x, y), player_pos) >= WANDERER_MIN_DISTANCE
+            })
+            .collect::<Vec<_>>();
+        (level.level_index, names, places)
+    };
+
+    for _ in 0..num {
+        if names.is_empty() || places.is_empty() {
+            break;
+        }
+        let name = &names[rng::range(0, names.len() as i32 - 1) as usize];
+        let (x, y) = places.swap_remove(rng::range(0, places.len() as i32 - 1) as usize);
+        spawn_entity(&mut gs.ecs, name, x, y, level_index);
+    }
+}
+
+/// Monsters (except final bosses) which can be spawned by spawn table.
+fn monster_names(spawn_table: &SpawnTable) -> Vec<String> {
+    let mut names = spawn_table
+        .spawn_packs
+        .iter()
+        .flat_map(|pack| pack.entities.iter())
+        .map(|entry| entry.entity_name.clone())
+        .filter(|name| {
+            raws::get_template(name)
+                .is_some_and(|template| template.ai.is_some() && template.final_boss.is_none())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Positions of entities blocking tiles on current level and of player.
+fn occupied_positions(gs: &State) -> HashSet<(usize, usize)> {
+    let level_index = gs.ecs.fetch::<Level>().level_index;
+    let positions = gs.ecs.read_storage::<components::Position>();
+    let blockers = gs.ecs.read_storage::<components::BlocksTile>();
+    let player_pos = *gs.ecs.fetch::<rltk::Point>();
+
+    let mut occupied = (&positions, &blockers)
+        .join()
+        .filter(|(pos, _blocker)| pos.level == level_index)
+        .map(|(pos, _blocker)| (pos.x, pos.y))
+        .collect::<HashSet<_>>();
+    occupied.insert((player_pos.x as usize, player_pos.y as usize));
+    occupied
+}
+
+/// Neighbouring tiles monster can enter, closed doors only if it opens them.
+fn possible_steps(
+    map: &Map,
+    (x, y): (usize, usize),
+    opens_doors: bool,
+    occupied: &HashSet<(usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let mut steps = vec![];
+    for ny in y.saturating_sub(1)..=(y + 1).min(map.height_max()) {
+        for nx in x.saturating_sub(1)..=(x + 1).min(map.width_max()) {
+            let tile = map.tile_at_xy(nx, ny);
+            let passable = !tile.blocks_movement()
+                || (opens_doors && tile == TileType::Door(DoorState::Closed));
+            if (nx, ny) != (x, y) && passable && !tile.is_hazard() && !occupied.contains(&(nx, ny))
+            {
+                steps.push((nx, ny));
+            }
+        }
+    }
+    steps
+}
+
+fn distance(a: (usize, usize), b: (usize, usize)) -> usize {
+    let dx = (a.0 as i64 - b.0 as i64).unsigned_abs() as usize;
+    let dy = (a.1 as i64 - b.1 as i64).unsigned_abs() as usize;
+    dx.max(dy)
+}
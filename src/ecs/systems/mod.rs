@@ -6,3 +6,6 @@ pub mod map;
 pub mod player;
 pub mod spawn;
 pub mod view_system;
+
+#[cfg(test)]
+mod scenario_tests;
@@ -5,11 +5,14 @@ use crate::ecs::{
 };
 
 use rltk::{Rltk, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 
-use super::movement::{try_move_player, try_move_player_down_level, try_move_player_up_level, Dir};
+use super::movement::{
+    try_close_door, try_move_player, try_move_player_down_level, try_move_player_up_level, Dir,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InputType {
     Up,
     Down,
@@ -28,6 +31,7 @@ pub enum InputType {
     Spacebar,
     DownLevel,
     UpLevel,
+    CloseDoor,
     D,
     U,
 
@@ -53,6 +57,7 @@ pub fn get_input(ctx: &mut Rltk) -> Option<InputType> {
             VirtualKeyCode::E => Some(InputType::ShowEquipment),
             VirtualKeyCode::U => Some(InputType::U),
             VirtualKeyCode::D => Some(InputType::D),
+            VirtualKeyCode::C => Some(InputType::CloseDoor),
 
             VirtualKeyCode::Period | VirtualKeyCode::Add => Some(InputType::DownLevel),
             VirtualKeyCode::Comma | VirtualKeyCode::Minus => Some(InputType::UpLevel),
@@ -98,6 +103,7 @@ pub fn try_handle_input(gs: &mut State) -> RunState {
 
             InputType::DownLevel => try_move_player_down_level(gs),
             InputType::UpLevel => try_move_player_up_level(gs),
+            InputType::CloseDoor => try_close_door(gs),
 
             // wait one turn
             InputType::Center => RunState::PlayerTurn,
@@ -112,7 +118,7 @@ pub fn try_handle_input(gs: &mut State) -> RunState {
                 RunState::ShowEquipment
             }
 
-            InputType::Escape => RunState::MainMenu,
+            InputType::Escape => RunState::SaveGame,
             _ => RunState::AwaitingInput,
         }
     } else {
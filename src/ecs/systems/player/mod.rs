@@ -13,25 +13,28 @@ mod movement;
 pub use input::InputType;
 pub use movement::Dir;
 
-pub fn try_player_turn(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    let mut is_sleeping = false;
-    {
-        let player = *gs.ecs.fetch::<Entity>();
-        let mut gamelog = gs.ecs.fetch_mut::<GameLog>();
-        let mut sleeping_effects = gs.ecs.write_storage::<components::SleepingEffect>();
-
-        if let Some(sleep) = sleeping_effects.get_mut(player) {
-            sleep.duration -= 1;
-            if sleep.duration < 1 {
-                sleeping_effects.remove(player);
-            }
-            gamelog
-                .entries
-                .push("You are sleeping. Skipping one turn.".to_string());
-            is_sleeping = true;
+/// If player is sleeping one turn of sleep is used up (and true is returned).
+pub fn try_sleep_turn(gs: &mut State) -> bool {
+    let player = *gs.ecs.fetch::<Entity>();
+    let mut gamelog = gs.ecs.fetch_mut::<GameLog>();
+    let mut sleeping_effects = gs.ecs.write_storage::<components::SleepingEffect>();
+
+    if let Some(sleep) = sleeping_effects.get_mut(player) {
+        sleep.duration -= 1;
+        if sleep.duration < 1 {
+            sleeping_effects.remove(player);
         }
+        gamelog
+            .entries
+            .push("You are sleeping. Skipping one turn.".to_string());
+        true
+    } else {
+        false
     }
-    if is_sleeping {
+}
+
+pub fn try_player_turn(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    if try_sleep_turn(gs) {
         return RunState::PlayerTurn;
     }
 
@@ -5,12 +5,13 @@ use crate::{
         State,
     },
     levels::level::Level,
-    maps::TileType,
+    maps::{DoorState, TerrainEffect, TileType},
 };
 
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Dir {
     Center,
     Left,
@@ -32,10 +33,13 @@ pub fn try_move_player(gs: &mut State, move_dir: Dir) -> RunState {
     let _combat_stats = gs.ecs.read_storage::<components::CombatBaseStats>();
     let hps = gs.ecs.read_storage::<components::Hp>();
     let mut wants_to_melee = gs.ecs.write_storage::<components::WantsToMeleeAtack>();
+    let mut suffer_damage = gs.ecs.write_storage::<components::SufferDamage>();
 
     let player = *gs.ecs.fetch_mut::<Entity>();
     let mut gamelog = gs.ecs.fetch_mut::<GameLog>();
-    let map = &gs.ecs.fetch::<Level>().map;
+    let mut level = gs.ecs.fetch_mut::<Level>();
+    let level = &mut *level;
+    let map = &mut level.map;
 
     let mut player_pos_res = gs.ecs.write_resource::<rltk::Point>();
     let mut pos = positions.get_mut(player).unwrap();
@@ -105,6 +109,23 @@ pub fn try_move_player(gs: &mut State, move_dir: Dir) -> RunState {
         }
     }
 
+    match map.tiles[destination_idx] {
+        TileType::Door(DoorState::Closed) => {
+            map.tiles[destination_idx] = TileType::Door(DoorState::Open);
+            gamelog.entries.push("You open the door".to_string());
+            // door changes what everyone sees
+            for view in (&mut views).join() {
+                view.should_update = true;
+            }
+            return RunState::PlayerTurn;
+        }
+        TileType::Door(DoorState::Locked) => {
+            gamelog.entries.push("The door is locked".to_string());
+            return RunState::AwaitingInput;
+        }
+        _ => (),
+    }
+
     if map.tile_at_xy(try_x, try_y).blocks_movement() {
         gamelog
             .entries
@@ -112,13 +133,81 @@ pub fn try_move_player(gs: &mut State, move_dir: Dir) -> RunState {
         return RunState::AwaitingInput;
     }
 
+    let effect = map.tiles[destination_idx].on_enter_effect();
+    let fall_destination = level
+        .exit_destination(try_x, try_y)
+        .filter(|key| gs.dungeon_layout.depth_layout(*key).is_some());
+    if effect == Some(TerrainEffect::Fall) && fall_destination.is_none() {
+        gamelog
+            .entries
+            .push("You don't dare to jump into the chasm".to_string());
+        return RunState::AwaitingInput;
+    }
+
     pos.x = try_x;
     pos.y = try_y;
     view.should_update = true;
     view_mem.should_update = true;
     *player_pos_res = rltk::Point::new(pos.x, pos.y);
 
-    RunState::PlayerTurn
+    match effect {
+        Some(TerrainEffect::Damage(damage)) => {
+            gamelog.entries.push("The lava burns you".to_string());
+            components::SufferDamage::new_damage(&mut suffer_damage, player, damage);
+            RunState::PlayerTurn
+        }
+        Some(TerrainEffect::Fall) => {
+            if level.map.tiles[destination_idx] == TileType::Trapdoor {
+                gamelog
+                    .entries
+                    .push("You fall through a trapdoor!".to_string());
+            } else {
+                gamelog.entries.push("You fall into the chasm!".to_string());
+            }
+            RunState::MoveLevel(fall_destination.unwrap())
+        }
+        None => RunState::PlayerTurn,
+    }
+}
+
+/// Closes open door next to player, if nothing stands in it.
+pub fn try_close_door(gs: &mut State) -> RunState {
+    let mut views = gs.ecs.write_storage::<components::View>();
+    let mut gamelog = gs.ecs.fetch_mut::<GameLog>();
+    let map = &mut gs.ecs.fetch_mut::<Level>().map;
+    let player_pos = *gs.ecs.fetch::<rltk::Point>();
+
+    let open_doors = (player_pos.y - 1..=player_pos.y + 1)
+        .flat_map(|y| (player_pos.x - 1..=player_pos.x + 1).map(move |x| (x, y)))
+        .map(|(x, y)| map.xy_to_index(x as usize, y as usize))
+        .filter(|index| map.tiles[*index] == TileType::Door(DoorState::Open))
+        .collect::<Vec<_>>();
+
+    match open_doors
+        .iter()
+        .find(|index| map.tile_content[**index].is_empty())
+    {
+        Some(&index) => {
+            map.tiles[index] = TileType::Door(DoorState::Closed);
+            gamelog.entries.push("You close the door".to_string());
+            for view in (&mut views).join() {
+                view.should_update = true;
+            }
+            RunState::PlayerTurn
+        }
+        None if !open_doors.is_empty() => {
+            gamelog
+                .entries
+                .push("Something blocks the door".to_string());
+            RunState::AwaitingInput
+        }
+        None => {
+            gamelog
+                .entries
+                .push("There is no open door next to you".to_string());
+            RunState::AwaitingInput
+        }
+    }
 }
 
 pub fn try_move_player_down_level(gs: &mut State) -> RunState {
@@ -136,17 +225,16 @@ pub fn try_move_player_down_level(gs: &mut State) -> RunState {
     let mut view_mem = views_memories.get_mut(player).unwrap();
 
     let index = level.map.xy_to_index(pos.x, pos.y);
-    if level.map.tiles[index] == TileType::StairsDown {
+    let destination = level.exit_destination(pos.x, pos.y);
+    if let (TileType::StairsDown, Some(destination)) = (level.map.tiles[index], destination) {
         gamelog
             .entries
             .push("You are going down stairs".to_string());
 
-        pos.level = level.level_index + 1;
-
         view.should_update = true;
         view_mem.should_update = true;
 
-        RunState::MoveLevel(level.level_index + 1)
+        RunState::MoveLevel(destination)
     } else {
         gamelog.entries.push("There is no stairs down".to_string());
         RunState::AwaitingInput
@@ -168,15 +256,14 @@ pub fn try_move_player_up_level(gs: &mut State) -> RunState {
     let mut view_mem = views_memories.get_mut(player).unwrap();
 
     let index = level.map.xy_to_index(pos.x, pos.y);
-    if level.map.tiles[index] == TileType::StairsUp {
+    let destination = level.exit_destination(pos.x, pos.y);
+    if let (TileType::StairsUp, Some(destination)) = (level.map.tiles[index], destination) {
         gamelog.entries.push("You are going up stairs".to_string());
 
-        pos.level = level.level_index - 1;
-
         view.should_update = true;
         view_mem.should_update = true;
 
-        RunState::MoveLevel(level.level_index - 1)
+        RunState::MoveLevel(destination)
     } else {
         gamelog.entries.push("There is no stairs up".to_string());
         RunState::AwaitingInput
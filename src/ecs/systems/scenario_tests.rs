@@ -0,0 +1,649 @@
+use rltk::Point;
+
+use crate::ecs::components::{BodyPart, SleepingEffect};
+use crate::ecs::game_state::RunState;
+use crate::ecs::systems::{ai::off_screen, player::InputType};
+use crate::levels::dungeon_layout::{DungeonLayout, LevelKey};
+use crate::maps::{map::LAVA_DAMAGE, DoorState, TileType};
+use crate::spawner::spawn_tables::{self, EliteModifier, SpawnEntry, SpawnPack, SpawnTable};
+use crate::test_support::TestWorld;
+
+// player: attack 5, defense 1
+
+#[test]
+fn melee_damage_includes_equipment_bonuses() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    world.spawn_equipped(player, "Short sword");
+
+    // Orc: defense 2, + 2 from armor
+    let orc = world.spawn("Orc", 2, 1);
+    world.spawn_equipped(orc, "Leather armor");
+
+    world.want_melee(player, orc);
+    world.run_combat();
+
+    // 5 + 2 - (2 + 2)
+    assert_eq!(world.hp(orc), 32 - 3);
+    assert!(world.log_contains("player hits Orc, for 3 hp."));
+}
+
+#[test]
+fn melee_damage_from_both_attackers_is_summed() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    world.spawn_equipped(player, "Dagger");
+
+    // Goblin: attack 4 + 1 from dagger
+    let goblin = world.spawn("Goblin", 2, 1);
+    let orc = world.spawn("Orc", 1, 2);
+
+    world.want_melee(goblin, player);
+    world.want_melee(orc, player);
+    world.run_combat();
+
+    // (5 - 1) + (13 - 1)
+    assert_eq!(world.hp(player), 300 - 4 - 12);
+}
+
+#[test]
+fn melee_blocked_by_defense_deals_no_damage() {
+    let mut world = TestWorld::new(20, 20);
+
+    // Goblin: attack 4 + 1 from dagger, Knight: defense 3 + 4 from chain armor
+    let goblin = world.spawn("Goblin", 2, 2);
+    let knight = world.spawn("Knight", 3, 2);
+
+    world.want_melee(goblin, knight);
+    world.run_combat();
+
+    assert_eq!(world.hp(knight), 35);
+    assert!(world.log_contains("Goblin Attacks doesnt affect  Knight (0 dmg)"));
+}
+
+#[test]
+fn one_handed_items_are_equipped_into_free_hands() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let dagger = world.spawn_equipped(player, "Dagger");
+    let sword = world.spawn_equipped(player, "Short sword");
+
+    assert_eq!(world.equipped_in(player, BodyPart::HandRight), Some(dagger));
+    assert_eq!(world.equipped_in(player, BodyPart::HandLeft), Some(sword));
+    assert!(world.is_equipped(dagger));
+    assert!(world.is_equipped(sword));
+}
+
+#[test]
+fn two_handed_item_replaces_items_in_both_hands() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let dagger = world.spawn_equipped(player, "Dagger");
+    let sword = world.spawn_equipped(player, "Short sword");
+    let zweihander = world.spawn_equipped(player, "Zweihander");
+
+    assert_eq!(
+        world.equipped_in(player, BodyPart::HandRight),
+        Some(zweihander)
+    );
+    assert_eq!(
+        world.equipped_in(player, BodyPart::HandLeft),
+        Some(zweihander)
+    );
+    assert!(world.is_equipped(zweihander));
+    assert!(!world.is_equipped(dagger));
+    assert!(!world.is_equipped(sword));
+}
+
+#[test]
+fn one_handed_item_replaces_two_handed_item() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let zweihander = world.spawn_equipped(player, "Zweihander");
+    let dagger = world.spawn_equipped(player, "Dagger");
+
+    assert_eq!(world.equipped_in(player, BodyPart::HandRight), Some(dagger));
+    assert_eq!(world.equipped_in(player, BodyPart::HandLeft), None);
+    assert!(!world.is_equipped(zweihander));
+}
+
+#[test]
+fn unequipping_two_handed_item_frees_both_hands() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let zweihander = world.spawn_equipped(player, "Zweihander");
+    world.want_unequip(player, zweihander);
+    world.run_equipment();
+
+    assert_eq!(world.equipped_in(player, BodyPart::HandRight), None);
+    assert_eq!(world.equipped_in(player, BodyPart::HandLeft), None);
+    assert!(!world.is_equipped(zweihander));
+    assert!(world.log_contains("You unequip the Zweihander."));
+}
+
+#[test]
+fn equip_system_uses_target_body_part() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let dagger = world.spawn_in_inventory(player, "Dagger");
+    world.want_equip(player, dagger, BodyPart::HandLeft);
+    world.run_equipment();
+
+    assert_eq!(world.equipped_in(player, BodyPart::HandLeft), Some(dagger));
+    assert_eq!(world.equipped_in(player, BodyPart::HandRight), None);
+    assert!(world.is_equipped(dagger));
+}
+
+#[test]
+fn fireball_damages_everything_in_area() {
+    let mut world = TestWorld::new(40, 20);
+    let player = world.player();
+    let scroll = world.spawn_in_inventory(player, "Fireball scroll");
+
+    // radius 4 around (10, 10)
+    let goblins = vec![
+        world.spawn("Goblin", 10, 10),
+        world.spawn("Goblin", 12, 10),
+        world.spawn("Goblin", 10, 13),
+    ];
+    let far_goblin = world.spawn("Goblin", 30, 10);
+    let orc = world.spawn("Orc", 11, 11);
+
+    world.want_use_item(player, scroll, Some(Point::new(10, 10)));
+    world.run_item_use();
+
+    for goblin in goblins.iter() {
+        assert_eq!(world.hp(*goblin), 10 - 15);
+    }
+    assert_eq!(world.hp(orc), 32 - 15);
+    assert_eq!(world.hp(far_goblin), 10);
+    assert_eq!(world.hp(player), 300);
+    assert!(!world.is_alive(scroll));
+
+    world.run_deaths();
+    for goblin in goblins {
+        assert!(!world.is_alive(goblin));
+    }
+    assert!(world.is_alive(orc));
+    assert!(world.log_contains("Goblin dies."));
+}
+
+#[test]
+fn area_sleep_scroll_puts_everything_in_area_to_sleep() {
+    let mut world = TestWorld::new(40, 20);
+    let player = world.player();
+    let scroll = world.spawn_in_inventory(player, "Area sleep scroll");
+
+    let near = world.spawn("Orc", 10, 10);
+    let also_near = world.spawn("Goblin", 8, 9);
+    let far = world.spawn("Orc", 30, 10);
+
+    world.want_use_item(player, scroll, Some(Point::new(10, 10)));
+    world.run_item_use();
+
+    assert!(world.has::<SleepingEffect>(near));
+    assert!(world.has::<SleepingEffect>(also_near));
+    assert!(!world.has::<SleepingEffect>(far));
+    assert!(!world.has::<SleepingEffect>(player));
+}
+
+#[test]
+fn magic_missile_hits_only_target() {
+    let mut world = TestWorld::new(40, 20);
+    let player = world.player();
+    let scroll = world.spawn_in_inventory(player, "Magic missile scroll");
+
+    let target = world.spawn("Orc", 10, 10);
+    let neighbour = world.spawn("Orc", 11, 10);
+
+    world.want_use_item(player, scroll, Some(Point::new(10, 10)));
+    world.run_item_use();
+
+    assert_eq!(world.hp(target), 32 - 20);
+    assert_eq!(world.hp(neighbour), 32);
+    assert!(world.log_contains("You use Magic missile scroll on Orc."));
+}
+
+#[test]
+fn slime_splits_into_small_slimes_after_death() {
+    let mut world = TestWorld::new(40, 20);
+
+    let slime = world.spawn("Slime", 10, 10);
+    world.set_hp(slime, 0);
+    world.run_deaths();
+
+    assert!(!world.is_alive(slime));
+    let small_slimes = world.named("Small slime");
+    assert!((3..=5).contains(&small_slimes.len()));
+    // 10% chance for new big slime
+    assert!(world.named("Slime").len() <= 1);
+
+    for small_slime in small_slimes {
+        let (x, y) = world.position(small_slime);
+        assert_ne!((x, y), (10, 10));
+        assert!((x as i32 - 10).abs() <= 3 && (y as i32 - 10).abs() <= 3);
+    }
+    assert!(world.log_contains("Slime dies."));
+}
+
+#[test]
+fn killing_final_boss_wins_game() {
+    let mut world = TestWorld::new(40, 20);
+
+    let goblin = world.spawn("Goblin", 5, 5);
+    world.set_hp(goblin, 0);
+    world.run_deaths();
+    assert!(!world.gs.game_won);
+
+    let boss = world.spawn("Mighty slime", 10, 10);
+    world.set_hp(boss, -5);
+    world.run_deaths();
+
+    assert!(world.gs.game_won);
+    assert!(!world.is_alive(boss));
+    assert!((2..=4).contains(&world.named("Slime").len()));
+    assert_eq!(world.run_state(), RunState::AwaitingInput);
+}
+
+#[test]
+fn player_death_ends_game() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+
+    let rogue = world.spawn("Rogue", 2, 1);
+    world.set_hp(player, 10);
+    world.want_melee(rogue, player);
+    world.run_combat();
+    world.run_deaths();
+
+    assert_eq!(world.run_state(), RunState::GameOver);
+    assert!(world.is_alive(player));
+    assert!(!world.gs.game_won);
+}
+
+#[test]
+fn bumping_into_closed_door_opens_it() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    world.set_tile(2, 1, TileType::Door(DoorState::Closed));
+
+    assert_eq!(world.press(InputType::Right), RunState::PlayerTurn);
+    assert_eq!(world.tile(2, 1), TileType::Door(DoorState::Open));
+    assert_eq!(world.position(player), (1, 1));
+    assert!(world.log_contains("You open the door"));
+
+    world.press(InputType::Right);
+    assert_eq!(world.position(player), (2, 1));
+}
+
+#[test]
+fn locked_door_stays_closed() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    world.set_tile(2, 1, TileType::Door(DoorState::Locked));
+
+    assert_eq!(world.press(InputType::Right), RunState::AwaitingInput);
+    assert_eq!(world.tile(2, 1), TileType::Door(DoorState::Locked));
+    assert_eq!(world.position(player), (1, 1));
+    assert!(world.log_contains("The door is locked"));
+}
+
+#[test]
+fn door_is_closed_only_if_nothing_stands_in_it() {
+    let mut world = TestWorld::new(20, 20);
+    world.set_tile(2, 2, TileType::Door(DoorState::Open));
+
+    let goblin = world.spawn("Goblin", 2, 2);
+    assert_eq!(world.press(InputType::CloseDoor), RunState::AwaitingInput);
+    assert!(world.log_contains("Something blocks the door"));
+
+    world.set_hp(goblin, 0);
+    world.run_deaths();
+    assert_eq!(world.press(InputType::CloseDoor), RunState::PlayerTurn);
+    assert_eq!(world.tile(2, 2), TileType::Door(DoorState::Closed));
+}
+
+/// Wall at x = 3 with closed door at (3, 2) and gap at (3, 1), blocked by sleeping slime
+/// (which doesn't block view of player).
+fn world_with_blocked_gap_and_door() -> TestWorld {
+    let mut world = TestWorld::new(20, 20);
+    for y in 2..19 {
+        world.set_tile(3, y, TileType::Wall);
+    }
+    world.set_tile(3, 2, TileType::Door(DoorState::Closed));
+    let slime = world.spawn("Small slime", 3, 1);
+    world.put_to_sleep(slime, 10);
+    world
+}
+
+#[test]
+fn monster_which_opens_doors_paths_through_closed_door() {
+    let mut world = world_with_blocked_gap_and_door();
+    let goblin = world.spawn("Goblin", 4, 1);
+
+    world.run_ai();
+    assert_eq!(world.tile(3, 2), TileType::Door(DoorState::Open));
+    assert_eq!(world.position(goblin), (4, 1));
+
+    world.run_ai();
+    assert_eq!(world.position(goblin), (3, 2));
+}
+
+#[test]
+fn monster_which_cant_open_doors_waits_behind_them() {
+    let mut world = world_with_blocked_gap_and_door();
+    let slime = world.spawn("Slime", 4, 1);
+
+    world.run_ai();
+    world.run_ai();
+    assert_eq!(world.tile(3, 2), TileType::Door(DoorState::Closed));
+    assert_eq!(world.position(slime), (4, 1));
+}
+
+#[test]
+fn stepping_into_lava_burns() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    let hp = world.hp(player);
+    world.set_tile(2, 1, TileType::Lava);
+
+    assert_eq!(world.press(InputType::Right), RunState::PlayerTurn);
+    world.run_combat();
+
+    assert_eq!(world.position(player), (2, 1));
+    assert_eq!(world.hp(player), hp - LAVA_DAMAGE);
+    assert!(world.log_contains("The lava burns you"));
+}
+
+#[test]
+fn player_doesnt_jump_into_chasm_on_last_level() {
+    let mut world = TestWorld::new(20, 20);
+    let player = world.player();
+    world.set_tile(2, 1, TileType::Chasm);
+
+    assert_eq!(world.press(InputType::Right), RunState::AwaitingInput);
+    assert_eq!(world.position(player), (1, 1));
+}
+
+#[test]
+fn stepping_into_chasm_moves_player_to_level_below() {
+    spawn_tables::load_spawn_tables(spawn_tables::SPAWN_TABLES_PATH).unwrap();
+    let mut world = TestWorld::new(20, 20);
+    world.gs.dungeon_layout = serde_json::from_str::<DungeonLayout>(
+        r#"{ "levels": [
+            { "generators": [] },
+            {
+                "generators": [ { "level_type": { "Cave": {} }, "spawn_table": "caves" } ],
+                "map_width": 40,
+                "map_height": 30
+            }
+        ] }"#,
+    )
+    .unwrap();
+    let player = world.player();
+    world.set_tile(2, 1, TileType::Chasm);
+
+    let run_state = world.press(InputType::Right);
+    assert_eq!(run_state, RunState::MoveLevel(LevelKey::main(1)));
+    assert!(world.log_contains("You fall into the chasm!"));
+
+    assert_eq!(world.gs.run_turn_state(run_state), RunState::PreRun);
+    assert_eq!(world.gs.current_level().key(), LevelKey::main(1));
+    let (x, y) = world.position(player);
+    let tile = world.tile(x, y);
+    assert!(!tile.blocks_movement() && !tile.is_hazard(), "{:?}", tile);
+}
+
+#[test]
+fn trapdoor_drops_player_to_its_destination() {
+    let mut world = TestWorld::new(20, 20);
+    world.gs.dungeon_layout = serde_json::from_str::<DungeonLayout>(
+        r#"{ "levels": [{ "generators": [] }, { "generators": [] }, { "generators": [] }] }"#,
+    )
+    .unwrap();
+    world.add_exit(2, 1, TileType::Trapdoor, LevelKey::main(2));
+
+    assert_eq!(
+        world.press(InputType::Right),
+        RunState::MoveLevel(LevelKey::main(2))
+    );
+    assert!(world.log_contains("You fall through a trapdoor!"));
+}
+
+#[test]
+fn stairs_lead_to_recorded_branch() {
+    let mut world = TestWorld::new(20, 20);
+    let slime_pits = LevelKey {
+        branch: 1,
+        depth: 1,
+    };
+    world.add_exit(1, 1, TileType::StairsDown, slime_pits);
+
+    assert_eq!(
+        world.press(InputType::DownLevel),
+        RunState::MoveLevel(slime_pits)
+    );
+}
+
+#[test]
+fn monster_goes_around_lava() {
+    let mut world = TestWorld::new(20, 20);
+    for y in 1..5 {
+        world.set_tile(3, y, TileType::Lava);
+    }
+    let goblin = world.spawn("Goblin", 5, 1);
+    let hp = world.hp(goblin);
+
+    for _ in 0..4 {
+        world.run_ai();
+        let (x, y) = world.position(goblin);
+        assert_ne!(world.tile(x, y), TileType::Lava);
+    }
+    world.run_combat();
+    assert_eq!(world.hp(goblin), hp);
+}
+
+#[test]
+fn monsters_of_left_level_wake_up_and_heal_on_return() {
+    let mut world = TestWorld::new(20, 20);
+    let goblin = world.spawn("Goblin", 10, 10);
+    world.put_to_sleep(goblin, 5);
+    world.set_hp(goblin, 1);
+
+    off_screen::catch_up_level(&mut world.gs, 50);
+
+    assert!(!world.has::<SleepingEffect>(goblin));
+    assert!(world.hp(goblin) > 1);
+}
+
+#[test]
+fn monsters_of_left_level_regroup() {
+    let mut world = TestWorld::new(30, 20);
+    let first = world.spawn("Goblin", 2, 10);
+    let second = world.spawn("Goblin", 27, 10);
+
+    off_screen::catch_up_level(&mut world.gs, 100);
+
+    let (x1, y1) = world.position(first);
+    let (x2, y2) = world.position(second);
+    let distance = (x1 as i32 - x2 as i32)
+        .abs()
+        .max((y1 as i32 - y2 as i32).abs());
+    assert!(distance <= 4);
+}
+
+fn goblins_table(min_depth: usize) -> SpawnTable {
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table.spawn_packs.push(
+        SpawnPack {
+            entities: vec![SpawnEntry::new("Goblin".to_string(), 3, 3)],
+            ..SpawnPack::default()
+        }
+        .with_max_spawns(1)
+        .with_depths(min_depth, usize::MAX),
+    );
+    spawn_table.elites.push(EliteModifier {
+        min_depth: 2,
+        chance_perc: 100,
+        hp_bonus_perc: 100,
+        attack_bonus: 1,
+        defense_bonus: 1,
+    });
+    spawn_table
+}
+
+#[test]
+fn deep_levels_spawn_elite_monsters() {
+    let mut world = TestWorld::new(20, 20);
+    let goblin = world.spawn("Goblin", 5, 5);
+    let hp = world.hp(goblin);
+
+    let report = world.spawn_from_table(goblins_table(0).with_level_weight(2));
+
+    assert_eq!(report.elites, 3);
+    assert_eq!(world.named("Goblin"), vec![goblin]);
+    let elites = world.named("Elite Goblin");
+    assert_eq!(elites.len(), 3);
+    assert_eq!(world.hp(elites[0]), hp * 2);
+}
+
+#[test]
+fn shallow_levels_dont_spawn_deep_packs_and_elites() {
+    let mut world = TestWorld::new(20, 20);
+
+    let report = world.spawn_from_table(goblins_table(3).with_level_weight(2));
+    assert!(report.packs.is_empty());
+    assert!(world.named("Goblin").is_empty());
+
+    let report = world.spawn_from_table(goblins_table(0).with_level_weight(1));
+    assert_eq!(report.elites, 0);
+    assert_eq!(world.named("Goblin").len(), 3);
+}
+
+#[test]
+fn guaranteed_pack_takes_the_smallest_area_it_fits() {
+    let pack = |name: &str, min_area: usize| SpawnPack {
+        name: name.to_string(),
+        min_area,
+        ..SpawnPack::default()
+    };
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table
+        .spawn_packs
+        .push(pack("goblins", 0).with_max_spawns(1));
+    spawn_table
+        .spawn_packs
+        .push(pack("armory", 5).with_min_spawns(1));
+
+    let plan = spawn_table.plan_spawn_packs(&[10, 3, 6, 4]);
+
+    assert_eq!(plan[2], Some(1));
+    assert_eq!(plan.iter().filter(|index| **index == Some(0)).count(), 1);
+    assert!(spawn_table.missing_guaranteed_packs().is_empty());
+}
+
+#[test]
+fn packs_which_dont_fit_any_area_arent_rolled() {
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table.spawn_packs.push(SpawnPack {
+        name: "armory".to_string(),
+        min_area: 50,
+        min_spawns: 1,
+        ..SpawnPack::default()
+    });
+
+    assert_eq!(spawn_table.plan_spawn_packs(&[10, 20]), vec![None, None]);
+    assert_eq!(spawn_table.missing_guaranteed_packs(), vec!["armory"]);
+}
+
+#[test]
+fn nothing_spawns_near_arrival_stairs() {
+    let mut world = TestWorld::new(30, 10);
+    world.set_tile(3, 4, TileType::StairsUp);
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table.spawn_packs.push(SpawnPack {
+        entities: vec![SpawnEntry::new("Goblin".to_string(), 30, 30)],
+        ..SpawnPack::default()
+    });
+    spawn_table.placement.safe_radius = 8.0;
+
+    let report = world.spawn_from_table(spawn_table.clone());
+
+    assert!(report.nearest_monster_distance.unwrap() >= 8.0);
+    for goblin in world.named("Goblin") {
+        let (x, _y) = world.position(goblin);
+        // walking diagonally costs 1.45
+        assert!(x >= 3 + 6);
+    }
+
+    // spawn area in safe zone stays empty
+    let report = world.spawn_from_table_in(vec![vec![(4, 4), (5, 5)]], spawn_table);
+    assert_eq!(report.empty_areas, 1);
+}
+
+#[test]
+fn boss_pack_spawns_in_the_farthest_area() {
+    let mut world = TestWorld::new(40, 10);
+    world.set_tile(2, 2, TileType::StairsUp);
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table.spawn_packs.push(
+        SpawnPack {
+            entities: vec![SpawnEntry::new("Mighty slime".to_string(), 1, 1)],
+            ..SpawnPack::default()
+        }
+        .as_boss()
+        .with_max_spawns(1),
+    );
+    spawn_table.spawn_packs.push(
+        SpawnPack {
+            entities: vec![SpawnEntry::new("Goblin".to_string(), 1, 1)],
+            ..SpawnPack::default()
+        }
+        .with_max_spawns(1),
+    );
+    let area = |x: usize| (x..x + 3).map(|x| (x, 5)).collect::<Vec<_>>();
+
+    let report = world.spawn_from_table_in(vec![area(20), area(34), area(10)], spawn_table);
+
+    let boss = world.named("Mighty slime");
+    assert_eq!(boss.len(), 1);
+    assert!(world.position(boss[0]).0 >= 34);
+    // monsters prefer far areas, the closest one stays empty
+    let goblin = world.named("Goblin")[0];
+    assert_eq!(world.position(goblin).1, 5);
+    assert!((20..23).contains(&world.position(goblin).0));
+    assert_eq!(report.empty_areas, 1);
+}
+
+#[test]
+fn items_prefer_dead_ends_and_corners() {
+    let mut world = TestWorld::new(30, 10);
+    let mut spawn_table = SpawnTable::empty();
+    spawn_table.spawn_packs.push(SpawnPack {
+        entities: vec![SpawnEntry::new("Health potion".to_string(), 2, 2)],
+        ..SpawnPack::default()
+    });
+    // (28, 8) is room corner, (20, 5) dead-end of corridor
+    world.set_tile(19, 5, TileType::Wall);
+    world.set_tile(20, 4, TileType::Wall);
+    world.set_tile(20, 6, TileType::Wall);
+    let area = (20..29)
+        .flat_map(|x| (5..9).map(move |y| (x, y)))
+        .filter(|&(x, y)| !(x == 20 && y != 5))
+        .collect();
+
+    world.spawn_from_table_in(vec![area], spawn_table);
+
+    let mut positions = world
+        .named("Health potion")
+        .into_iter()
+        .map(|potion| world.position(potion))
+        .collect::<Vec<_>>();
+    positions.sort_unstable();
+    assert_eq!(positions, vec![(20, 5), (28, 8)]);
+}
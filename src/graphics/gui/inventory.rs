@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use itertools::Itertools;
 use rltk::Rltk;
@@ -80,7 +80,8 @@ impl GuiInventoryManager {
         let renderables = gs.ecs.read_storage::<components::Renderable>();
         let entities = gs.ecs.entities();
 
-        let mut items_groupped = HashMap::<String, (usize, Entity)>::default();
+        // sorted by name, so items order (and letters used to select them) is always the same
+        let mut items_groupped = BTreeMap::<String, (usize, Entity)>::default();
 
         for (ent, _in_inv, name) in (&entities, &inventories, &names)
             .join()
@@ -90,7 +91,8 @@ impl GuiInventoryManager {
             if equipped.contains(ent) {
                 name += " <EQUIPPED> ";
             }
-            if let std::collections::hash_map::Entry::Vacant(e) = items_groupped.entry(name.clone())
+            if let std::collections::btree_map::Entry::Vacant(e) =
+                items_groupped.entry(name.clone())
             {
                 e.insert((1, ent));
             } else {
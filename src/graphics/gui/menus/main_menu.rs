@@ -1,7 +1,10 @@
-use rltk::{Rltk, RGB};
+use rltk::{Rltk, VirtualKeyCode, RGB};
 
 use crate::{
-    ecs::systems::player::{input::get_input, InputType},
+    ecs::{
+        saveload,
+        systems::player::{input::get_input, InputType},
+    },
     graphics::{
         gui::{MainMenuAction, MainMenuSelection},
         window::CHAR_CONSOLE_INDEX,
@@ -12,6 +15,8 @@ pub struct MainMenu {
     title: String,
     options: Vec<(String, MainMenuSelection)>,
     selected: u8,
+    /// digits typed by player, empty means random seed
+    seed_input: String,
     title_y: usize,
     first_opt_y: usize,
 }
@@ -22,6 +27,8 @@ impl MainMenu {
             title: "Roguelike Game".to_string(),
             options: vec![
                 ("New Game".to_string(), MainMenuSelection::NewGame),
+                ("Seed: ".to_string(), MainMenuSelection::Seed),
+                ("Continue".to_string(), MainMenuSelection::Continue),
                 #[cfg(feature = "map_gen_testing")]
                 (
                     "Test Map Generators".to_string(),
@@ -31,11 +38,41 @@ impl MainMenu {
                 ("Quit".to_string(), MainMenuSelection::Quit),
             ],
             selected: 0,
+            seed_input: String::new(),
             title_y: 15,
             first_opt_y: 20,
         }
     }
 
+    /// Seed entered by player, None if it should be random.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed_input.parse().ok()
+    }
+
+    fn edit_seed(&mut self, key: VirtualKeyCode) {
+        let digit = match key {
+            VirtualKeyCode::Key0 => '0',
+            VirtualKeyCode::Key1 => '1',
+            VirtualKeyCode::Key2 => '2',
+            VirtualKeyCode::Key3 => '3',
+            VirtualKeyCode::Key4 => '4',
+            VirtualKeyCode::Key5 => '5',
+            VirtualKeyCode::Key6 => '6',
+            VirtualKeyCode::Key7 => '7',
+            VirtualKeyCode::Key8 => '8',
+            VirtualKeyCode::Key9 => '9',
+            VirtualKeyCode::Back => {
+                self.seed_input.pop();
+                return;
+            }
+            _ => return,
+        };
+        // longer could overflow u64
+        if self.seed_input.len() < 19 {
+            self.seed_input.push(digit);
+        }
+    }
+
     pub fn incr_selection(&mut self) {
         if (self.selected as usize) < self.options.len() - 1 {
             self.selected += 1;
@@ -62,13 +99,29 @@ impl MainMenu {
             self.title.clone(),
         );
 
+        let save_exists = saveload::save_file_exists();
+        let seed_selected = self.options[self.selected as usize].1 == MainMenuSelection::Seed;
         for (opt, i) in self.options.iter() {
             let color = if *i as u8 == self.selected {
                 RGB::named(rltk::GREEN)
+            } else if *i == MainMenuSelection::Continue && !save_exists {
+                RGB::named(rltk::GREY)
             } else {
                 RGB::named(rltk::WHITE)
             };
 
+            let opt = if *i == MainMenuSelection::Seed {
+                if self.seed_input.is_empty() && !seed_selected {
+                    format!("{}random", opt)
+                } else if seed_selected {
+                    format!("{}{}_", opt, self.seed_input)
+                } else {
+                    format!("{}{}", opt, self.seed_input)
+                }
+            } else {
+                opt.clone()
+            };
+
             ctx.print_color_centered(
                 self.first_opt_y + (*i as usize),
                 color,
@@ -77,6 +130,12 @@ impl MainMenu {
             );
         }
 
+        if seed_selected {
+            if let Some(key) = ctx.key {
+                self.edit_seed(key);
+            }
+        }
+
         let input = get_input(ctx);
         if let Some(key) = input {
             match key {
@@ -89,6 +148,7 @@ impl MainMenu {
                     self.incr_selection();
                     MainMenuAction::NotSelected
                 }
+                // enter on seed starts new game with typed seed
                 InputType::Enter => {
                     let selection = self.options[self.selected as usize].1;
                     MainMenuAction::Selected(selection)
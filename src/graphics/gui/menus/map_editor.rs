@@ -0,0 +1,355 @@
+#![cfg(feature = "map_gen_testing")]
+//! Map editor for authoring prefabs (boss arenas, vaults). Editing starts from map of tested
+//! generator, tiles, entities and spawn areas are painted with mouse and result is saved
+//! as ascii map (see `maps::ascii`) to prefabs directory.
+
+use std::path::Path;
+
+use rltk::{DrawBatch, Rltk, VirtualKeyCode, RGB};
+
+use crate::{
+    graphics::{
+        draw_map_without_fov,
+        window::{CHAR_CONSOLE_INDEX, SPRITE_16X16_CONSOLE_INDEX},
+    },
+    maps::{
+        ascii::{self, AsciiMap, MapEntity, MAX_SPAWN_AREAS},
+        DoorState, Map, TileType,
+    },
+    spawner::raws,
+};
+
+/// Tiles which can be painted, in order of cycling through them.
+const TILES: [TileType; 17] = [
+    TileType::Floor,
+    TileType::Wall,
+    TileType::Rock,
+    TileType::StairsUp,
+    TileType::StairsDown,
+    TileType::Door(DoorState::Closed),
+    TileType::Door(DoorState::Open),
+    TileType::Door(DoorState::Locked),
+    TileType::ShallowWater,
+    TileType::DeepWater,
+    TileType::Bridge,
+    TileType::Lava,
+    TileType::Chasm,
+    TileType::Trapdoor,
+    TileType::Rubble,
+    TileType::Grass,
+    TileType::Tree,
+];
+
+const SPAWN_AREA_COLORS: [(u8, u8, u8); 5] = [
+    rltk::YELLOW,
+    rltk::CYAN,
+    rltk::MAGENTA,
+    rltk::ORANGE,
+    rltk::LIME_GREEN,
+];
+
+/// Painting tool, index points to `TILES`, entity names or spawn areas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditorTool {
+    Tile(usize),
+    Entity(usize),
+    SpawnArea(usize),
+    /// removes entities and spawn area marks
+    Erase,
+}
+
+pub enum MapEditorAction {
+    Close,
+    NoResponse,
+}
+
+pub struct MapEditor {
+    pub map: Map,
+    pub entities: Vec<MapEntity>,
+    /// always `MAX_SPAWN_AREAS` long, empty areas aren't saved
+    pub spawn_areas: Vec<Vec<(usize, usize)>>,
+    /// result of last action which could fail
+    pub message: Option<String>,
+
+    tool: EditorTool,
+    entity_names: Vec<String>,
+}
+
+impl MapEditor {
+    pub fn new(width: usize, height: usize) -> MapEditor {
+        MapEditor {
+            map: Map::new(width, height).with_all_solid(),
+            entities: vec![],
+            spawn_areas: vec![vec![]; MAX_SPAWN_AREAS],
+            message: None,
+            tool: EditorTool::Tile(0),
+            entity_names: vec![],
+        }
+    }
+
+    /// Starts editing of given map, entity names are read from loaded raws.
+    pub fn open(
+        &mut self,
+        map: Map,
+        entities: Vec<MapEntity>,
+        mut spawn_areas: Vec<Vec<(usize, usize)>>,
+    ) {
+        spawn_areas.resize(MAX_SPAWN_AREAS, vec![]);
+        self.map = map;
+        self.entities = entities;
+        self.spawn_areas = spawn_areas;
+        self.message = None;
+        self.entity_names = raws::entity_names();
+        self.tool = EditorTool::Tile(0);
+    }
+
+    pub fn update(&mut self, ctx: &mut Rltk, window_height: usize) -> MapEditorAction {
+        self.draw(ctx, window_height);
+
+        ctx.set_active_console(CHAR_CONSOLE_INDEX);
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        let (painting, erasing) = {
+            let input = rltk::INPUT.lock();
+            (
+                input.is_mouse_button_pressed(0),
+                input.is_mouse_button_pressed(1),
+            )
+        };
+        let in_map = mouse_x >= 0
+            && mouse_y >= 0
+            && (mouse_x as usize) < self.map.width
+            && (mouse_y as usize) < self.map.height;
+        if in_map {
+            let (x, y) = (mouse_x as usize, mouse_y as usize);
+            if painting {
+                self.apply_tool(self.tool, x, y);
+            } else if erasing {
+                self.apply_tool(EditorTool::Erase, x, y);
+            }
+        }
+
+        match ctx.key {
+            Some(VirtualKeyCode::Escape) => return MapEditorAction::Close,
+            Some(VirtualKeyCode::Tab) => self.switch_tool(),
+            Some(VirtualKeyCode::Left) => self.change_tool_index(-1),
+            Some(VirtualKeyCode::Right) => self.change_tool_index(1),
+            Some(VirtualKeyCode::S) => self.save_as_prefab(),
+            _ => {}
+        }
+        MapEditorAction::NoResponse
+    }
+
+    /// Tiles -> entities -> spawn areas -> erase -> tiles.
+    fn switch_tool(&mut self) {
+        self.tool = match self.tool {
+            EditorTool::Tile(_) if !self.entity_names.is_empty() => EditorTool::Entity(0),
+            EditorTool::Tile(_) | EditorTool::Entity(_) => EditorTool::SpawnArea(0),
+            EditorTool::SpawnArea(_) => EditorTool::Erase,
+            EditorTool::Erase => EditorTool::Tile(0),
+        };
+    }
+
+    fn change_tool_index(&mut self, delta: i32) {
+        let cycle =
+            |index: usize, len: usize| (index as i32 + delta).rem_euclid(len as i32) as usize;
+        self.tool = match self.tool {
+            EditorTool::Tile(i) => EditorTool::Tile(cycle(i, TILES.len())),
+            EditorTool::Entity(i) => EditorTool::Entity(cycle(i, self.entity_names.len())),
+            EditorTool::SpawnArea(i) => EditorTool::SpawnArea(cycle(i, MAX_SPAWN_AREAS)),
+            EditorTool::Erase => EditorTool::Erase,
+        };
+    }
+
+    /// Entities and spawn areas stay only on floor, one of them per tile.
+    fn apply_tool(&mut self, tool: EditorTool, x: usize, y: usize) {
+        let index = self.map.xy_to_index(x, y);
+        let on_floor = self.map.tiles[index] == TileType::Floor;
+        match tool {
+            EditorTool::Tile(i) => {
+                self.map.tiles[index] = TILES[i];
+                if TILES[i] != TileType::Floor {
+                    self.erase(x, y);
+                }
+            }
+            EditorTool::Entity(i) if on_floor => {
+                let entity = MapEntity {
+                    name: self.entity_names[i].clone(),
+                    x,
+                    y,
+                };
+                if !self.entities.contains(&entity) {
+                    self.erase(x, y);
+                    self.entities.push(entity);
+                }
+            }
+            EditorTool::SpawnArea(i) if on_floor => {
+                if !self.spawn_areas[i].contains(&(x, y)) {
+                    self.erase(x, y);
+                    self.spawn_areas[i].push((x, y));
+                }
+            }
+            EditorTool::Entity(_) | EditorTool::SpawnArea(_) => {
+                self.message = Some("Entities and spawn areas can be placed only on floor".into())
+            }
+            EditorTool::Erase => self.erase(x, y),
+        }
+    }
+
+    fn erase(&mut self, x: usize, y: usize) {
+        self.entities
+            .retain(|entity| (entity.x, entity.y) != (x, y));
+        for area in self.spawn_areas.iter_mut() {
+            area.retain(|&pos| pos != (x, y));
+        }
+    }
+
+    /// Saved under first free name `edited_<n>`, so nothing is overwritten.
+    fn save_as_prefab(&mut self) {
+        let name = (1..)
+            .map(|i| format!("edited_{}", i))
+            .find(|name| !Path::new(&ascii::prefab_path(name)).exists())
+            .unwrap();
+        self.message = Some(
+            match ascii::save_ascii_map(&ascii::prefab_path(&name), &self.ascii_map()) {
+                Ok(()) => format!("Saved as prefab \"{}\"", name),
+                Err(e) => format!("Saving prefab failed: {}", e),
+            },
+        );
+    }
+
+    /// Edited map in form which is saved, empty spawn areas are left out.
+    fn ascii_map(&self) -> AsciiMap {
+        AsciiMap {
+            map: self.map.clone(),
+            entities: self.entities.clone(),
+            spawn_areas: self
+                .spawn_areas
+                .iter()
+                .filter(|area| !area.is_empty())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn tool_text(&self) -> String {
+        match self.tool {
+            EditorTool::Tile(i) => format!("Tile: {:?}", TILES[i]),
+            EditorTool::Entity(i) => format!("Entity: {}", self.entity_names[i]),
+            EditorTool::SpawnArea(i) => {
+                format!("Spawn area: {} ({} tiles)", i, self.spawn_areas[i].len())
+            }
+            EditorTool::Erase => "Erase entities and spawn areas".to_string(),
+        }
+    }
+
+    fn draw(&self, ctx: &mut Rltk, window_height: usize) {
+        draw_map_without_fov(&self.map, None, ctx);
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(SPRITE_16X16_CONSOLE_INDEX);
+        for entity in self.entities.iter() {
+            let texture = raws::get_template(&entity.name)
+                .and_then(|template| template.renderable)
+                .and_then(|renderable| renderable.texture);
+            draw_batch.set(
+                rltk::Point::new(entity.x, entity.y),
+                rltk::ColorPair::new(RGB::from_f32(1.0, 1., 1.0), RGB::from_f32(0., 0., 0.)),
+                texture.unwrap_or(3),
+            );
+        }
+        draw_batch
+            .submit(SPRITE_16X16_CONSOLE_INDEX)
+            .expect("Draw Batch error");
+        rltk::render_draw_buffer(ctx).expect("Render error");
+
+        ctx.set_active_console(CHAR_CONSOLE_INDEX);
+        ctx.cls();
+        for (i, area) in self.spawn_areas.iter().enumerate() {
+            let color = RGB::named(SPAWN_AREA_COLORS[i % SPAWN_AREA_COLORS.len()]);
+            for &(x, y) in area.iter() {
+                ctx.print_color(x, y, color, RGB::named(rltk::BLACK), i.to_string());
+            }
+        }
+
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        ctx.set_bg(mouse_x, mouse_y, RGB::named(rltk::MAGENTA));
+
+        let lines = [
+            self.tool_text(),
+            format!(
+                "Mouse: ({}, {})  {}",
+                mouse_x,
+                mouse_y,
+                self.message.as_deref().unwrap_or("")
+            ),
+            "LEFT MOUSE to paint, RIGHT MOUSE to erase, TAB to change tool, LEFT/RIGHT to change tile/entity/area".to_string(),
+            "S to save as prefab, ESCAPE to return to tested map".to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            ctx.print_color(
+                1,
+                window_height - lines.len() + i,
+                RGB::named(rltk::WHITE),
+                RGB::named(rltk::BLACK),
+                line,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn painted_map_is_the_same_after_export_and_import() {
+        let mut editor = MapEditor::new(8, 5);
+        editor.open(
+            Map::new(8, 5).with_edges_solid(),
+            vec![],
+            vec![vec![(3, 3)]],
+        );
+        editor.entity_names = vec!["Goblin".to_string(), "Health potion".to_string()];
+        let lava = TILES
+            .iter()
+            .position(|tile| *tile == TileType::Lava)
+            .unwrap();
+        let door = TILES
+            .iter()
+            .position(|tile| *tile == TileType::Door(DoorState::Locked))
+            .unwrap();
+
+        editor.apply_tool(EditorTool::Tile(lava), 1, 1);
+        editor.apply_tool(EditorTool::Tile(door), 2, 1);
+        editor.apply_tool(EditorTool::Entity(0), 4, 2);
+        editor.apply_tool(EditorTool::Entity(1), 5, 2);
+        editor.apply_tool(EditorTool::SpawnArea(2), 6, 3);
+        // entities and spawn areas aren't placed on lava
+        editor.apply_tool(EditorTool::Entity(0), 1, 1);
+        editor.apply_tool(EditorTool::Erase, 3, 3);
+
+        let ascii_map = editor.ascii_map();
+        let text = ascii::map_with_spawn_areas_to_ascii(
+            &ascii_map.map,
+            &ascii_map.entities,
+            &ascii_map.spawn_areas,
+        )
+        .unwrap();
+        let reloaded = ascii::map_from_ascii(&text).unwrap();
+
+        assert_eq!(reloaded.map.tiles, editor.map.tiles);
+        assert_eq!(reloaded.entities, editor.entities);
+        assert_eq!(reloaded.spawn_areas, vec![vec![(6, 3)]]);
+        assert_eq!(
+            text,
+            "########\n\
+             #^=....#\n\
+             #...gh.#\n\
+             #.....0#\n\
+             ########\n\
+             \n\
+             g = Goblin\n\
+             h = Health potion\n"
+        );
+    }
+}
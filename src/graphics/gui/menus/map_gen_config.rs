@@ -0,0 +1,388 @@
+#![cfg(feature = "map_gen_testing")]
+//! Settings of tested map generator. Config of level type is edited in its json form,
+//! so every number and flag of every generator config (nested ones too) can be changed.
+
+use rltk::Rltk;
+use serde_json::{json, Value};
+
+use crate::{
+    ecs::systems::player::{input::get_input, InputType},
+    impl_window_option_selector,
+    maps::rect::Rect,
+};
+
+use super::{map_testing::MapGenSettings, TextCol, WindowOptionSelector};
+
+/// Smallest map size which can be set, generators need some space for rooms.
+const MIN_MAP_SIZE: usize = 20;
+
+/// Change of float values for one key press.
+const FLOAT_STEP: f64 = 0.05;
+
+/// Highest values of config numbers by ending of their name, numbers not listed
+/// here (counts, sizes, limits) can't be bigger than the biggest map side.
+/// Generators loop by these values, so they have to stay in range generating can end in.
+const VALUE_LIMITS: &[(&str, i64)] = &[("perc", 100), ("percent", 100)];
+
+pub enum ConfigEditorAction {
+    /// settings were changed, map should be generated again
+    Changed,
+    NewSeed,
+    SavePreset,
+    Close,
+    NoResponse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConfigField {
+    Width,
+    Height,
+    Seed,
+    /// json pointer to number or bool in level type
+    Value(String),
+    NewSeed,
+    SavePreset,
+}
+
+pub struct GuiMapGenConfigEditor {
+    pub selected: usize,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub bg: rltk::RGB,
+
+    pub title: TextCol,
+    pub options: Vec<TextCol>,
+    pub options_sprites_indexes: Vec<Option<usize>>,
+
+    fields: Vec<ConfigField>,
+}
+
+impl WindowOptionSelector for GuiMapGenConfigEditor {
+    impl_window_option_selector!();
+
+    fn options(&self) -> &[TextCol] {
+        &self.options
+    }
+}
+
+impl GuiMapGenConfigEditor {
+    pub fn new(x: usize, y: usize, width: usize) -> GuiMapGenConfigEditor {
+        GuiMapGenConfigEditor {
+            x,
+            y,
+            width,
+            height: 2,
+            selected: 0,
+            bg: rltk::RGB::named(rltk::BLACK),
+            title: TextCol::new(vec![(
+                "Generator settings (LEFT/RIGHT to change)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            options: vec![],
+            options_sprites_indexes: vec![],
+            fields: vec![],
+        }
+    }
+
+    /// Window with text below it, map isn't drawn there.
+    pub fn area(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height + 1)
+    }
+
+    /// Fields and their values are read from settings again, selection is kept.
+    pub fn reset(&mut self, settings: &MapGenSettings) {
+        let level_type = serde_json::to_value(&settings.level_type).unwrap_or(Value::Null);
+        let mut pointers = vec![];
+        editable_values(&level_type, String::new(), &mut pointers);
+
+        self.fields = vec![ConfigField::Width, ConfigField::Height, ConfigField::Seed];
+        self.fields
+            .extend(pointers.into_iter().map(ConfigField::Value));
+        self.fields.push(ConfigField::NewSeed);
+        self.fields.push(ConfigField::SavePreset);
+
+        self.options = self
+            .fields
+            .iter()
+            .map(|field| field_text(field, settings, &level_type))
+            .collect();
+        self.height = self.options.len() + 1;
+        if self.selected >= self.options.len() {
+            self.selected = 0;
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        ctx: &mut Rltk,
+        settings: &mut MapGenSettings,
+        max_map_size: (usize, usize),
+    ) -> ConfigEditorAction {
+        self.draw(ctx);
+        let action = match get_input(ctx) {
+            Some(InputType::Escape) => ConfigEditorAction::Close,
+            Some(InputType::Up) => {
+                self.decr_selection();
+                ConfigEditorAction::NoResponse
+            }
+            Some(InputType::Down) => {
+                self.incr_selection();
+                ConfigEditorAction::NoResponse
+            }
+            Some(InputType::Left) => self.change_selected(settings, max_map_size, -1),
+            Some(InputType::Right) => self.change_selected(settings, max_map_size, 1),
+            Some(InputType::Enter) => match self.fields[self.selected] {
+                ConfigField::NewSeed => ConfigEditorAction::NewSeed,
+                ConfigField::SavePreset => ConfigEditorAction::SavePreset,
+                _ => ConfigEditorAction::NoResponse,
+            },
+            _ => ConfigEditorAction::NoResponse,
+        };
+        self.reset(settings);
+        action
+    }
+
+    /// Numbers are changed by `delta` (floats by `delta` steps), flags are switched.
+    fn change_selected(
+        &self,
+        settings: &mut MapGenSettings,
+        max_map_size: (usize, usize),
+        delta: i64,
+    ) -> ConfigEditorAction {
+        match &self.fields[self.selected] {
+            ConfigField::Width => {
+                settings.width = add_clamped(settings.width, delta, MIN_MAP_SIZE, max_map_size.0)
+            }
+            ConfigField::Height => {
+                settings.height = add_clamped(settings.height, delta, MIN_MAP_SIZE, max_map_size.1)
+            }
+            ConfigField::Seed => settings.seed = settings.seed.wrapping_add(delta as u64),
+            ConfigField::Value(pointer) => {
+                let mut level_type = match serde_json::to_value(&settings.level_type) {
+                    Ok(level_type) => level_type,
+                    Err(_) => return ConfigEditorAction::NoResponse,
+                };
+                let max = value_limit(pointer, max_map_size);
+                if let Some(value) = level_type.pointer_mut(pointer) {
+                    *value = changed_value(value, delta, max);
+                }
+                // value out of range of config field isn't accepted
+                match serde_json::from_value(level_type) {
+                    Ok(level_type) => settings.level_type = level_type,
+                    Err(_) => return ConfigEditorAction::NoResponse,
+                }
+            }
+            ConfigField::NewSeed | ConfigField::SavePreset => {
+                return ConfigEditorAction::NoResponse
+            }
+        }
+        ConfigEditorAction::Changed
+    }
+}
+
+/// Json pointers to all numbers and bools in `value`.
+fn editable_values(value: &Value, pointer: String, pointers: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter() {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                editable_values(field, format!("{}/{}", pointer, key), pointers);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                editable_values(item, format!("{}/{}", pointer, i), pointers);
+            }
+        }
+        Value::Number(_) | Value::Bool(_) => pointers.push(pointer),
+        Value::String(_) | Value::Null => {}
+    }
+}
+
+/// Highest value of number at json `pointer`, see `VALUE_LIMITS`.
+fn value_limit(pointer: &str, max_map_size: (usize, usize)) -> i64 {
+    let name = pointer.rsplit('/').next().unwrap_or_default();
+    VALUE_LIMITS
+        .iter()
+        .find(|(ending, _)| name.ends_with(ending))
+        .map(|(_, limit)| *limit)
+        .unwrap_or_else(|| max_map_size.0.max(max_map_size.1) as i64)
+}
+
+/// Integers are kept in range `-max..=max` (unsigned ones in `0..=max`).
+fn changed_value(value: &Value, delta: i64, max: i64) -> Value {
+    match value {
+        Value::Bool(flag) => Value::Bool(!flag),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                json!((n as i64 + delta).clamp(0, max))
+            } else if let Some(n) = number.as_i64() {
+                json!((n + delta).clamp(-max, max))
+            } else {
+                let n = number.as_f64().unwrap_or(0.0) + delta as f64 * FLOAT_STEP;
+                json!((n * 100.0).round() / 100.0)
+            }
+        }
+        other => other.clone(),
+    }
+}
+
+fn add_clamped(value: usize, delta: i64, min: usize, max: usize) -> usize {
+    (value as i64 + delta).clamp(min as i64, max.max(min) as i64) as usize
+}
+
+fn field_text(field: &ConfigField, settings: &MapGenSettings, level_type: &Value) -> TextCol {
+    let (label, value) = match field {
+        ConfigField::Width => ("map width".to_string(), settings.width.to_string()),
+        ConfigField::Height => ("map height".to_string(), settings.height.to_string()),
+        ConfigField::Seed => ("seed".to_string(), settings.seed.to_string()),
+        ConfigField::Value(pointer) => (
+            pointer.trim_start_matches('/').replace('/', "."),
+            level_type
+                .pointer(pointer)
+                .map(|value| value.to_string())
+                .unwrap_or_default(),
+        ),
+        ConfigField::NewSeed => {
+            return TextCol::new(vec![(
+                "Generate with new seed (ENTER)".to_string(),
+                rltk::RGB::named(rltk::YELLOW),
+            )])
+        }
+        ConfigField::SavePreset => {
+            return TextCol::new(vec![(
+                "Save as preset (ENTER)".to_string(),
+                rltk::RGB::named(rltk::YELLOW),
+            )])
+        }
+    };
+    TextCol::new(vec![
+        (format!("{}: ", label), rltk::RGB::named(rltk::WHITE)),
+        (value, rltk::RGB::named(rltk::GREEN)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{level::LevelType, presets};
+    use crate::rng;
+
+    const MAX_MAP_SIZE: (usize, usize) = (76, 40);
+
+    fn settings(level_type: LevelType) -> MapGenSettings {
+        MapGenSettings {
+            level_type,
+            width: MIN_MAP_SIZE,
+            height: MIN_MAP_SIZE,
+            seed: 1,
+        }
+    }
+
+    fn editor(settings: &MapGenSettings) -> GuiMapGenConfigEditor {
+        let mut editor = GuiMapGenConfigEditor::new(0, 0, 60);
+        editor.reset(settings);
+        editor
+    }
+
+    /// Selected field of editor is changed by `delta` key presses at once.
+    fn change_field(settings: &mut MapGenSettings, pointer: &str, delta: i64) {
+        let mut editor = editor(settings);
+        editor.selected = editor
+            .fields
+            .iter()
+            .position(|field| *field == ConfigField::Value(pointer.to_string()))
+            .unwrap();
+        editor.change_selected(settings, MAX_MAP_SIZE, delta);
+    }
+
+    fn value_pointers(settings: &MapGenSettings) -> Vec<String> {
+        editor(settings)
+            .fields
+            .into_iter()
+            .filter_map(|field| match field {
+                ConfigField::Value(pointer) => Some(pointer),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edited_values_are_kept_in_range() {
+        let mut settings = settings(LevelType::Cave(Default::default()));
+        change_field(&mut settings, "/Cave/alive_on_start_chance_percent", 1000);
+        change_field(&mut settings, "/Cave/step_limit", 1000);
+        change_field(&mut settings, "/Cave/death_limit", -1000);
+
+        let level_type = serde_json::to_value(&settings.level_type).unwrap();
+        assert_eq!(level_type["Cave"]["alive_on_start_chance_percent"], 100);
+        assert_eq!(level_type["Cave"]["step_limit"], 76);
+        assert_eq!(level_type["Cave"]["death_limit"], 0);
+    }
+
+    #[test]
+    fn maps_are_generated_with_every_value_at_its_limit() {
+        let level_types = vec![
+            LevelType::Cave(Default::default()).with_terrain(Default::default()),
+            LevelType::BasicDungeon(Default::default()),
+            LevelType::BSPDungeon(Default::default()),
+            LevelType::BSPInterior(Default::default()),
+            LevelType::Maze(Default::default()),
+            LevelType::DLA(Default::default()),
+            LevelType::Voronoi(Default::default()),
+            LevelType::eroded_bsp_dungeon(),
+            LevelType::Wfc {
+                sample: Box::new(LevelType::Cave(Default::default())),
+                config: Default::default(),
+            },
+        ];
+        for level_type in level_types {
+            for &(delta, size) in &[(-1000, MIN_MAP_SIZE), (1000, MIN_MAP_SIZE), (1000, 76)] {
+                let mut settings = settings(level_type.clone());
+                settings.width = size;
+                for pointer in value_pointers(&settings) {
+                    change_field(&mut settings, &pointer, delta);
+                }
+
+                // generating can fail, but it has to end
+                rng::reseed(settings.seed);
+                let _ = settings
+                    .level_type
+                    .map_generator(settings.width, settings.height, vec![])
+                    .and_then(|mut map_gen| map_gen.generate(None));
+            }
+        }
+    }
+
+    #[test]
+    fn edited_level_type_is_saved_as_preset() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "roguelike_edited_presets_{}.json",
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::copy(presets::PRESETS_PATH, &path).unwrap();
+        let mut settings = settings(LevelType::Maze(Default::default()));
+        change_field(&mut settings, "/Maze/braid_perc", 5);
+        let unknown = self::settings(LevelType::Wfc {
+            sample: Box::new(LevelType::Preset("missing".to_string())),
+            config: Default::default(),
+        });
+
+        let name = presets::save_preset(&path, &settings.level_type).unwrap();
+        let unknown_result = presets::save_preset(&path, &unknown.level_type);
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            saved["presets"][&name],
+            serde_json::to_value(&settings.level_type).unwrap()
+        );
+        assert!(unknown_result.is_err());
+        assert!(saved["presets"].get("wfc_1").is_none());
+    }
+}
@@ -4,10 +4,16 @@ use rltk::Rltk;
 
 use crate::{
     impl_window_option_selector,
+    levels::{level::LevelType, presets},
     maps::generators::{test_map::TestMap, MapGenerator},
+    rng,
 };
 
-use super::{MenuAction, TextCol, WindowOptionSelector};
+use super::{
+    map_editor::MapEditor,
+    map_gen_config::{ConfigEditorAction, GuiMapGenConfigEditor},
+    MenuAction, TextCol, WindowOptionSelector,
+};
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum MapGenTestingMenuAction {
@@ -17,6 +23,13 @@ pub enum MapGenTestingMenuAction {
     TestBSPDungeonGen,
     TestBSPInteriorGen,
     TestDrunkardWalkGen,
+    TestMazeGen,
+    TestDLAGen,
+    TestVoronoiGen,
+    TestWfcGenWithBSPInteriorSample,
+    TestWfcGenWithAsciiSample,
+    TestPipelineGen,
+    TestTerrainGen,
 
     Cancel,
     NoResponse,
@@ -31,12 +44,28 @@ impl From<usize> for MapGenTestingMenuAction {
             3 => MapGenTestingMenuAction::TestBSPDungeonGen,
             4 => MapGenTestingMenuAction::TestBSPInteriorGen,
             5 => MapGenTestingMenuAction::TestDrunkardWalkGen,
+            6 => MapGenTestingMenuAction::TestMazeGen,
+            7 => MapGenTestingMenuAction::TestDLAGen,
+            8 => MapGenTestingMenuAction::TestVoronoiGen,
+            9 => MapGenTestingMenuAction::TestWfcGenWithBSPInteriorSample,
+            10 => MapGenTestingMenuAction::TestWfcGenWithAsciiSample,
+            11 => MapGenTestingMenuAction::TestPipelineGen,
+            12 => MapGenTestingMenuAction::TestTerrainGen,
 
             _ => MapGenTestingMenuAction::NoResponse,
         }
     }
 }
 
+/// Everything needed to generate the same map again.
+#[derive(Debug, Clone)]
+pub struct MapGenSettings {
+    pub level_type: LevelType,
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+}
+
 pub struct GuiMapGenTestingManager {
     pub selected: usize,
     pub x: usize,
@@ -52,6 +81,15 @@ pub struct GuiMapGenTestingManager {
 
     pub map_gen: Box<dyn MapGenerator>,
     pub current_history_index: usize,
+
+    pub settings: MapGenSettings,
+    /// tested map can't be bigger than this
+    pub max_map_size: (usize, usize),
+    pub config_editor: GuiMapGenConfigEditor,
+    pub editing_config: bool,
+    /// result of last generating or saving of preset
+    pub message: Option<String>,
+    pub map_editor: MapEditor,
 }
 
 impl WindowOptionSelector for GuiMapGenTestingManager {
@@ -80,19 +118,89 @@ impl GuiMapGenTestingManager {
             show_steps: false,
             map_gen: Box::new(TestMap::new(width - 4, height - 4)),
             current_history_index: 0,
+            settings: MapGenSettings {
+                level_type: LevelType::TestLevel,
+                width: width - 4,
+                height: height - 4,
+                seed: 0,
+            },
+            max_map_size: (width - 4, height - 4),
+            config_editor: GuiMapGenConfigEditor::new(2, 2, 60),
+            editing_config: false,
+            message: None,
+            map_editor: MapEditor::new(width - 4, height - 4),
         }
     }
 
-    pub fn reset_map_gen(&mut self, mut gen: Box<dyn MapGenerator>) {
-        gen.generate(None).unwrap();
-        self.map_gen = gen;
+    /// Starts testing of `level_type` with default map size `width` x `height`,
+    /// which is also the biggest one which can be set in config editor.
+    pub fn test_level_type(&mut self, level_type: LevelType, width: usize, height: usize) {
+        self.settings = MapGenSettings {
+            level_type,
+            width,
+            height,
+            seed: rng::random_seed(),
+        };
+        self.max_map_size = (width, height);
+        self.editing_config = false;
+        self.generate();
+    }
+
+    /// Generates map with current settings. When generating fails, previous map is kept.
+    pub fn generate(&mut self) {
+        rng::reseed(self.settings.seed);
+        let settings = &self.settings;
+        let result = settings
+            .level_type
+            .map_generator(settings.width, settings.height, vec![])
+            .and_then(|mut map_gen| map_gen.generate(None).map(|_| map_gen));
+        match result {
+            Ok(map_gen) => {
+                self.map_gen = map_gen;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("Generating failed: {}", e)),
+        }
         self.current_history_index = 0;
     }
 
     pub fn reset_current_map_gen(&mut self) {
-        self.map_gen.reset();
-        self.map_gen.generate(None).unwrap();
-        self.current_history_index = 0;
+        self.settings.seed = rng::random_seed();
+        self.generate();
+    }
+
+    /// Generated map with its entities and spawn areas is edited.
+    pub fn open_map_editor(&mut self) {
+        self.map_editor.open(
+            self.map_gen.map(),
+            self.map_gen.entities(),
+            self.map_gen.spawn_areas(),
+        );
+    }
+
+    pub fn open_config_editor(&mut self) {
+        self.config_editor.reset(&self.settings);
+        self.editing_config = true;
+    }
+
+    pub fn update_config_editor(&mut self, ctx: &mut Rltk) {
+        match self
+            .config_editor
+            .update(ctx, &mut self.settings, self.max_map_size)
+        {
+            ConfigEditorAction::Changed => self.generate(),
+            ConfigEditorAction::NewSeed => self.reset_current_map_gen(),
+            ConfigEditorAction::SavePreset => {
+                self.message = Some(
+                    match presets::save_preset(presets::PRESETS_PATH, &self.settings.level_type) {
+                        Ok(name) => format!("Saved as preset \"{}\"", name),
+                        Err(e) => format!("Saving preset failed: {}", e),
+                    },
+                )
+            }
+            ConfigEditorAction::Close => self.editing_config = false,
+            ConfigEditorAction::NoResponse => {}
+        }
     }
 
     pub fn reset(&mut self) {
@@ -130,6 +238,34 @@ impl GuiMapGenTestingManager {
                 "Test Drunkard walk map generator".to_string(),
                 rltk::RGB::named(rltk::WHITE),
             )]),
+            TextCol::new(vec![(
+                "Test Maze generator".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Diffusion-limited aggregation Generator (random preset)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Voronoi cells Generator (random preset)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Wave function collapse Generator (BSP interior sample)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Wave function collapse Generator (ascii sample)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Pipeline (BSP rooms + CA erosion + farthest stairs)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
+            TextCol::new(vec![(
+                "Test Terrain features (cave + rivers, lava, chasms)".to_string(),
+                rltk::RGB::named(rltk::WHITE),
+            )]),
         ]
     }
 
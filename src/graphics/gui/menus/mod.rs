@@ -1,4 +1,5 @@
 use rltk::{Rltk, RGB};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     ecs::systems::player::{input::get_input, InputType},
@@ -9,6 +10,10 @@ use super::inventory::INV_TEXT_COL;
 
 pub mod main_menu;
 
+#[cfg(feature = "map_gen_testing")]
+pub mod map_editor;
+#[cfg(feature = "map_gen_testing")]
+pub mod map_gen_config;
 #[cfg(feature = "map_gen_testing")]
 pub mod map_testing;
 
@@ -20,7 +25,7 @@ pub enum MenuAction {
 
 pub type StrCol = (String, RGB);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextCol {
     pub strings: Vec<StrCol>,
     pub bg: rltk::RGB,
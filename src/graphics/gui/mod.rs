@@ -37,6 +37,8 @@ pub enum PopuSelection {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MainMenuSelection {
     NewGame,
+    Seed,
+    Continue,
     #[cfg(feature = "map_gen_testing")]
     MapGenTesting,
     Controls,
@@ -92,7 +94,7 @@ impl GuiDrawer {
         GuiMapGenTestingManager::new(x, y, width, height)
     }
 
-    pub fn draw_ui(&self, ecs: &World, ctx: &mut Rltk) {
+    pub fn draw_ui(&self, ecs: &World, ctx: &mut Rltk, seed: u64) {
         ctx.set_active_console(CHAR_CONSOLE_INDEX);
         // Draw mouse cursor
         let mouse_pos = ctx.mouse_pos();
@@ -104,6 +106,8 @@ impl GuiDrawer {
 
         self.draw_gamelog(ecs, ctx);
 
+        self.draw_seed(ctx, seed);
+
         //self.draw_cursor_tooltips(ecs, ctx);
     }
 
@@ -153,6 +157,16 @@ impl GuiDrawer {
         }
     }
 
+    fn draw_seed(&self, ctx: &mut Rltk, seed: u64) {
+        ctx.print_color(
+            2,
+            self.window_height - 1,
+            RGB::named(rltk::GREY),
+            RGB::named(rltk::BLACK),
+            format!("Seed: {}", seed),
+        );
+    }
+
     fn draw_gamelog(&self, ecs: &World, ctx: &mut Rltk) {
         let log = ecs.fetch::<GameLog>();
 
@@ -267,7 +281,7 @@ impl GuiDrawer {
         }
     }
 
-    pub fn game_over(&self, ctx: &mut Rltk) -> PopuSelection {
+    pub fn game_over(&self, ctx: &mut Rltk, seed: u64) -> PopuSelection {
         ctx.set_active_console(CHAR_CONSOLE_INDEX);
         ctx.draw_box_double(
             (self.window_width / 2) - 20,
@@ -288,7 +302,7 @@ impl GuiDrawer {
             (self.window_height / 2) + 2,
             RGB::named(rltk::WHITE),
             RGB::named(rltk::BLACK),
-            "...",
+            format!("Seed: {}", seed),
         );
 
         ctx.print_color_centered(
@@ -306,7 +320,7 @@ impl GuiDrawer {
         }
     }
 
-    pub fn game_won(&self, ctx: &mut Rltk) -> PopuSelection {
+    pub fn game_won(&self, ctx: &mut Rltk, seed: u64) -> PopuSelection {
         ctx.set_active_console(CHAR_CONSOLE_INDEX);
         ctx.draw_box_double(
             (self.window_width / 2) - 20,
@@ -333,7 +347,7 @@ impl GuiDrawer {
             (self.window_height / 2) + 3,
             RGB::named(rltk::WHITE),
             RGB::named(rltk::BLACK),
-            "...",
+            format!("Seed: {}", seed),
         );
 
         ctx.print_color_centered(
@@ -377,8 +391,10 @@ Inventory - 'I'
 Equipped items - 'E'
 Selection in menus - 'Enter'
 
-Return to main menu (ends the game) - 'Esc'
+Save and return to main menu - 'Esc'
 
+New runs are recorded into replay.jsonl,
+play them with '--replay <file>'
 
 
 
@@ -5,6 +5,7 @@ use crate::{
     ecs::{
         components,
         game_state::TargetingAction,
+        replay::ReplayEvent,
         systems::player::{input::get_input, InputType},
         State,
     },
@@ -77,6 +78,10 @@ pub fn show_targeting(
                 ctx.set_bg(mouse_pos.x, mouse_pos.y, RGB::named(rltk::PINK));
                 if ctx.left_click {
                     gs.targeting_pos = mouse_pos + rltk::Point::new(x_left, y_up);
+                    gs.replay.record(ReplayEvent::Target {
+                        x: gs.targeting_pos.x,
+                        y: gs.targeting_pos.y,
+                    });
                     return TargetingMenuAction::Selected;
                 }
             } else {
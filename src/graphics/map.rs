@@ -5,7 +5,7 @@ use specs::{Entity, Join, WorldExt};
 use crate::{
     ecs::{components, State},
     levels::level::Level,
-    maps::{Map, TileType},
+    maps::{rect::Rect, Map, TileType},
 };
 
 use super::window::{SPRITE_16X16_CONSOLE_INDEX, SPRITE_32X32_CONSOLE_INDEX};
@@ -169,18 +169,23 @@ pub fn draw_map_with_fov(gs: &State, _ctx: &mut Rltk) {
     }
 }
 
-pub fn draw_map_without_fov(map: &Map, _ctx: &mut Rltk) {
+/// Whole map is drawn, except tiles in `hidden_area` (e.g. covered by window).
+pub fn draw_map_without_fov(map: &Map, hidden_area: Option<Rect>, _ctx: &mut Rltk) {
     let mut draw_batch = DrawBatch::new();
     draw_batch.target(SPRITE_16X16_CONSOLE_INDEX);
 
     let mut x = 0;
     let mut y = 0;
     for tile in map.tiles().iter() {
-        draw_batch.set(
-            rltk::Point::new(x, y),
-            rltk::ColorPair::new(RGB::from_f32(1.0, 1., 1.0), RGB::from_f32(0., 0., 0.)),
-            tile.texture_index().unwrap(),
-        );
+        let hidden =
+            hidden_area.filter(|area| area.x1 <= x && x <= area.x2 && area.y1 <= y && y <= area.y2);
+        if hidden.is_none() {
+            draw_batch.set(
+                rltk::Point::new(x, y),
+                rltk::ColorPair::new(RGB::from_f32(1.0, 1., 1.0), RGB::from_f32(0., 0., 0.)),
+                tile.texture_index().unwrap(),
+            );
+        }
 
         x += 1;
         if x > map.width - 1 {
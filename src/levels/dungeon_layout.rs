@@ -0,0 +1,371 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt, ResultExt};
+
+use crate::{rng, spawner::spawn_tables};
+
+use super::errors::{
+    BranchEntranceOutOfDungeon, EmptyBranch, EmptyDungeonLayout, LayoutFileIo, LayoutFileSerde,
+    LayoutMapTooSmall, LayoutUnknownPreset, LayoutUnknownSpawnTable, NoGeneratorsForDepth,
+    NoLayoutForDepth, Result,
+};
+use super::level::LevelType;
+use super::level_manager::MIN_MAP_SIZE;
+use super::presets;
+
+pub const DUNGEON_LAYOUT_PATH: &str = "./resources/dungeon_layout.json";
+
+/// One of possible generators for depth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorChoice {
+    pub level_type: LevelType,
+    /// name of spawn table from spawn tables file
+    pub spawn_table: String,
+    /// chance of being picked, relative to other generators of the same depth
+    #[serde(default = "default_weight")]
+    pub weight: usize,
+}
+
+fn default_weight() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DepthLayout {
+    pub generators: Vec<GeneratorChoice>,
+    /// if None, default map size from `State` is used
+    pub map_width: Option<usize>,
+    pub map_height: Option<usize>,
+    /// trapdoors dropping player two levels down in the same branch
+    #[serde(default)]
+    pub trapdoors: usize,
+    /// difficulty of level used by spawn tables, depth of level by default
+    #[serde(default)]
+    pub level_weight: Option<usize>,
+}
+
+/// Side branch entered by extra down stairs from level of main dungeon,
+/// its levels are right below that level. Last level of branch is a dead end.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BranchLayout {
+    pub name: String,
+    /// depth of main dungeon level with stairs down to branch
+    pub entrance_depth: usize,
+    pub levels: Vec<DepthLayout>,
+}
+
+/// Main dungeon is branch 0, `branches[i]` of layout is branch `i + 1`.
+pub const MAIN_BRANCH: usize = 0;
+
+/// Level in dungeon graph, depth is counted from the first level of main dungeon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LevelKey {
+    pub branch: usize,
+    pub depth: usize,
+}
+
+impl LevelKey {
+    pub fn main(depth: usize) -> LevelKey {
+        LevelKey {
+            branch: MAIN_BRANCH,
+            depth,
+        }
+    }
+
+    /// Level right below in the same branch.
+    pub fn below(self) -> LevelKey {
+        LevelKey {
+            depth: self.depth + 1,
+            ..self
+        }
+    }
+}
+
+/// Where exits of one level lead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitsPlan {
+    /// down stairs made by generator, None for dead end (stairs are removed);
+    /// below the last level of main dungeon there is no level, going there wins the game
+    pub next: Option<LevelKey>,
+    /// extra down stairs to branches
+    pub branches: Vec<LevelKey>,
+    pub trapdoors: Vec<LevelKey>,
+}
+
+/// Describes whole dungeon, `levels[depth]` is used for level of main dungeon at given depth.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DungeonLayout {
+    pub levels: Vec<DepthLayout>,
+    #[serde(default)]
+    pub branches: Vec<BranchLayout>,
+}
+
+impl DungeonLayout {
+    /// Spawn tables and presets have to be loaded first (used for validation).
+    pub fn load_from_file(path: &str) -> Result<DungeonLayout> {
+        let contents = fs::read_to_string(path).context(LayoutFileIo { path })?;
+        let layout: DungeonLayout =
+            serde_json::from_str(&contents).context(LayoutFileSerde { path })?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.levels.is_empty(), EmptyDungeonLayout);
+        for branch in self.branches.iter() {
+            ensure!(
+                !branch.levels.is_empty(),
+                EmptyBranch {
+                    branch: &branch.name
+                }
+            );
+            ensure!(
+                branch.entrance_depth < self.levels.len(),
+                BranchEntranceOutOfDungeon {
+                    branch: &branch.name,
+                    depth: branch.entrance_depth,
+                }
+            );
+        }
+
+        let spawn_table_names = spawn_tables::spawn_table_names();
+        let preset_names = presets::preset_names();
+        for branch in 0..=self.branches.len() {
+            let (levels, first_depth) = self.branch_levels(branch).unwrap();
+            let branch_name = self.branch_name(branch);
+            for (i, depth_layout) in levels.iter().enumerate() {
+                let depth = first_depth + i;
+                ensure!(
+                    depth_layout.generators.iter().any(|gen| gen.weight > 0),
+                    NoGeneratorsForDepth {
+                        depth,
+                        branch: &branch_name
+                    }
+                );
+                let width = depth_layout.map_width.unwrap_or(MIN_MAP_SIZE);
+                let height = depth_layout.map_height.unwrap_or(MIN_MAP_SIZE);
+                ensure!(
+                    width >= MIN_MAP_SIZE && height >= MIN_MAP_SIZE,
+                    LayoutMapTooSmall {
+                        width,
+                        height,
+                        depth,
+                        branch: &branch_name,
+                    }
+                );
+                for generator in depth_layout.generators.iter() {
+                    ensure!(
+                        spawn_table_names.contains(&generator.spawn_table),
+                        LayoutUnknownSpawnTable {
+                            name: &generator.spawn_table,
+                            depth,
+                            branch: &branch_name,
+                        }
+                    );
+                    for name in generator.level_type.used_presets() {
+                        ensure!(
+                            preset_names.contains(&name),
+                            LayoutUnknownPreset {
+                                name,
+                                depth,
+                                branch: &branch_name,
+                            }
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of levels in main dungeon.
+    pub fn depth_num(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Levels of branch with depth of the first one.
+    fn branch_levels(&self, branch: usize) -> Option<(&[DepthLayout], usize)> {
+        if branch == MAIN_BRANCH {
+            Some((&self.levels, 0))
+        } else {
+            self.branches
+                .get(branch - 1)
+                .map(|b| (&b.levels[..], b.entrance_depth + 1))
+        }
+    }
+
+    pub fn branch_name(&self, branch: usize) -> String {
+        if branch == MAIN_BRANCH {
+            "main dungeon".to_string()
+        } else {
+            self.branches
+                .get(branch - 1)
+                .map(|b| b.name.clone())
+                .unwrap_or_else(|| format!("branch {}", branch))
+        }
+    }
+
+    pub fn depth_layout(&self, key: LevelKey) -> Option<&DepthLayout> {
+        let (levels, first_depth) = self.branch_levels(key.branch)?;
+        levels.get(key.depth.checked_sub(first_depth)?)
+    }
+
+    /// Level with stairs down to `key`, None for the first level of main dungeon.
+    pub fn parent(&self, key: LevelKey) -> Option<LevelKey> {
+        let (_levels, first_depth) = self.branch_levels(key.branch)?;
+        if key.branch != MAIN_BRANCH && key.depth == first_depth {
+            Some(LevelKey::main(first_depth - 1))
+        } else {
+            key.depth
+                .checked_sub(1)
+                .map(|depth| LevelKey { depth, ..key })
+        }
+    }
+
+    pub fn exits_plan(&self, key: LevelKey) -> ExitsPlan {
+        let below = key.below();
+        let next = if key.branch == MAIN_BRANCH || self.depth_layout(below).is_some() {
+            Some(below)
+        } else {
+            None
+        };
+
+        let branches = if key.branch == MAIN_BRANCH {
+            self.branches
+                .iter()
+                .enumerate()
+                .filter(|(_i, branch)| branch.entrance_depth == key.depth)
+                .map(|(i, _branch)| LevelKey {
+                    branch: i + 1,
+                    depth: below.depth,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let trapdoor_destination = below.below();
+        let trapdoors = match self.depth_layout(key) {
+            Some(depth_layout) if self.depth_layout(trapdoor_destination).is_some() => {
+                vec![trapdoor_destination; depth_layout.trapdoors]
+            }
+            _ => vec![],
+        };
+
+        ExitsPlan {
+            next,
+            branches,
+            trapdoors,
+        }
+    }
+
+    /// Randomly picks one of generators for level (using their weights).
+    pub fn roll_generator(&self, key: LevelKey) -> Result<&GeneratorChoice> {
+        let depth_layout = self.depth_layout(key).context(NoLayoutForDepth {
+            depth: key.depth,
+            branch: self.branch_name(key.branch),
+        })?;
+        let total_weight: usize = depth_layout.generators.iter().map(|gen| gen.weight).sum();
+        ensure!(
+            total_weight > 0,
+            NoGeneratorsForDepth {
+                depth: key.depth,
+                branch: self.branch_name(key.branch),
+            }
+        );
+
+        let mut roll = rng::range(1, total_weight as i32) as usize;
+        for generator in depth_layout.generators.iter() {
+            if roll <= generator.weight {
+                return Ok(generator);
+            }
+            roll -= generator.weight;
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::Error;
+    use crate::spawner::raws;
+
+    fn load_layout(json: &str) -> Result<DungeonLayout> {
+        raws::load_raws(raws::RAWS_DIR).unwrap();
+        spawn_tables::load_spawn_tables(spawn_tables::SPAWN_TABLES_PATH).unwrap();
+        presets::load_presets(presets::PRESETS_PATH).unwrap();
+        let layout: DungeonLayout = serde_json::from_str(json).unwrap();
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    #[test]
+    fn dungeon_layout_file_is_valid() {
+        load_layout(&fs::read_to_string(DUNGEON_LAYOUT_PATH).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unknown_spawn_table_is_reported() {
+        let result = load_layout(
+            r#"{ "levels": [
+                { "generators": [ { "level_type": { "Cave": {} }, "spawn_table": "caves" } ] },
+                { "generators": [ { "level_type": { "Cave": {} }, "spawn_table": "grotto" } ] }
+            ] }"#,
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::LayoutUnknownSpawnTable { name, depth: 1, .. }) if name == "grotto"
+        ));
+    }
+
+    #[test]
+    fn unknown_preset_is_reported() {
+        let result = load_layout(
+            r#"{ "levels": [
+                { "generators": [ { "level_type": { "Preset": "nope" }, "spawn_table": "caves" } ] }
+            ] }"#,
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::LayoutUnknownPreset { name, depth: 0, .. }) if name == "nope"
+        ));
+    }
+
+    #[test]
+    fn too_small_map_is_reported() {
+        let result = load_layout(
+            r#"{ "levels": [
+                { "generators": [ { "level_type": { "Cave": {} }, "spawn_table": "caves" } ] },
+                {
+                    "generators": [ { "level_type": { "Cave": {} }, "spawn_table": "caves" } ],
+                    "map_width": 2
+                }
+            ] }"#,
+        );
+
+        assert!(matches!(
+            result,
+            Err(Error::LayoutMapTooSmall {
+                width: 2,
+                depth: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn empty_layout_is_reported() {
+        assert!(matches!(
+            load_layout(r#"{ "levels": [] }"#),
+            Err(Error::EmptyDungeonLayout)
+        ));
+    }
+}
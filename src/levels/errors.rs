@@ -10,4 +10,110 @@ pub enum Error {
     #[snafu(context(false))]
     #[snafu(display("Error in maps: {:?}", source.to_string()))]
     MapError { source: maps::Error },
+
+    #[snafu(display("Unable to read dungeon layout file {}: {}", path, source))]
+    LayoutFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to parse dungeon layout file {}: {}", path, source))]
+    LayoutFileSerde {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[snafu(display("Dungeon layout has no levels"))]
+    EmptyDungeonLayout,
+
+    #[snafu(display("Dungeon layout has no generators for depth {} of {}", depth, branch))]
+    NoGeneratorsForDepth { depth: usize, branch: String },
+
+    #[snafu(display("Dungeon layout doesn't define depth {} of {}", depth, branch))]
+    NoLayoutForDepth { depth: usize, branch: String },
+
+    #[snafu(display(
+        "Unknown spawn table \"{}\" used at depth {} of {}",
+        name,
+        depth,
+        branch
+    ))]
+    LayoutUnknownSpawnTable {
+        name: String,
+        depth: usize,
+        branch: String,
+    },
+
+    #[snafu(display(
+        "Unknown map generator preset \"{}\" used at depth {} of {}",
+        name,
+        depth,
+        branch
+    ))]
+    LayoutUnknownPreset {
+        name: String,
+        depth: usize,
+        branch: String,
+    },
+
+    #[snafu(display(
+        "Map size {}x{} used at depth {} of {} is too small",
+        width,
+        height,
+        depth,
+        branch
+    ))]
+    LayoutMapTooSmall {
+        width: usize,
+        height: usize,
+        depth: usize,
+        branch: String,
+    },
+
+    #[snafu(display("Map size {}x{} is too small for level", width, height))]
+    MapTooSmall { width: usize, height: usize },
+
+    #[snafu(display(
+        "No free tile for exit to {:?} in {} generated maps",
+        destination,
+        tries
+    ))]
+    NoTileForExit {
+        destination: super::dungeon_layout::LevelKey,
+        tries: usize,
+    },
+
+    #[snafu(display("Dungeon branch {} has no levels", branch))]
+    EmptyBranch { branch: String },
+
+    #[snafu(display(
+        "Entrance of dungeon branch {} is at depth {}, which isn't in main dungeon",
+        branch,
+        depth
+    ))]
+    BranchEntranceOutOfDungeon { branch: String, depth: usize },
+
+    #[snafu(display("Unable to read map generator presets file {}: {}", path, source))]
+    PresetsFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to parse map generator presets file {}: {}", path, source))]
+    PresetsFileSerde {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[snafu(display("Map generator preset {} uses unknown preset \"{}\"", preset, name))]
+    PresetUnknownReference { name: String, preset: String },
+
+    #[snafu(display("Map generator preset {} refers to itself", name))]
+    PresetCycle { name: String },
+
+    #[snafu(display("Unknown map generator \"{}\"", name))]
+    UnknownGeneratorName { name: String },
+
+    #[snafu(display("Incorrect map size \"{}\", expected WIDTHxHEIGHT", size))]
+    IncorrectMapSize { size: String },
 }
@@ -1,23 +1,333 @@
-use crate::maps::Map;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt};
 
-#[derive(Debug, Clone, Copy)]
+use crate::maps::generators::{
+    basic_dungeon::{BasicDungeonMap, BasicDungeonMapConfig},
+    bsp::{interior::BSPInteriorGen, BSPConfig, BSPDungeonGen},
+    cellular_automata::{CAMapGen, CAMapGenConfig},
+    dla::{DLAConfig, DLAGen},
+    drunkard_walk::{DrunkardWalkConfig, DrunkardWalkGen},
+    maze::{MazeConfig, MazeGen},
+    modifiers::{MapModifier, SpawnAreasPartition},
+    pipeline::PipelineGen,
+    prefab::PrefabGen,
+    stairs::StairsPlacement,
+    terrain::TerrainConfig,
+    test_map::TestMap,
+    voronoi::{VoronoiConfig, VoronoiGen},
+    wfc::{WfcConfig, WfcGen},
+    MapGenerator,
+};
+use crate::maps::{
+    self,
+    errors::{GeneratorPresetCycle, UnknownGeneratorPreset},
+    vaults::Vault,
+    Map, TileType,
+};
+use crate::rng;
+use crate::spawner::SpawnReport;
+
+use super::dungeon_layout::{LevelKey, MAIN_BRANCH};
+use super::presets;
+
+/// Map generator used for level, with its config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LevelType {
-    Cave,
-    BasicDungeon,
-    BSPDungeon,
-    BSPInterior,
-    DrunkardWalk,
+    Cave(CAMapGenConfig),
+    BasicDungeon(BasicDungeonMapConfig),
+    BSPDungeon(BSPConfig),
+    BSPInterior(BSPConfig),
+    DrunkardWalk(DrunkardWalkConfig),
+    Maze(MazeConfig),
+    DLA(DLAConfig),
+    Voronoi(VoronoiConfig),
+    /// map made by `sample` level type is used as sample for wave function collapse
+    Wfc {
+        sample: Box<LevelType>,
+        #[serde(default)]
+        config: WfcConfig,
+    },
+    /// map of `initial` level type changed by modifiers, one after another
+    Pipeline {
+        initial: Box<LevelType>,
+        modifiers: Vec<MapModifier>,
+    },
+    /// name of ascii map file in prefabs directory
+    Prefab(String),
+    /// name of level type from presets file (see `presets`)
+    Preset(String),
     TestLevel,
 }
 
-#[derive(Debug, Clone)]
+impl LevelType {
+    /// BSP rooms eroded by cellular automata, with down stairs far away from up stairs.
+    /// Corridors are widened first, so erosion doesn't fill them.
+    pub fn eroded_bsp_dungeon() -> LevelType {
+        LevelType::Pipeline {
+            initial: Box::new(LevelType::BSPDungeon(Default::default())),
+            modifiers: vec![
+                MapModifier::WidenCorridors,
+                MapModifier::CellularAutomata { iterations: 2 },
+                MapModifier::CullUnreachable,
+                MapModifier::Stairs(StairsPlacement::Farthest),
+                MapModifier::SpawnAreas(SpawnAreasPartition::Regions { tiles: 60 }),
+            ],
+        }
+    }
+
+    /// Name of variant, e.g. `Cave`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LevelType::Cave(_) => "Cave",
+            LevelType::BasicDungeon(_) => "BasicDungeon",
+            LevelType::BSPDungeon(_) => "BSPDungeon",
+            LevelType::BSPInterior(_) => "BSPInterior",
+            LevelType::DrunkardWalk(_) => "DrunkardWalk",
+            LevelType::Maze(_) => "Maze",
+            LevelType::DLA(_) => "DLA",
+            LevelType::Voronoi(_) => "Voronoi",
+            LevelType::Wfc { .. } => "Wfc",
+            LevelType::Pipeline { .. } => "Pipeline",
+            LevelType::Prefab(_) => "Prefab",
+            LevelType::Preset(_) => "Preset",
+            LevelType::TestLevel => "TestLevel",
+        }
+    }
+
+    /// Names of presets used by level type, also by nested ones.
+    pub fn used_presets(&self) -> Vec<String> {
+        match self {
+            LevelType::Preset(name) => vec![name.clone()],
+            LevelType::Wfc { sample, .. } => sample.used_presets(),
+            LevelType::Pipeline { initial, .. } => initial.used_presets(),
+            _ => vec![],
+        }
+    }
+
+    /// Terrain features pass is added at the end of pipeline, other level types
+    /// become initial generator of new pipeline.
+    pub fn with_terrain(self, config: TerrainConfig) -> LevelType {
+        match self {
+            LevelType::Pipeline {
+                initial,
+                mut modifiers,
+            } => {
+                modifiers.push(MapModifier::Terrain(config));
+                LevelType::Pipeline { initial, modifiers }
+            }
+            level_type => LevelType::Pipeline {
+                initial: Box::new(level_type),
+                modifiers: vec![MapModifier::Terrain(config)],
+            },
+        }
+    }
+
+    /// `vaults` are used only by generators which can place them.
+    pub fn map_generator(
+        &self,
+        width: usize,
+        height: usize,
+        vaults: Vec<Vault>,
+    ) -> maps::errors::Result<Box<dyn MapGenerator>> {
+        self.map_generator_with_presets(width, height, vaults, &mut vec![])
+    }
+
+    /// `used_presets` are names of presets resolved on the way to this level type,
+    /// so preset which (even indirectly) uses itself is reported instead of endless recursion.
+    fn map_generator_with_presets(
+        &self,
+        width: usize,
+        height: usize,
+        vaults: Vec<Vault>,
+        used_presets: &mut Vec<String>,
+    ) -> maps::errors::Result<Box<dyn MapGenerator>> {
+        Ok(match self {
+            LevelType::TestLevel => Box::new(TestMap::new(width, height)),
+            LevelType::Cave(config) => Box::new(
+                CAMapGen::new(width, height)?
+                    .with_config(config.clone())
+                    .with_vaults(vaults),
+            ),
+            LevelType::BasicDungeon(config) => {
+                Box::new(BasicDungeonMap::new(width, height, config.clone()).with_vaults(vaults))
+            }
+            LevelType::BSPDungeon(config) => {
+                Box::new(BSPDungeonGen::new(width, height, config.clone()).with_vaults(vaults))
+            }
+            LevelType::BSPInterior(config) => {
+                Box::new(BSPInteriorGen::new(width, height, config.clone()))
+            }
+            LevelType::DrunkardWalk(config) => {
+                Box::new(DrunkardWalkGen::new(width, height, config.clone()))
+            }
+            LevelType::Maze(config) => Box::new(MazeGen::new(width, height, config.clone())),
+            LevelType::DLA(config) => Box::new(DLAGen::new(width, height, config.clone())),
+            LevelType::Voronoi(config) => Box::new(VoronoiGen::new(width, height, config.clone())),
+            LevelType::Prefab(name) => Box::new(PrefabGen::new(name, width, height)),
+            LevelType::Preset(name) => {
+                ensure!(!used_presets.contains(name), GeneratorPresetCycle { name });
+                used_presets.push(name.clone());
+                presets::preset(name)
+                    .context(UnknownGeneratorPreset { name })?
+                    .map_generator_with_presets(width, height, vaults, used_presets)?
+            }
+            LevelType::Wfc { sample, config } => {
+                // prefab samples keep their own size, they aren't padded with walls
+                let sample_gen = match sample.as_ref() {
+                    LevelType::Prefab(name) => Box::new(PrefabGen::new(name, 0, 0)),
+                    sample => {
+                        sample.map_generator_with_presets(width, height, vec![], used_presets)?
+                    }
+                };
+                Box::new(WfcGen::new(width, height, sample_gen, config.clone()))
+            }
+            LevelType::Pipeline { initial, modifiers } => Box::new(
+                PipelineGen::new(
+                    initial.map_generator_with_presets(width, height, vec![], used_presets)?,
+                    modifiers.clone(),
+                )
+                .with_vaults(vaults),
+            ),
+        })
+    }
+}
+
+/// Other exits are kept at least this far from stairs and trapdoors.
+const EXITS_CLEARANCE: usize = 3;
+
+/// Stairs or trapdoor leading to other level.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelExit {
+    pub x: usize,
+    pub y: usize,
+    pub destination: LevelKey,
+    /// where entity arrives on destination level, None until it's created
+    /// (and for trapdoors, falling entity lands at the same position)
+    pub arrival: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Level {
     pub map: Map,
     pub level_index: usize,
     pub depth: usize,
+    /// see `dungeon_layout::MAIN_BRANCH`
+    #[serde(default)]
+    pub branch: usize,
 
-    // used for spawn tables
+    /// difficulty used by spawn tables as depth, see `DepthLayout::level_weight`
     pub level_weight: usize,
+    /// many elite monsters were spawned, player is warned on arrival
+    #[serde(default)]
+    pub dangerous: bool,
+    #[serde(default)]
+    pub spawn_report: SpawnReport,
 
     pub spawn_areas: Vec<Vec<(usize, usize)>>,
+    /// vaults have fixed spawns, spawn tables don't use them
+    #[serde(default)]
+    pub vault_areas: Vec<Vec<(usize, usize)>>,
+    #[serde(default)]
+    pub exits: Vec<LevelExit>,
+
+    /// name of spawn table, wandering monsters are taken from it
+    #[serde(default)]
+    pub spawn_table: String,
+    /// turn when player left level (or when it was created)
+    #[serde(default)]
+    pub last_active_turn: usize,
+    /// where player starts the run, only the first level has it
+    #[serde(default)]
+    pub start: Option<(usize, usize)>,
+}
+
+impl Level {
+    pub fn key(&self) -> LevelKey {
+        LevelKey {
+            branch: self.branch,
+            depth: self.depth,
+        }
+    }
+
+    pub fn exit_at(&self, x: usize, y: usize) -> Option<&LevelExit> {
+        self.exits.iter().find(|exit| (exit.x, exit.y) == (x, y))
+    }
+
+    /// Destination of exit at position, levels made before exits were recorded
+    /// are connected only to levels right above and below.
+    pub fn exit_destination(&self, x: usize, y: usize) -> Option<LevelKey> {
+        match self.exit_at(x, y) {
+            Some(exit) => Some(exit.destination),
+            None => match self.map.tile_at_xy(x, y) {
+                TileType::StairsDown | TileType::Chasm | TileType::Trapdoor => {
+                    Some(self.key().below())
+                }
+                TileType::StairsUp if self.branch == MAIN_BRANCH => {
+                    self.depth.checked_sub(1).map(LevelKey::main)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Places `tile` (stairs or trapdoor) leading to `destination` on free tile of spawn areas,
+    /// away from other exits if possible. Returns false if there is no free tile.
+    pub fn add_exit(&mut self, tile: TileType, destination: LevelKey) -> bool {
+        let exits = self
+            .map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_i, tile)| {
+                matches!(
+                    tile,
+                    TileType::StairsUp | TileType::StairsDown | TileType::Trapdoor
+                )
+            })
+            .map(|(i, _tile)| self.map.index_to_xy(i))
+            .collect::<Vec<_>>();
+        let candidates = self
+            .spawn_areas
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&(x, y)| self.map.tile_at_xy(x, y) == TileType::Floor)
+            .collect::<Vec<_>>();
+        let far_from_exits = candidates
+            .iter()
+            .copied()
+            .filter(|&(x, y)| {
+                exits.iter().all(|&(ex, ey)| {
+                    (x as i32 - ex as i32)
+                        .abs()
+                        .max((y as i32 - ey as i32).abs())
+                        > EXITS_CLEARANCE as i32
+                })
+            })
+            .collect::<Vec<_>>();
+        let candidates = if far_from_exits.is_empty() {
+            candidates
+        } else {
+            far_from_exits
+        };
+        if candidates.is_empty() {
+            return false;
+        }
+
+        let (x, y) = candidates[rng::range(0, candidates.len() as i32 - 1) as usize];
+        let index = self.map.xy_to_index(x, y);
+        self.map.tiles[index] = tile;
+        self.map.update_blocked_with_blocking_tiles();
+        for area in self.spawn_areas.iter_mut() {
+            area.retain(|&pos| pos != (x, y));
+        }
+        self.spawn_areas.retain(|area| !area.is_empty());
+        self.exits.push(LevelExit {
+            x,
+            y,
+            destination,
+            arrival: None,
+        });
+        true
+    }
 }
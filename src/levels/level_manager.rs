@@ -1,20 +1,23 @@
 use rltk::Point;
+use serde::{Deserialize, Serialize};
+use snafu::ensure;
 
-use crate::maps::generators::{
-    basic_dungeon::{BasicDungeonMap, BasicDungeonMapConfig},
-    bsp::{interior::BSPInteriorGen, BSPConfig, BSPDungeonGen},
-    cellular_automata::CAMapGen,
-    drunkard_walk::{DrunkardWalkConfig, DrunkardWalkGen},
-    generate_map_and_spawn_areas,
-    test_map::TestMap,
-};
+use crate::maps::{ascii::MapEntity, generators::generate_valid_map, vaults, TileType};
+use crate::spawner::SpawnReport;
 
-use super::level::{Level, LevelType};
+use super::dungeon_layout::{ExitsPlan, LevelKey};
+use super::level::{Level, LevelExit, LevelType};
 
-use crate::levels::errors::Result;
+use crate::levels::errors::{MapTooSmall, NoTileForExit, Result};
+
+/// Map needs at least one tile inside of its edges.
+pub const MIN_MAP_SIZE: usize = 3;
+
+/// How many maps are generated for level, when branch stairs or trapdoors don't fit into them.
+const MAX_EXITS_TRIES: usize = 5;
 
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LevelManager {
     pub current_level_index: usize,
     pub levels: Vec<Level>,
@@ -41,50 +44,225 @@ impl LevelManager {
         &mut self.levels[self.current_level_index]
     }
 
-    /// returns new level index
+    pub fn level_index(&self, key: LevelKey) -> Option<usize> {
+        self.levels.iter().position(|level| level.key() == key)
+    }
+
+    /// Returns new level index and entities placed on map by generator.
+    /// `parent` is level with stairs down to the new one and position of these stairs,
+    /// up stairs of new level are placed at the same position (if possible, the closest position
+    /// inside of map otherwise).
+    /// Map is generated again if branch stairs or trapdoors don't fit into it.
     pub fn crete_new_level(
         &mut self,
-        level_type: LevelType,
+        level_type: &LevelType,
         width: usize,
         height: usize,
-        depth: usize,
-        prev_down_stairs_pos: Option<Point>,
-    ) -> Result<usize> {
-        let (map, spawn_areas) = match level_type {
-            LevelType::TestLevel => {
-                let gen = TestMap::new(width, height);
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
-            }
-            LevelType::Cave => {
-                let gen = CAMapGen::new(width, height)?;
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
-            }
-            LevelType::BasicDungeon => {
-                let gen = BasicDungeonMap::new(width, height, BasicDungeonMapConfig::default());
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
-            }
-            LevelType::BSPDungeon => {
-                let gen = BSPDungeonGen::new(width, height, BSPConfig::default());
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
-            }
-            LevelType::BSPInterior => {
-                let gen = BSPInteriorGen::new(width, height, BSPConfig::default());
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
+        key: LevelKey,
+        parent: Option<(LevelKey, (usize, usize))>,
+        exits: &ExitsPlan,
+    ) -> Result<(usize, Vec<MapEntity>)> {
+        ensure!(
+            width >= MIN_MAP_SIZE && height >= MIN_MAP_SIZE,
+            MapTooSmall { width, height }
+        );
+        let vaults = vaults::roll_vaults_for_depth(key.depth);
+        let mut generator = level_type.map_generator(width, height, vaults)?;
+        // parent level can be bigger, stairs are moved inside of edges of smaller map
+        let prev_down_stairs_pos =
+            parent.map(|(_key, (x, y))| Point::new(x.clamp(1, width - 2), y.clamp(1, height - 2)));
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            let generated = generate_valid_map(generator.as_mut(), prev_down_stairs_pos)?;
+            let mut new_level = Level {
+                map: generated.map,
+                depth: key.depth,
+                branch: key.branch,
+                level_index: self.levels.len(),
+                level_weight: key.depth,
+                dangerous: false,
+                spawn_report: SpawnReport::default(),
+                spawn_areas: generated.spawn_areas,
+                vault_areas: generated.vault_areas,
+                exits: vec![],
+                spawn_table: String::new(),
+                last_active_turn: 0,
+                start: None,
+            };
+            add_stairs_exits(&mut new_level, parent, exits.next);
+            let missing_exit = exits
+                .branches
+                .iter()
+                .map(|&destination| (TileType::StairsDown, destination))
+                .chain(
+                    exits
+                        .trapdoors
+                        .iter()
+                        .map(|&destination| (TileType::Trapdoor, destination)),
+                )
+                .find(|&(tile, destination)| !new_level.add_exit(tile, destination));
+
+            match missing_exit {
+                None => {
+                    self.levels.push(new_level);
+                    return Ok((self.levels.len() - 1, generated.entities));
+                }
+                Some((_tile, destination)) if tries >= MAX_EXITS_TRIES => {
+                    return NoTileForExit { destination, tries }.fail()
+                }
+                Some(_) => generator.reset(),
             }
-            LevelType::DrunkardWalk => {
-                let gen = DrunkardWalkGen::new(width, height, DrunkardWalkConfig::default());
-                generate_map_and_spawn_areas(gen, prev_down_stairs_pos)?
+        }
+    }
+}
+
+/// Up stairs made by generator lead to parent, down stairs to `next` level.
+/// If there is no next level, down stairs are removed.
+fn add_stairs_exits(
+    level: &mut Level,
+    parent: Option<(LevelKey, (usize, usize))>,
+    next: Option<LevelKey>,
+) {
+    for i in 0..level.map.tiles.len() {
+        let (x, y) = level.map.index_to_xy(i);
+        match (level.map.tiles[i], parent, next) {
+            (TileType::StairsUp, Some((parent_key, arrival)), _) => level.exits.push(LevelExit {
+                x,
+                y,
+                destination: parent_key,
+                arrival: Some(arrival),
+            }),
+            (TileType::StairsDown, _, Some(next)) => level.exits.push(LevelExit {
+                x,
+                y,
+                destination: next,
+                arrival: None,
+            }),
+            (TileType::StairsDown, _, None) => level.map.tiles[i] = TileType::Floor,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::dungeon_layout::LevelKey;
+    use crate::maps::generators::{
+        basic_dungeon::BasicDungeonMapConfig, bsp::BSPConfig, cellular_automata::CAMapGenConfig,
+    };
+    use crate::rng;
+
+    #[test]
+    fn smaller_level_below_bigger_one_gets_up_stairs_inside_of_map() {
+        let level_types = [
+            LevelType::Cave(CAMapGenConfig::default()),
+            LevelType::BSPDungeon(BSPConfig::default()),
+            LevelType::BasicDungeon(BasicDungeonMapConfig {
+                rooms_min: 3,
+                rooms_max: 4,
+                room_size_min: 4,
+                room_size_max: 6,
+                ..BasicDungeonMapConfig::default()
+            }),
+        ];
+        let parent = Some((LevelKey::main(0), (63, 43)));
+        let key = LevelKey::main(1);
+        let exits = ExitsPlan {
+            next: Some(key.below()),
+            branches: vec![],
+            trapdoors: vec![],
+        };
+
+        for level_type in level_types.iter() {
+            for seed in 0..10 {
+                rng::reseed(seed);
+                let mut level_manager = LevelManager::new();
+                let (index, _entities) = level_manager
+                    .crete_new_level(level_type, 30, 20, key, parent, &exits)
+                    .unwrap();
+
+                let level = &level_manager.levels[index];
+                let up_stairs = level
+                    .exits
+                    .iter()
+                    .find(|exit| exit.destination == LevelKey::main(0))
+                    .unwrap();
+                assert!(up_stairs.x < 29 && up_stairs.y < 19, "{:?}", level_type);
+                assert_eq!(up_stairs.arrival, Some((63, 43)));
             }
+        }
+    }
+
+    #[test]
+    fn too_small_level_is_rejected() {
+        let exits = ExitsPlan {
+            next: None,
+            branches: vec![],
+            trapdoors: vec![],
         };
+        let parent = Some((LevelKey::main(0), (10, 10)));
+        let result = LevelManager::new().crete_new_level(
+            &LevelType::Cave(CAMapGenConfig::default()),
+            2,
+            20,
+            LevelKey::main(1),
+            parent,
+            &exits,
+        );
 
-        let new_level = Level {
-            map,
-            depth,
-            level_index: self.levels.len(),
-            level_weight: 1,
-            spawn_areas,
+        assert!(matches!(
+            result,
+            Err(crate::levels::Error::MapTooSmall {
+                width: 2,
+                height: 20
+            })
+        ));
+    }
+
+    #[test]
+    fn exit_without_free_tile_is_reported() {
+        // test map has single tile of spawn areas, at (30, 30)
+        let branch = LevelKey {
+            branch: 1,
+            depth: 1,
+        };
+        let mut exits = ExitsPlan {
+            next: None,
+            branches: vec![branch],
+            trapdoors: vec![],
         };
-        self.levels.push(new_level);
-        Ok(self.levels.len() - 1)
+        let mut level_manager = LevelManager::new();
+        rng::reseed(1);
+        let (index, _entities) = level_manager
+            .crete_new_level(
+                &LevelType::TestLevel,
+                40,
+                40,
+                LevelKey::main(1),
+                None,
+                &exits,
+            )
+            .unwrap();
+        assert!(level_manager.levels[index].exits.iter().any(|exit| (
+            exit.x,
+            exit.y,
+            exit.destination
+        ) == (30, 30, branch)));
+
+        exits.trapdoors.push(LevelKey::main(2));
+        let result = level_manager.crete_new_level(
+            &LevelType::TestLevel,
+            40,
+            40,
+            LevelKey::main(1),
+            None,
+            &exits,
+        );
+        assert!(matches!(
+            result,
+            Err(crate::levels::Error::NoTileForExit { tries, .. }) if tries == MAX_EXITS_TRIES
+        ));
     }
 }
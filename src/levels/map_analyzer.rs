@@ -0,0 +1,405 @@
+//! Runs map generators many times (without window) and collects statistics about
+//! generated maps, so generators and changes in them can be compared.
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use rltk::Point;
+use serde::Serialize;
+use snafu::OptionExt;
+
+use crate::maps::generators::{dla::DLAConfig, voronoi::VoronoiConfig};
+use crate::maps::{validation, Map, TileType};
+use crate::rng;
+
+use super::errors::{IncorrectMapSize, Result, UnknownGeneratorName};
+use super::level::LevelType;
+
+pub const GENERATOR_NAMES: [&str; 11] = [
+    "BasicDungeon",
+    "Cave",
+    "BSPDungeon",
+    "BSPInterior",
+    "DrunkardWalk",
+    "Maze",
+    "DLA",
+    "Voronoi",
+    "Wfc",
+    "Pipeline",
+    "Cave+Terrain",
+];
+
+/// Level type with default config, names are the same as `LevelType` variants
+/// (prefabs are named `Prefab:<prefab name>`, generator presets `DLA:<preset name>`
+/// and `Voronoi:<preset name>`, wave function collapse with sample from other level
+/// type `Wfc:<level type name>`, plain `Wfc` uses `BSPInterior` sample, presets
+/// `Preset:<preset name>`).
+/// `Pipeline` is `LevelType::eroded_bsp_dungeon`, `+Terrain` suffix adds terrain features pass.
+pub fn level_type_by_name(name: &str) -> Result<LevelType> {
+    if let Some(base) = name.strip_suffix("+Terrain") {
+        return Ok(level_type_by_name(base)?.with_terrain(Default::default()));
+    }
+    if let Some(prefab) = name.strip_prefix("Prefab:") {
+        return Ok(LevelType::Prefab(prefab.to_string()));
+    }
+    if let Some(preset) = name.strip_prefix("Preset:") {
+        return Ok(LevelType::Preset(preset.to_string()));
+    }
+    if let Some(sample) = name.strip_prefix("Wfc:") {
+        return Ok(LevelType::Wfc {
+            sample: Box::new(level_type_by_name(sample)?),
+            config: Default::default(),
+        });
+    }
+    if let Some(preset) = name.strip_prefix("DLA:") {
+        let config = DLAConfig::preset(preset).context(UnknownGeneratorName { name })?;
+        return Ok(LevelType::DLA(config));
+    }
+    if let Some(preset) = name.strip_prefix("Voronoi:") {
+        let config = VoronoiConfig::preset(preset).context(UnknownGeneratorName { name })?;
+        return Ok(LevelType::Voronoi(config));
+    }
+    let level_type = match name {
+        "BasicDungeon" => LevelType::BasicDungeon(Default::default()),
+        "Cave" => LevelType::Cave(Default::default()),
+        "BSPDungeon" => LevelType::BSPDungeon(Default::default()),
+        "BSPInterior" => LevelType::BSPInterior(Default::default()),
+        "DrunkardWalk" => LevelType::DrunkardWalk(Default::default()),
+        "Maze" => LevelType::Maze(Default::default()),
+        "DLA" => LevelType::DLA(Default::default()),
+        "Voronoi" => LevelType::Voronoi(Default::default()),
+        "Pipeline" => LevelType::eroded_bsp_dungeon(),
+        "Wfc" => LevelType::Wfc {
+            sample: Box::new(LevelType::BSPInterior(Default::default())),
+            config: Default::default(),
+        },
+        "TestLevel" => LevelType::TestLevel,
+        _ => return UnknownGeneratorName { name }.fail(),
+    };
+    Ok(level_type)
+}
+
+/// Parses size in `WIDTHxHEIGHT` format, e.g. `80x52`.
+pub fn parse_map_size(size: &str) -> Result<(usize, usize)> {
+    let mut parts = size
+        .split('x')
+        .map(|part| part.trim().parse::<usize>().ok());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(width)), Some(Some(height)), None) if width > 2 && height > 2 => {
+            Ok((width, height))
+        }
+        _ => IncorrectMapSize { size }.fail(),
+    }
+}
+
+pub struct AnalyzerConfig {
+    pub generators: Vec<String>,
+    pub sizes: Vec<(usize, usize)>,
+    /// every generator is run this many times for every size
+    pub runs: usize,
+    /// runs use seeds `first_seed..first_seed + runs`
+    pub first_seed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MinAvgMax {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl MinAvgMax {
+    fn from_values(values: &[f64]) -> Option<MinAvgMax> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(MinAvgMax {
+            min: values.iter().cloned().fold(f64::INFINITY, f64::min),
+            avg: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+/// Statistics of one generator for one map size.
+/// Values are collected only from maps which were generated and repaired successfully.
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratorReport {
+    pub generator: String,
+    pub width: usize,
+    pub height: usize,
+    pub runs: usize,
+    /// generator returned error
+    pub generation_failures: usize,
+    /// generated map had unreachable regions (they were connected by repair)
+    pub disconnected_maps: usize,
+    /// generated map couldn't be repaired
+    pub invalid_maps: usize,
+    /// error message and how many times it happened
+    pub errors: BTreeMap<String, usize>,
+    pub floor_tiles_perc: Option<MinAvgMax>,
+    pub spawn_areas_count: Option<MinAvgMax>,
+    pub spawn_area_size: Option<MinAvgMax>,
+    /// steps from up to down stairs
+    pub stairs_distance: Option<MinAvgMax>,
+    /// generation and repair time in milliseconds
+    pub generation_ms: Option<MinAvgMax>,
+}
+
+impl GeneratorReport {
+    pub fn failure_perc(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        ((self.generation_failures + self.invalid_maps) * 100) as f64 / self.runs as f64
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    floor_tiles_perc: Vec<f64>,
+    spawn_areas_count: Vec<f64>,
+    spawn_area_size: Vec<f64>,
+    stairs_distance: Vec<f64>,
+    generation_ms: Vec<f64>,
+}
+
+/// Analyzes every generator in every size. Global rng is reseeded before each run.
+pub fn analyze(config: &AnalyzerConfig) -> Result<Vec<GeneratorReport>> {
+    let mut reports = vec![];
+    for name in config.generators.iter() {
+        let level_type = level_type_by_name(name)?;
+        for &(width, height) in config.sizes.iter() {
+            reports.push(analyze_generator(name, &level_type, width, height, config));
+        }
+    }
+    Ok(reports)
+}
+
+fn analyze_generator(
+    name: &str,
+    level_type: &LevelType,
+    width: usize,
+    height: usize,
+    config: &AnalyzerConfig,
+) -> GeneratorReport {
+    let mut report = GeneratorReport {
+        generator: name.to_string(),
+        width,
+        height,
+        runs: config.runs,
+        generation_failures: 0,
+        disconnected_maps: 0,
+        invalid_maps: 0,
+        errors: BTreeMap::new(),
+        floor_tiles_perc: None,
+        spawn_areas_count: None,
+        spawn_area_size: None,
+        stairs_distance: None,
+        generation_ms: None,
+    };
+    let mut samples = Samples::default();
+
+    // player comes from previous level, so generators have to place up stairs too
+    let prev_down_stairs_pos = Point::new(width / 2, height / 2);
+
+    for seed in config.first_seed..config.first_seed + config.runs as u64 {
+        rng::reseed(seed);
+        let start = Instant::now();
+
+        let generated =
+            level_type
+                .map_generator(width, height, vec![])
+                .and_then(|mut generator| {
+                    generator.generate(Some(prev_down_stairs_pos))?;
+                    Ok((generator.map(), generator.spawn_areas()))
+                });
+        let (mut map, mut spawn_areas) = match generated {
+            Ok(generated) => generated,
+            Err(e) => {
+                report.generation_failures += 1;
+                *report.errors.entry(e.to_string()).or_insert(0) += 1;
+                continue;
+            }
+        };
+
+        map.update_blocked_with_blocking_tiles();
+        if validation::connected_regions(&map).len() > 1 {
+            report.disconnected_maps += 1;
+        }
+        let repaired =
+            validation::repair_map(&mut map, &mut spawn_areas, Some(prev_down_stairs_pos));
+        let generation_ms = start.elapsed().as_secs_f64() * 1000.0;
+        if let Err(e) = repaired {
+            report.invalid_maps += 1;
+            *report.errors.entry(e.to_string()).or_insert(0) += 1;
+            continue;
+        }
+
+        samples.generation_ms.push(generation_ms);
+        samples.floor_tiles_perc.push(map.floor_tiles_perc() as f64);
+        samples.spawn_areas_count.push(spawn_areas.len() as f64);
+        samples
+            .spawn_area_size
+            .extend(spawn_areas.iter().map(|area| area.len() as f64));
+        if let Some(distance) = stairs_distance(&map, prev_down_stairs_pos) {
+            samples.stairs_distance.push(distance as f64);
+        }
+    }
+
+    report.floor_tiles_perc = MinAvgMax::from_values(&samples.floor_tiles_perc);
+    report.spawn_areas_count = MinAvgMax::from_values(&samples.spawn_areas_count);
+    report.spawn_area_size = MinAvgMax::from_values(&samples.spawn_area_size);
+    report.stairs_distance = MinAvgMax::from_values(&samples.stairs_distance);
+    report.generation_ms = MinAvgMax::from_values(&samples.generation_ms);
+    report
+}
+
+/// Distance to the closest down stairs.
+fn stairs_distance(map: &Map, stairs_up: Point) -> Option<usize> {
+    let start = map.xy_to_index(stairs_up.x as usize, stairs_up.y as usize);
+    validation::distances_from(map, start)
+        .into_iter()
+        .zip(map.tiles.iter())
+        .filter(|(_distance, tile)| **tile == TileType::StairsDown)
+        .filter_map(|(distance, _tile)| distance)
+        .min()
+}
+
+pub fn reports_to_json(reports: &[GeneratorReport]) -> String {
+    serde_json::to_string_pretty(reports).expect("Unable to serialize map generators reports")
+}
+
+pub fn reports_to_table(reports: &[GeneratorReport]) -> String {
+    let avg_range = |value: &Option<MinAvgMax>| match value {
+        Some(v) => format!("{:.1} ({:.0}-{:.0})", v.avg, v.min, v.max),
+        None => "-".to_string(),
+    };
+
+    let mut table = format!(
+        "{:<14}{:>8}{:>6}{:>8}{:>8}{:>9}{:>20}{:>20}{:>20}{:>20}{:>20}\n",
+        "generator",
+        "size",
+        "runs",
+        "fail %",
+        "discon",
+        "invalid",
+        "floor %",
+        "spawn areas",
+        "area size",
+        "stairs dist",
+        "time ms",
+    );
+    for report in reports {
+        table.push_str(&format!(
+            "{:<14}{:>8}{:>6}{:>8.1}{:>8}{:>9}{:>20}{:>20}{:>20}{:>20}{:>20}\n",
+            report.generator,
+            format!("{}x{}", report.width, report.height),
+            report.runs,
+            report.failure_perc(),
+            report.disconnected_maps,
+            report.invalid_maps,
+            avg_range(&report.floor_tiles_perc),
+            avg_range(&report.spawn_areas_count),
+            avg_range(&report.spawn_area_size),
+            avg_range(&report.stairs_distance),
+            avg_range(&report.generation_ms),
+        ));
+    }
+
+    for report in reports.iter().filter(|report| !report.errors.is_empty()) {
+        table.push_str(&format!(
+            "\n{} {}x{} errors:\n",
+            report.generator, report.width, report.height
+        ));
+        for (error, count) in report.errors.iter() {
+            table.push_str(&format!("  {:>5}x {}\n", count, error));
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_generator_name_is_known() {
+        for name in GENERATOR_NAMES.iter() {
+            assert!(level_type_by_name(name).is_ok(), "{}", name);
+        }
+        assert!(level_type_by_name("Wfc:Maze").is_ok());
+        assert!(level_type_by_name("Prefab:boss_arena").is_ok());
+        assert!(level_type_by_name("Unknown").is_err());
+        assert!(level_type_by_name("DLA:unknown preset").is_err());
+    }
+
+    #[test]
+    fn map_size_is_parsed() {
+        assert_eq!(parse_map_size("80x52").unwrap(), (80, 52));
+        assert_eq!(parse_map_size(" 40 x 30 ").unwrap(), (40, 30));
+        for size in ["80", "80x", "2x10", "80x52x3", "axb"].iter() {
+            assert!(parse_map_size(size).is_err(), "{}", size);
+        }
+    }
+
+    #[test]
+    fn analyzer_reports_every_generator_and_size() {
+        let config = AnalyzerConfig {
+            generators: vec!["BSPDungeon".to_string(), "Cave".to_string()],
+            sizes: vec![(60, 40), (40, 30)],
+            runs: 2,
+            first_seed: 0,
+        };
+        let reports = analyze(&config).unwrap();
+
+        assert_eq!(reports.len(), 4);
+        for report in reports.iter() {
+            assert_eq!(report.runs, 2);
+            assert_eq!(report.failure_perc(), 0.0, "{:?}", report.errors);
+            let floor = report.floor_tiles_perc.as_ref().unwrap();
+            assert!(floor.min > 0.0 && floor.min <= floor.avg && floor.avg <= floor.max);
+            assert!(report.stairs_distance.as_ref().unwrap().min > 0.0);
+        }
+        let table = reports_to_table(&reports);
+        assert!(table.contains("BSPDungeon") && table.contains("40x30"));
+        assert!(reports_to_json(&reports).contains("\"stairs_distance\""));
+        // the same seeds give the same maps
+        let again = analyze(&config).unwrap();
+        for (report, other) in reports.iter().zip(again.iter()) {
+            assert_eq!(
+                report.floor_tiles_perc.as_ref().unwrap().avg,
+                other.floor_tiles_perc.as_ref().unwrap().avg
+            );
+        }
+    }
+
+    #[test]
+    fn generation_failures_are_counted() {
+        // rooms of default config don't fit into small map
+        let config = AnalyzerConfig {
+            generators: vec!["BasicDungeon".to_string()],
+            sizes: vec![(20, 20)],
+            runs: 2,
+            first_seed: 0,
+        };
+        let reports = analyze(&config).unwrap();
+
+        assert_eq!(reports[0].generation_failures, 2);
+        assert_eq!(reports[0].failure_perc(), 100.0);
+        assert_eq!(reports[0].errors.values().sum::<usize>(), 2);
+        assert!(reports[0].floor_tiles_perc.is_none());
+        assert!(reports_to_table(&reports).contains("errors:"));
+    }
+
+    #[test]
+    fn unknown_generator_fails_analysis() {
+        let config = AnalyzerConfig {
+            generators: vec!["Unknown".to_string()],
+            sizes: vec![(80, 50)],
+            runs: 1,
+            first_seed: 0,
+        };
+        assert!(analyze(&config).is_err());
+    }
+}
@@ -1,5 +1,8 @@
+pub mod dungeon_layout;
 pub mod errors;
 pub mod level;
 pub mod level_manager;
+pub mod map_analyzer;
+pub mod presets;
 
 pub use errors::Error;
@@ -0,0 +1,212 @@
+//! Named level types with tuned configs (saved from map generators testing menu),
+//! dungeon layout refers to them by `LevelType::Preset`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt, ResultExt};
+
+use super::errors::{PresetCycle, PresetUnknownReference, PresetsFileIo, PresetsFileSerde, Result};
+use super::level::LevelType;
+
+pub const PRESETS_PATH: &str = "./resources/map_gen_presets.json";
+
+lazy_static! {
+    static ref PRESETS: Mutex<BTreeMap<String, LevelType>> = Mutex::new(BTreeMap::new());
+}
+
+/// Format of presets file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PresetsFile {
+    presets: BTreeMap<String, LevelType>,
+}
+
+fn read_presets_file(path: &str) -> Result<PresetsFile> {
+    let contents = fs::read_to_string(path).context(PresetsFileIo { path })?;
+    serde_json::from_str(&contents).context(PresetsFileSerde { path })
+}
+
+/// Loads presets from `path` and replaces currently used ones.
+pub fn load_presets(path: &str) -> Result<()> {
+    let presets = read_presets_file(path)?.presets;
+    validate_presets(&presets)?;
+    *PRESETS.lock().unwrap() = presets;
+    Ok(())
+}
+
+/// Presets can use other presets, these have to exist and can't lead back to the first one.
+fn validate_presets(presets: &BTreeMap<String, LevelType>) -> Result<()> {
+    for name in presets.keys() {
+        let mut visited = vec![name.clone()];
+        let mut to_check = vec![name.clone()];
+        while let Some(preset) = to_check.pop() {
+            for used in presets[&preset].used_presets() {
+                ensure!(used != *name, PresetCycle { name });
+                presets.get(&used).context(PresetUnknownReference {
+                    name: &used,
+                    preset: &preset,
+                })?;
+                if !visited.contains(&used) {
+                    visited.push(used.clone());
+                    to_check.push(used);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn preset(name: &str) -> Option<LevelType> {
+    PRESETS.lock().unwrap().get(name).cloned()
+}
+
+pub fn preset_names() -> Vec<String> {
+    PRESETS.lock().unwrap().keys().cloned().collect()
+}
+
+/// Adds level type to presets file under new name (e.g. `cave_2`), which is returned.
+/// Presets in file are loaded, so the new one can be used right away.
+pub fn save_preset(path: &str, level_type: &LevelType) -> Result<String> {
+    let mut file = read_presets_file(path)?;
+
+    let base_name = level_type.name().to_lowercase();
+    let name = (1..)
+        .map(|i| format!("{}_{}", base_name, i))
+        .find(|name| !file.presets.contains_key(name))
+        .unwrap();
+    file.presets.insert(name.clone(), level_type.clone());
+    validate_presets(&file.presets)?;
+
+    let contents = serde_json::to_string_pretty(&file).context(PresetsFileSerde { path })?;
+    fs::write(path, contents).context(PresetsFileIo { path })?;
+    *PRESETS.lock().unwrap() = file.presets;
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::Error;
+    use crate::maps::generators::cellular_automata::CAMapGenConfig;
+
+    /// Copy of presets file, so saving doesn't change the real one.
+    fn temp_presets_path(name: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "roguelike_presets_{}_{}.json",
+                name,
+                std::process::id()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        fs::copy(PRESETS_PATH, &path).unwrap();
+        path
+    }
+
+    fn presets(list: &[(&str, LevelType)]) -> BTreeMap<String, LevelType> {
+        list.iter()
+            .map(|(name, level_type)| (name.to_string(), level_type.clone()))
+            .collect()
+    }
+
+    fn wfc_of(preset: &str) -> LevelType {
+        LevelType::Wfc {
+            sample: Box::new(LevelType::Preset(preset.to_string())),
+            config: Default::default(),
+        }
+    }
+
+    #[test]
+    fn presets_file_is_valid() {
+        validate_presets(&read_presets_file(PRESETS_PATH).unwrap().presets).unwrap();
+    }
+
+    #[test]
+    fn used_presets_are_found_in_nested_level_types() {
+        let level_type = LevelType::Pipeline {
+            initial: Box::new(wfc_of("cave_1")),
+            modifiers: vec![],
+        };
+
+        assert_eq!(level_type.used_presets(), vec!["cave_1".to_string()]);
+        assert!(LevelType::Cave(CAMapGenConfig::default())
+            .used_presets()
+            .is_empty());
+    }
+
+    #[test]
+    fn unknown_preset_reference_is_reported() {
+        let presets = presets(&[
+            ("cave_1", LevelType::Cave(CAMapGenConfig::default())),
+            ("wfc_1", wfc_of("cave_2")),
+        ]);
+
+        assert!(matches!(
+            validate_presets(&presets),
+            Err(Error::PresetUnknownReference { name, preset })
+                if name == "cave_2" && preset == "wfc_1"
+        ));
+    }
+
+    #[test]
+    fn preset_cycle_is_reported() {
+        let self_reference = presets(&[("wfc_1", wfc_of("wfc_1"))]);
+        let cycle = presets(&[
+            ("cave_1", LevelType::Cave(CAMapGenConfig::default())),
+            ("wfc_1", wfc_of("wfc_2")),
+            ("wfc_2", wfc_of("wfc_1")),
+        ]);
+        let chain = presets(&[
+            ("cave_1", LevelType::Cave(CAMapGenConfig::default())),
+            ("wfc_1", wfc_of("wfc_2")),
+            ("wfc_2", wfc_of("cave_1")),
+        ]);
+
+        assert!(matches!(
+            validate_presets(&self_reference),
+            Err(Error::PresetCycle { name }) if name == "wfc_1"
+        ));
+        assert!(matches!(
+            validate_presets(&cycle),
+            Err(Error::PresetCycle { .. })
+        ));
+        validate_presets(&chain).unwrap();
+    }
+
+    #[test]
+    fn saved_preset_gets_new_name() {
+        let path = temp_presets_path("save");
+        let level_type = LevelType::Cave(CAMapGenConfig {
+            step_limit: 3,
+            ..CAMapGenConfig::default()
+        });
+
+        let first = save_preset(&path, &level_type).unwrap();
+        let second = save_preset(&path, &level_type).unwrap();
+        let saved = read_presets_file(&path).unwrap().presets;
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((first.as_str(), second.as_str()), ("cave_1", "cave_2"));
+        assert_eq!(
+            serde_json::to_value(&saved[&first]).unwrap(),
+            serde_json::to_value(&level_type).unwrap()
+        );
+        assert!(saved.contains_key("open_cave"));
+    }
+
+    #[test]
+    fn preset_using_itself_is_not_saved() {
+        let path = temp_presets_path("cycle");
+
+        // new preset is saved as "wfc_1"
+        let result = save_preset(&path, &wfc_of("wfc_1"));
+        let saved = read_presets_file(&path).unwrap().presets;
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::PresetCycle { .. })));
+        assert!(!saved.contains_key("wfc_1"));
+    }
+}
@@ -10,14 +10,16 @@ pub mod maps;
 pub mod rng;
 pub mod spawner;
 
+#[cfg(test)]
+mod test_support;
+
 use ecs::{components, game_state::RunState, State};
+use snafu::ResultExt;
 // use kira::{
 //     instance::InstanceSettings,
 //     manager::{AudioManager, AudioManagerSettings},
 //     sound::SoundSettings,
 // };
-use levels::level::LevelType;
-
 use crate::{
     ecs::game_state::GameLog,
     graphics::{window::create_sprite_window, GuiDrawer},
@@ -32,6 +34,84 @@ rltk::embedded_resource!(SPRITE_SHEET, "../resources/sprite_sheet_16x16.png");
 rltk::embedded_resource!(CHAR_SHEET, "../resources/terminal_16x16.png");
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+            .map(|value| value.as_str())
+    };
+
+    // `--headless <turns> [--seed <seed>]` runs random bot without window
+    if args.iter().any(|arg| arg == "--headless") {
+        let turns = arg_value("--headless")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1000);
+        let seed = arg_value("--seed")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(rng::random_seed);
+        run_headless_bot(turns, seed);
+        return;
+    }
+
+    // `--analyze-maps <runs> [--generators <a,b>] [--sizes <80x52,40x30>] [--seed <seed>] [--json]`
+    // prints statistics of map generators
+    if args.iter().any(|arg| arg == "--analyze-maps") {
+        let runs = arg_value("--analyze-maps")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+        let result = analyze_maps_config(runs, &arg_value).and_then(|config| {
+            let reports = levels::map_analyzer::analyze(&config)?;
+            Ok(if args.iter().any(|arg| arg == "--json") {
+                levels::map_analyzer::reports_to_json(&reports)
+            } else {
+                levels::map_analyzer::reports_to_table(&reports)
+            })
+        });
+        match result {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                println!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // `--export-map <depth> [--seed <seed>] [--out <path>]` prints level (with entities) as ascii
+    if args.iter().any(|arg| arg == "--export-map") {
+        let depth = arg_value("--export-map")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let seed = arg_value("--seed")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(rng::random_seed);
+        let result = export_map(depth, seed, arg_value("--out"));
+        if let Err(e) = result {
+            println!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `--spawn-report <depth> [--seed <seed>]` prints what was spawned on levels up to depth
+    if args.iter().any(|arg| arg == "--spawn-report") {
+        let depth = arg_value("--spawn-report")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let seed = arg_value("--seed")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(rng::random_seed);
+        match spawn_report(depth, seed) {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                println!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     rltk::link_resource!(SPRITE_SHEET, "resources/sprite_sheet_16x16.png");
     rltk::link_resource!(CHAR_SHEET, "resources/terminal_16x16.png");
 
@@ -50,6 +130,11 @@ fn main() {
     );
     gs.register_all_components();
 
+    if let Err(e) = gs.load_game_data() {
+        println!("ERROR: {}", e);
+        std::process::exit(1);
+    }
+
     gs.ecs.insert(RunState::MainMenu);
     gs.ecs.insert(GameLog {
         entries: vec![
@@ -58,7 +143,8 @@ fn main() {
         ],
     });
 
-    let test = gs.create_new_level(LevelType::BasicDungeon, WINDOW_WIDTH, map_height, 0, None);
+    gs.seed = rng::random_seed();
+    let test = gs.get_or_create_level(levels::dungeon_layout::LevelKey::main(0));
 
     match test {
         Ok(_) => (),
@@ -71,7 +157,9 @@ fn main() {
 
     let mut p_x = 0;
     let mut p_y = 0;
-    while gs.current_map().tile_at_xy(p_x, p_y).blocks_movement() {
+    while gs.current_map().tile_at_xy(p_x, p_y).blocks_movement()
+        || gs.current_map().tile_at_xy(p_x, p_y).is_hazard()
+    {
         p_x = rng::range(2, gs.current_map().width_max() as i32 - 2) as usize;
         p_y = rng::range(2, gs.current_map().height_max() as i32 - 2) as usize;
     }
@@ -81,6 +169,15 @@ fn main() {
 
     gs.ecs.insert(player);
 
+    // `--replay <path>` plays recorded run (every new run is recorded into `REPLAY_FILE_PATH`)
+    if args.iter().any(|arg| arg == "--replay") {
+        let path = arg_value("--replay").unwrap_or(ecs::replay::REPLAY_FILE_PATH);
+        if let Err(e) = gs.start_replay(path) {
+            println!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+    }
+
     let result = rltk::main_loop(context, gs);
     match result {
         Ok(_) => (),
@@ -90,3 +187,149 @@ fn main() {
         }
     }
 }
+
+/// Plays up to `turns` random turns, prints how far the bot got.
+fn run_headless_bot(turns: usize, seed: u64) {
+    use ecs::headless::{HeadlessGame, PlayerAction, StepResult};
+    use ecs::systems::player::Dir;
+    use maps::TileType;
+
+    const DIRS: [Dir; 8] = [
+        Dir::Up,
+        Dir::Down,
+        Dir::Left,
+        Dir::Right,
+        Dir::UpLeft,
+        Dir::UpRight,
+        Dir::DownLeft,
+        Dir::DownRight,
+    ];
+
+    let mut game = match HeadlessGame::new(WINDOW_WIDTH, WINDOW_HEIGHT - CONSOLE_BOX_HEIGHT, seed) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // separate rng, so bot choices don't change game rolls
+    let mut bot_rng = rltk::RandomNumberGenerator::seeded(seed);
+
+    let mut result = StepResult::TurnPassed;
+    let mut turn = 0;
+    while turn < turns {
+        let pos = game.player_pos();
+        let on_stairs = game
+            .state
+            .current_map()
+            .tile_at_xy(pos.x as usize, pos.y as usize)
+            == TileType::StairsDown;
+        let action = if on_stairs {
+            PlayerAction::GoDown
+        } else {
+            PlayerAction::Move(DIRS[bot_rng.range(0, DIRS.len())])
+        };
+
+        result = match game.step(action) {
+            Ok(result) => result,
+            Err(e) => {
+                println!("ERROR: {}", e);
+                std::process::exit(1);
+            }
+        };
+        match result {
+            StepResult::NoTurn => {}
+            StepResult::TurnPassed | StepResult::Slept => turn += 1,
+            StepResult::GameOver | StepResult::Won => break,
+        }
+    }
+
+    let hp = game.player_hp().map(|hp| hp.hp).unwrap_or(0);
+    println!(
+        "seed: {}, turns: {}, depth: {}, hp: {}, result: {:?}",
+        seed,
+        turn,
+        game.depth(),
+        hp,
+        result
+    );
+}
+
+fn analyze_maps_config<'a>(
+    runs: usize,
+    arg_value: &impl Fn(&str) -> Option<&'a str>,
+) -> levels::errors::Result<levels::map_analyzer::AnalyzerConfig> {
+    use levels::map_analyzer::{parse_map_size, AnalyzerConfig, GENERATOR_NAMES};
+
+    levels::presets::load_presets(levels::presets::PRESETS_PATH)?;
+
+    let generators = match arg_value("--generators") {
+        Some(names) => names.split(',').map(|name| name.to_string()).collect(),
+        None => GENERATOR_NAMES
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    };
+    let sizes = match arg_value("--sizes") {
+        Some(sizes) => sizes
+            .split(',')
+            .map(parse_map_size)
+            .collect::<levels::errors::Result<_>>()?,
+        None => vec![(WINDOW_WIDTH, WINDOW_HEIGHT - CONSOLE_BOX_HEIGHT)],
+    };
+    let first_seed = arg_value("--seed")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+
+    Ok(AnalyzerConfig {
+        generators,
+        sizes,
+        runs,
+        first_seed,
+    })
+}
+
+/// Generates levels of new run up to `depth` and exports the last one.
+fn export_map(depth: usize, seed: u64, out_path: Option<&str>) -> ecs::errors::Result<()> {
+    let mut game =
+        ecs::headless::HeadlessGame::new(WINDOW_WIDTH, WINDOW_HEIGHT - CONSOLE_BOX_HEIGHT, seed)?;
+    for level_index in 1..=depth {
+        game.state
+            .player_move_level(levels::dungeon_layout::LevelKey::main(level_index))?;
+    }
+
+    let text = game.state.level_to_ascii(game.state.current_level, true)?;
+    match out_path {
+        Some(path) => std::fs::write(path, text).context(ecs::errors::AsciiMapExportIo { path })?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Generates levels of new run up to `depth` and describes spawns of every created level.
+fn spawn_report(depth: usize, seed: u64) -> ecs::errors::Result<String> {
+    let mut game =
+        ecs::headless::HeadlessGame::new(WINDOW_WIDTH, WINDOW_HEIGHT - CONSOLE_BOX_HEIGHT, seed)?;
+    for level_index in 1..=depth {
+        game.state
+            .player_move_level(levels::dungeon_layout::LevelKey::main(level_index))?;
+    }
+
+    let state = &game.state;
+    let reports = state
+        .level_manager
+        .levels
+        .iter()
+        .map(|level| {
+            format!(
+                "{} {} (weight {}, table {}):\n{}",
+                state.dungeon_layout.branch_name(level.branch),
+                level.depth,
+                level.level_weight,
+                level.spawn_table,
+                level.spawn_report.to_text()
+            )
+        })
+        .collect::<Vec<_>>();
+    Ok(reports.join("\n"))
+}
@@ -0,0 +1,385 @@
+//! Plain text maps. File is a grid of tiles, optionally followed by empty line
+//! and legend of entities placed on map:
+//!
+//! ```text
+//! #######
+//! #..S..#
+//! #.<.>.#
+//! #######
+//!
+//! S = Mighty slime
+//! ```
+//!
+//! Entities stand on floor. Only one entity per tile is exported.
+//! Digits are floor tiles of spawn areas, tiles with the same digit make one area.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use snafu::{ensure, OptionExt, ResultExt};
+
+use super::errors::{
+    AsciiMapEmpty, AsciiMapFileIo, AsciiMapLegend, AsciiMapLegendFull, AsciiMapRowLength,
+    AsciiMapUnknownChar, Result,
+};
+use super::{DoorState, Map, TileType};
+
+pub const PREFABS_DIR: &str = "./resources/prefabs";
+
+pub fn prefab_path(name: &str) -> String {
+    format!("{}/{}.txt", PREFABS_DIR, name)
+}
+
+/// Entity placed on map, `name` is resolved by `spawner::spawn_entity`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapEntity {
+    pub name: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsciiMap {
+    pub map: Map,
+    pub entities: Vec<MapEntity>,
+    /// marked by digits, ordered by them
+    pub spawn_areas: Vec<Vec<(usize, usize)>>,
+}
+
+/// Spawn areas which can be marked in ascii map, one for each digit.
+pub const MAX_SPAWN_AREAS: usize = 10;
+
+pub fn tile_to_char(tile: TileType) -> char {
+    match tile {
+        TileType::Floor => '.',
+        TileType::Wall => '#',
+        TileType::Rock => '%',
+        TileType::TestWall => '&',
+        TileType::StairsDown => '>',
+        TileType::StairsUp => '<',
+        TileType::Door(DoorState::Closed) => '+',
+        TileType::Door(DoorState::Open) => '\'',
+        TileType::Door(DoorState::Locked) => '=',
+        TileType::ShallowWater => '~',
+        TileType::DeepWater => '_',
+        TileType::Lava => '^',
+        TileType::Chasm => ':',
+        TileType::Rubble => ';',
+        TileType::Grass => '"',
+        TileType::Tree => '*',
+        TileType::Bridge => '|',
+        TileType::Trapdoor => '!',
+    }
+}
+
+pub fn char_to_tile(ch: char) -> Option<TileType> {
+    match ch {
+        '.' => Some(TileType::Floor),
+        '#' => Some(TileType::Wall),
+        '%' => Some(TileType::Rock),
+        '&' => Some(TileType::TestWall),
+        '>' => Some(TileType::StairsDown),
+        '<' => Some(TileType::StairsUp),
+        '+' => Some(TileType::Door(DoorState::Closed)),
+        '\'' => Some(TileType::Door(DoorState::Open)),
+        '=' => Some(TileType::Door(DoorState::Locked)),
+        '~' => Some(TileType::ShallowWater),
+        '_' => Some(TileType::DeepWater),
+        '^' => Some(TileType::Lava),
+        ':' => Some(TileType::Chasm),
+        ';' => Some(TileType::Rubble),
+        '"' => Some(TileType::Grass),
+        '*' => Some(TileType::Tree),
+        '|' => Some(TileType::Bridge),
+        '!' => Some(TileType::Trapdoor),
+        _ => None,
+    }
+}
+
+pub fn map_to_ascii(map: &Map, entities: &[MapEntity]) -> Result<String> {
+    map_with_spawn_areas_to_ascii(map, entities, &[])
+}
+
+/// Spawn areas after `MAX_SPAWN_AREAS` and their tiles with entities aren't exported.
+pub fn map_with_spawn_areas_to_ascii(
+    map: &Map,
+    entities: &[MapEntity],
+    spawn_areas: &[Vec<(usize, usize)>],
+) -> Result<String> {
+    let legend = entities_legend(entities)?;
+    let mut grid: Vec<Vec<char>> = (0..map.height)
+        .map(|y| {
+            (0..map.width)
+                .map(|x| tile_to_char(map.tiles[map.xy_to_index(x, y)]))
+                .collect()
+        })
+        .collect();
+
+    for (digit, area) in spawn_areas.iter().take(MAX_SPAWN_AREAS).enumerate() {
+        for &(x, y) in area.iter() {
+            let in_map = x < map.width && y < map.height;
+            if in_map && map.tiles[map.xy_to_index(x, y)] == TileType::Floor {
+                grid[y][x] = std::char::from_digit(digit as u32, 10).unwrap();
+            }
+        }
+    }
+
+    let mut used_tiles = vec![];
+    for entity in entities.iter() {
+        let in_map = entity.x < map.width && entity.y < map.height;
+        if in_map && !used_tiles.contains(&(entity.x, entity.y)) {
+            if let Some(ch) = legend.get(&entity.name) {
+                grid[entity.y][entity.x] = *ch;
+                used_tiles.push((entity.x, entity.y));
+            }
+        }
+    }
+
+    let mut text = grid
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n");
+    text.push('\n');
+
+    if !legend.is_empty() {
+        let mut legend = legend.into_iter().collect::<Vec<_>>();
+        legend.sort_by_key(|(_name, ch)| *ch);
+        text.push('\n');
+        for (name, ch) in legend {
+            text.push_str(&format!("{} = {}\n", ch, name));
+        }
+    }
+    Ok(text)
+}
+
+/// Letter for every entity name, first letter of name is used if it's free.
+/// Map with more different entities than letters can't be exported.
+fn entities_legend(entities: &[MapEntity]) -> Result<BTreeMap<String, char>> {
+    let mut legend = BTreeMap::new();
+    let mut used_chars = vec![];
+    for entity in entities.iter() {
+        if legend.contains_key(&entity.name) {
+            continue;
+        }
+        let first_letter = entity.name.chars().next().filter(|ch| ch.is_alphabetic());
+        let free_char = first_letter
+            .into_iter()
+            .flat_map(|ch| vec![ch.to_ascii_lowercase(), ch.to_ascii_uppercase()])
+            .chain('a'..='z')
+            .chain('A'..='Z')
+            .find(|ch| !used_chars.contains(ch))
+            .context(AsciiMapLegendFull { name: &entity.name })?;
+        used_chars.push(free_char);
+        legend.insert(entity.name.clone(), free_char);
+    }
+    Ok(legend)
+}
+
+pub fn map_from_ascii(text: &str) -> Result<AsciiMap> {
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    let rows: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+    ensure!(!rows.is_empty(), AsciiMapEmpty);
+
+    let mut legend = BTreeMap::new();
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let (ch, name) = parse_legend_line(line).context(AsciiMapLegend { line })?;
+        legend.insert(ch, name);
+    }
+
+    let width = rows[0].chars().count();
+    let height = rows.len();
+    let mut map = Map::new(width, height);
+    let mut entities = vec![];
+    let mut spawn_areas = BTreeMap::new();
+    for (y, row) in rows.iter().enumerate() {
+        let row_width = row.chars().count();
+        ensure!(
+            row_width == width,
+            AsciiMapRowLength {
+                row: y,
+                expected: width,
+                found: row_width,
+            }
+        );
+        for (x, ch) in row.chars().enumerate() {
+            let index = map.xy_to_index(x, y);
+            map.tiles[index] = match (char_to_tile(ch), legend.get(&ch)) {
+                (Some(tile), _) => tile,
+                (None, _) if ch.is_ascii_digit() => {
+                    spawn_areas.entry(ch).or_insert_with(Vec::new).push((x, y));
+                    TileType::Floor
+                }
+                (None, Some(name)) => {
+                    entities.push(MapEntity {
+                        name: name.clone(),
+                        x,
+                        y,
+                    });
+                    TileType::Floor
+                }
+                (None, None) => return AsciiMapUnknownChar { ch, x, y }.fail(),
+            };
+        }
+    }
+    map.update_blocked_with_blocking_tiles();
+
+    Ok(AsciiMap {
+        map,
+        entities,
+        spawn_areas: spawn_areas.into_values().collect(),
+    })
+}
+
+/// `c = Entity name`, tile chars and digits can't be used.
+fn parse_legend_line(line: &str) -> Option<(char, String)> {
+    let mut parts = line.splitn(2, '=');
+    let ch = parts.next()?.trim();
+    let name = parts.next()?.trim();
+    let mut chars = ch.chars();
+    let ch = chars.next()?;
+    if chars.next().is_some()
+        || char_to_tile(ch).is_some()
+        || ch.is_ascii_digit()
+        || name.is_empty()
+    {
+        return None;
+    }
+    Some((ch, name.to_string()))
+}
+
+pub fn load_ascii_map(path: &str) -> Result<AsciiMap> {
+    let text = fs::read_to_string(path).context(AsciiMapFileIo { path })?;
+    map_from_ascii(&text)
+}
+
+pub fn save_ascii_map(path: &str, ascii_map: &AsciiMap) -> Result<()> {
+    let text =
+        map_with_spawn_areas_to_ascii(&ascii_map.map, &ascii_map.entities, &ascii_map.spawn_areas)?;
+    fs::write(path, text).context(AsciiMapFileIo { path })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::Error;
+
+    const MAP: &str = "##########\n\
+                       #.<.+'=.0#\n\
+                       #~_^:;\"*0#\n\
+                       #|!%&.g1>#\n\
+                       ##########\n\
+                       \n\
+                       g = Goblin\n";
+
+    #[test]
+    fn exported_map_is_the_same_after_import() {
+        let ascii_map = map_from_ascii(MAP).unwrap();
+        assert_eq!(
+            ascii_map.entities,
+            vec![MapEntity {
+                name: "Goblin".to_string(),
+                x: 6,
+                y: 3,
+            }]
+        );
+        assert_eq!(
+            ascii_map.spawn_areas,
+            vec![vec![(8, 1), (8, 2)], vec![(7, 3)]]
+        );
+
+        let text = map_with_spawn_areas_to_ascii(
+            &ascii_map.map,
+            &ascii_map.entities,
+            &ascii_map.spawn_areas,
+        )
+        .unwrap();
+        assert_eq!(text, MAP);
+
+        let text = map_to_ascii(&ascii_map.map, &ascii_map.entities).unwrap();
+        let reloaded = map_from_ascii(&text).unwrap();
+        assert_eq!(reloaded.map.tiles, ascii_map.map.tiles);
+        assert_eq!(reloaded.map.blocked, ascii_map.map.blocked);
+        assert_eq!(reloaded.entities, ascii_map.entities);
+    }
+
+    #[test]
+    fn prefabs_survive_round_trip() {
+        let prefab = load_ascii_map(&prefab_path("boss_arena")).unwrap();
+        let text =
+            map_with_spawn_areas_to_ascii(&prefab.map, &prefab.entities, &prefab.spawn_areas)
+                .unwrap();
+        let reloaded = map_from_ascii(&text).unwrap();
+
+        assert_eq!(reloaded.map.tiles, prefab.map.tiles);
+        assert_eq!(reloaded.spawn_areas, prefab.spawn_areas);
+        let mut entities = prefab.entities.clone();
+        let mut reloaded_entities = reloaded.entities;
+        entities.sort_by_key(|entity| (entity.y, entity.x));
+        reloaded_entities.sort_by_key(|entity| (entity.y, entity.x));
+        assert_eq!(reloaded_entities, entities);
+    }
+
+    #[test]
+    fn map_with_more_entity_names_than_letters_is_not_exported() {
+        let map = Map::new(60, 3);
+        let entities = (0..53)
+            .map(|i| MapEntity {
+                name: format!("Goblin {}", i),
+                x: i,
+                y: 1,
+            })
+            .collect::<Vec<_>>();
+
+        map_to_ascii(&map, &entities[..52]).unwrap();
+        assert!(matches!(
+            map_to_ascii(&map, &entities),
+            Err(Error::AsciiMapLegendFull { name }) if name == "Goblin 52"
+        ));
+    }
+
+    #[test]
+    fn unknown_char_is_reported() {
+        assert!(matches!(
+            map_from_ascii("###\n#?#\n###\n"),
+            Err(Error::AsciiMapUnknownChar {
+                ch: '?',
+                x: 1,
+                y: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn incorrect_legend_is_reported() {
+        for legend in [
+            "g Goblin",
+            "# = Goblin",
+            "1 = Goblin",
+            "gg = Goblin",
+            "g = ",
+        ]
+        .iter()
+        {
+            let text = format!("###\n#g#\n###\n\n{}\n", legend);
+            assert!(
+                matches!(map_from_ascii(&text), Err(Error::AsciiMapLegend { .. })),
+                "{}",
+                legend
+            );
+        }
+    }
+
+    #[test]
+    fn rows_of_different_length_are_reported() {
+        assert!(matches!(
+            map_from_ascii("###\n##\n###\n"),
+            Err(Error::AsciiMapRowLength {
+                row: 1,
+                expected: 3,
+                found: 2,
+            })
+        ));
+        assert!(matches!(map_from_ascii("\n"), Err(Error::AsciiMapEmpty)));
+    }
+}
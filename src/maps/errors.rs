@@ -17,4 +17,98 @@ pub enum Error {
 
     #[snafu(display("Area of BSP node is to small to split into two areas.",))]
     TooSmallBSPAreaToSplit,
+
+    #[snafu(display("Too many tries to place rooms in map.",))]
+    TooManyRoomPlacementRetries,
+
+    #[snafu(display("Map generator config can't be used: {}", reason))]
+    InvalidGeneratorConfig { reason: String },
+
+    #[snafu(display("Map has no walkable tiles"))]
+    NoWalkableTiles,
+
+    #[snafu(display("Map is split into {} regions not reachable from each other", regions))]
+    MapNotConnected { regions: usize },
+
+    #[snafu(display("Map has no stairs down"))]
+    NoStairsDown,
+
+    #[snafu(display("Map has no stairs up at ({}, {})", x, y))]
+    NoStairsUp { x: i32, y: i32 },
+
+    #[snafu(display("Stairs up position ({}, {}) is outside of map", x, y))]
+    StairsUpOutsideMap { x: i32, y: i32 },
+
+    #[snafu(display("Spawn area contains not walkable position ({}, {})", x, y))]
+    SpawnAreaNotWalkable { x: usize, y: usize },
+
+    #[snafu(display("Unable to generate valid map in {} tries: {}", tries, source))]
+    InvalidGeneratedMap { tries: usize, source: Box<Error> },
+
+    #[snafu(display("Unable to access ascii map file {}: {}", path, source))]
+    AsciiMapFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Ascii map is empty"))]
+    AsciiMapEmpty,
+
+    #[snafu(display("Row {} of ascii map has length {}, expected {}", row, found, expected))]
+    AsciiMapRowLength {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[snafu(display("Unknown char '{}' at ({}, {}) of ascii map", ch, x, y))]
+    AsciiMapUnknownChar { ch: char, x: usize, y: usize },
+
+    #[snafu(display("Incorrect legend line \"{}\" of ascii map", line))]
+    AsciiMapLegend { line: String },
+
+    #[snafu(display("No free legend letter left for entity \"{}\" of ascii map", name))]
+    AsciiMapLegendFull { name: String },
+
+    #[snafu(display("Unknown map generator preset \"{}\"", name))]
+    UnknownGeneratorPreset { name: String },
+
+    #[snafu(display("Map generator preset {} refers to itself", name))]
+    GeneratorPresetCycle { name: String },
+
+    #[snafu(display("Unable to read vaults file {}: {}", path, source))]
+    VaultsFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to parse vaults file {}: {}", path, source))]
+    VaultsFileSerde {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[snafu(display("Incorrect vault \"{}\": {}", name, source))]
+    InvalidVault { name: String, source: Box<Error> },
+
+    #[snafu(display("Vault \"{}\" has no connections", name))]
+    VaultWithoutConnections { name: String },
+
+    #[snafu(display("Connection ({}, {}) of vault \"{}\" is not on its edge", x, y, name))]
+    VaultConnectionNotOnEdge { name: String, x: usize, y: usize },
+
+    #[snafu(display(
+        "Sample map {}x{} is too small for wave function collapse with chunk size {}",
+        width,
+        height,
+        chunk_size
+    ))]
+    WfcSampleTooSmall {
+        width: usize,
+        height: usize,
+        chunk_size: usize,
+    },
+
+    #[snafu(display("Wave function collapse contradiction in all {} tries", tries))]
+    WfcContradiction { tries: usize },
 }
@@ -1,25 +1,34 @@
 use itertools::Itertools;
 use rltk::Point;
+use serde::{Deserialize, Serialize};
+use snafu::ensure;
 
 use crate::{
     maps::{
-        errors::Result,
+        errors::{Error, InvalidGeneratorConfig, Result},
         rect::{apply_room_to_map, Rect},
+        vaults::{self, PlacedVault, Vault},
         Map, TileType,
     },
     rng,
 };
 
 use super::{
-    common::{apply_horizontal_tunnel, apply_vertical_tunnel},
+    common::{apply_horizontal_tunnel, apply_vertical_tunnel, place_doors},
+    stairs::{self, StairsPlacement},
     MapGenerator,
 };
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BasicDungeonMapConfig {
     pub rooms_min: usize,
     pub rooms_max: usize,
     pub room_size_min: usize,
     pub room_size_max: usize,
+    /// chance of closed door where corridor enters room
+    pub doors_perc: usize,
+    pub stairs: StairsPlacement,
 }
 
 impl Default for BasicDungeonMapConfig {
@@ -29,6 +38,8 @@ impl Default for BasicDungeonMapConfig {
             rooms_max: 9,
             room_size_min: 8,
             room_size_max: 15,
+            doors_perc: 60,
+            stairs: StairsPlacement::RoomCenter,
         }
     }
 }
@@ -39,6 +50,8 @@ pub struct BasicDungeonMap {
     config: BasicDungeonMapConfig,
     map: Map,
     rooms: Vec<Rect>,
+    vaults: Vec<Vault>,
+    placed_vaults: Vec<PlacedVault>,
 
     #[cfg(feature = "map_gen_testing")]
     history: Vec<(Map, String)>,
@@ -52,55 +65,79 @@ impl BasicDungeonMap {
             config,
             map: Map::new(width, height).with_all_solid(),
             rooms: vec![],
+            vaults: vec![],
+            placed_vaults: vec![],
 
             #[cfg(feature = "map_gen_testing")]
             history: vec![],
         }
     }
 
-    pub fn create_basic_dungeon_map(&mut self, prev_down_stairs_pos: Option<Point>) {
+    /// Vaults are placed after rooms and corridors, if there is free space.
+    pub fn with_vaults(mut self, vaults: Vec<Vault>) -> BasicDungeonMap {
+        self.vaults = vaults;
+        self
+    }
+
+    pub fn create_basic_dungeon_map(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
         #[cfg(feature = "map_gen_testing")]
         self.history.push((self.map.clone(), "Start".to_string()));
 
-        self.add_rooms();
+        self.add_rooms()?;
 
-        if let Some(prev_stairs) = prev_down_stairs_pos {
+        if let Some(prev_stairs) =
+            prev_down_stairs_pos.filter(|pos| stairs::is_inside_map(&self.map, *pos))
+        {
             let index = self
                 .map
                 .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-            while self.map.tiles[index] != TileType::Floor {
+            let mut tries = 0;
+            while self.map.tiles[index] != TileType::Floor && tries < stairs::MAX_STAIRS_ROOM_TRIES
+            {
                 self.map = Map::new(self.width, self.height).with_all_solid();
-                self.add_rooms();
+                self.add_rooms()?;
+                tries += 1;
             }
         }
 
         self.add_corridors();
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
-    }
+        stairs::place_stairs_with_rooms(
+            &mut self.map,
+            prev_down_stairs_pos,
+            self.config.stairs,
+            &self.rooms,
+        );
 
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
+        place_doors(&mut self.map, &self.rooms, self.config.doors_perc);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing doors".to_string()));
 
-        let mut index = 0;
-        while self.map.tiles[index].blocks_movement() || self.map.tiles[index] == TileType::StairsUp
-        {
-            let random_room = rng::range(0, self.rooms.len() as i32 - 1) as usize;
-            let center = self.rooms[random_room].center();
-            index = self.map.xy_to_index(center.0, center.1);
-        }
-        self.map.tiles[index] = TileType::StairsDown;
+        self.placed_vaults =
+            vaults::place_vaults(&mut self.map, &self.vaults, prev_down_stairs_pos);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing vaults".to_string()));
+        Ok(())
     }
 
-    fn add_rooms(&mut self) {
+    fn add_rooms(&mut self) -> Result<()> {
+        let config = &self.config;
+        ensure!(
+            config.rooms_min <= config.rooms_max && config.room_size_min <= config.room_size_max,
+            InvalidGeneratorConfig {
+                reason: "minimum of rooms count or size is bigger than maximum"
+            }
+        );
+        ensure!(
+            config.room_size_min > 0 && config.room_size_max + 2 < self.width.min(self.height),
+            InvalidGeneratorConfig {
+                reason: "rooms don't fit into map"
+            }
+        );
+
         let mut rooms = vec![];
+        let mut error_count = 0;
         let rooms_num = rng::range(self.config.rooms_min as i32, self.config.rooms_max as i32);
         while rooms.len() != rooms_num as usize {
             let w = rng::range(
@@ -121,9 +158,16 @@ impl BasicDungeonMap {
                 #[cfg(feature = "map_gen_testing")]
                 self.history
                     .push((self.map.clone(), "Adding rooms".to_string()));
+            } else {
+                error_count += 1;
+                // rooms don't fit into map
+                if error_count > 1000 {
+                    return Err(Error::TooManyRoomPlacementRetries);
+                }
             }
         }
         self.rooms = rooms;
+        Ok(())
     }
 
     fn add_corridors(&mut self) {
@@ -149,14 +193,14 @@ impl BasicDungeonMap {
 
 impl MapGenerator for BasicDungeonMap {
     fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
-        self.create_basic_dungeon_map(prev_down_stairs_pos);
-        Ok(())
+        self.create_basic_dungeon_map(prev_down_stairs_pos)
     }
 
     fn reset(&mut self) {
         #[cfg(feature = "map_gen_testing")]
         self.history.clear();
         self.map = Map::new(self.width, self.height).with_all_solid();
+        self.placed_vaults.clear();
     }
 
     fn map(&self) -> Map {
@@ -167,8 +211,71 @@ impl MapGenerator for BasicDungeonMap {
         self.rooms.iter().map(|r| r.area_within()).collect()
     }
 
+    fn placed_vaults(&self) -> Vec<PlacedVault> {
+        self.placed_vaults.clone()
+    }
+
     #[cfg(feature = "map_gen_testing")]
     fn history(&self) -> Vec<(Map, String)> {
         self.history.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{generators::assert_generates_valid_maps, validation};
+
+    #[test]
+    fn basic_dungeon_is_valid() {
+        let mut generator = BasicDungeonMap::new(80, 50, BasicDungeonMapConfig::default());
+        assert_generates_valid_maps(&mut generator, Some(Point::new(40, 25)), 0..5);
+    }
+
+    #[test]
+    fn down_stairs_are_in_room_center_by_default() {
+        for seed in 0..5 {
+            rng::reseed(seed);
+            let mut generator = BasicDungeonMap::new(80, 50, BasicDungeonMapConfig::default());
+            generator.generate(Some(Point::new(40, 25))).unwrap();
+
+            let down_stairs = generator
+                .map
+                .tiles
+                .iter()
+                .position(|tile| *tile == TileType::StairsDown)
+                .unwrap();
+            let centers = generator
+                .rooms
+                .iter()
+                .map(|room| generator.map.xy_to_index(room.center().0, room.center().1))
+                .collect::<Vec<_>>();
+            assert!(centers.contains(&down_stairs), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn down_stairs_are_far_from_up_stairs() {
+        let config = BasicDungeonMapConfig {
+            stairs: StairsPlacement::Farthest,
+            ..BasicDungeonMapConfig::default()
+        };
+        for seed in 0..5 {
+            rng::reseed(seed);
+            let mut generator = BasicDungeonMap::new(80, 50, config.clone());
+            generator.generate(Some(Point::new(40, 25))).unwrap();
+            let mut map = generator.map();
+            map.update_blocked_with_blocking_tiles();
+
+            let up_stairs = map.xy_to_index(40, 25);
+            let down_stairs = map
+                .tiles
+                .iter()
+                .position(|tile| *tile == TileType::StairsDown)
+                .unwrap();
+            let distances = validation::distances_from(&map, up_stairs);
+            let farthest = distances.iter().filter_map(|distance| *distance).max();
+            assert_eq!(distances[down_stairs], farthest, "seed {}", seed);
+        }
+    }
+}
@@ -1,17 +1,14 @@
 use itertools::Itertools;
 use rltk::Point;
 
-use crate::{
-    maps::{
-        generators::{
-            bsp::tree::NodeOrientation,
-            common::{apply_horizontal_tunnel, apply_vertical_tunnel},
-            MapGenerator,
-        },
-        rect::{apply_room_to_map, Rect},
-        Map, TileType,
+use crate::maps::{
+    generators::{
+        bsp::tree::NodeOrientation,
+        common::{apply_horizontal_tunnel, apply_vertical_tunnel, place_doors},
+        stairs, MapGenerator,
     },
-    rng,
+    rect::{apply_room_to_map, Rect},
+    Map, TileType,
 };
 
 use crate::maps::errors::Result;
@@ -48,6 +45,7 @@ impl BSPInteriorGen {
     }
 
     fn create_map(&mut self) -> Result<()> {
+        self.config.validate(self.width, self.height)?;
         self.map = Map::new(self.width, self.height).with_all_solid();
 
         let mut errors_count = 0;
@@ -107,7 +105,9 @@ impl BSPInteriorGen {
 
         for tree_level in (1..=tree_height).rev() {
             let start_node = ((2_u32).pow(tree_level as u32) - 1) as usize;
-            let max_node = ((2_u32).pow(tree_height as u32 + 1) - 2) as usize;
+            // tree isn't complete, if map is too small to split its nodes
+            let max_node = (((2_u32).pow(tree_height as u32 + 1) - 2) as usize)
+                .min(self.tree.nodes.len().saturating_sub(1));
 
             let i_adder = |counter: usize| {
                 if tree_level != 1 {
@@ -229,29 +229,6 @@ impl BSPInteriorGen {
         }
     }
 
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
-
-        let mut index = 0;
-        while index == 0
-            || self.map.tiles[index].blocks_movement()
-            || self.map.tiles[index] == TileType::StairsUp
-        {
-            let random_room = rng::range(0, self.rooms.len() as i32 - 1) as usize;
-            let center = self.rooms[random_room].center();
-            index = self.map.xy_to_index(center.0, center.1);
-        }
-        self.map.tiles[index] = TileType::StairsDown;
-    }
-
     fn replace_debug_walls_with_walls(&mut self) {
         for t in self.map.tiles.iter_mut() {
             if *t == TileType::TestWall {
@@ -265,18 +242,34 @@ impl MapGenerator for BSPInteriorGen {
     fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
         self.create_map()?;
 
-        if let Some(prev_stairs) = prev_down_stairs_pos {
+        if let Some(prev_stairs) =
+            prev_down_stairs_pos.filter(|pos| stairs::is_inside_map(&self.map, *pos))
+        {
             let index = self
                 .map
                 .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-            while self.map.tiles[index] != TileType::Floor {
+            let mut tries = 0;
+            while self.map.tiles[index] != TileType::Floor && tries < stairs::MAX_STAIRS_ROOM_TRIES
+            {
                 self.reset();
                 self.create_map()?;
+                tries += 1;
             }
         }
 
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
+        // debug walls don't block movement, they would be used by stairs placement
         self.replace_debug_walls_with_walls();
+        stairs::place_stairs_with_rooms(
+            &mut self.map,
+            prev_down_stairs_pos,
+            self.config.stairs,
+            &self.rooms,
+        );
+
+        place_doors(&mut self.map, &self.rooms, self.config.doors_perc);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing doors".to_string()));
         #[cfg(feature = "map_gen_testing")]
         {
             self.history
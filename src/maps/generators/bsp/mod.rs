@@ -3,28 +3,34 @@ mod tree;
 
 use itertools::Itertools;
 use rltk::Point;
+use serde::{Deserialize, Serialize};
+use snafu::ensure;
 
-use crate::{
-    maps::{
-        rect::{apply_room_to_map, Rect},
-        Map, TileType,
-    },
-    rng,
+use crate::maps::{
+    rect::{apply_room_to_map, Rect},
+    vaults::{self, PlacedVault, Vault},
+    Map, TileType,
 };
 
 use self::tree::{BTree, NodeOrientation};
 
 use super::{
-    common::{apply_horizontal_tunnel, apply_vertical_tunnel},
+    common::{apply_horizontal_tunnel, apply_vertical_tunnel, place_doors},
+    stairs::{self, StairsPlacement},
     MapGenerator,
 };
 
-use crate::maps::errors::Result;
+use crate::maps::errors::{InvalidGeneratorConfig, Result};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BSPConfig {
     pub room_size_min: usize,
 
     pub tree_height: usize,
+    /// chance of closed door where corridor enters room
+    pub doors_perc: usize,
+    pub stairs: StairsPlacement,
 }
 
 impl Default for BSPConfig {
@@ -32,10 +38,31 @@ impl Default for BSPConfig {
         BSPConfig {
             room_size_min: 5,
             tree_height: 4,
+            doors_perc: 60,
+            stairs: StairsPlacement::RoomCenter,
         }
     }
 }
 
+impl BSPConfig {
+    /// Room of minimal size has to fit into map, tree can't be split into more nodes than `u32`.
+    fn validate(&self, width: usize, height: usize) -> Result<()> {
+        ensure!(
+            self.room_size_min + 2 < width.min(height),
+            InvalidGeneratorConfig {
+                reason: "rooms don't fit into map"
+            }
+        );
+        ensure!(
+            self.tree_height < 32,
+            InvalidGeneratorConfig {
+                reason: "BSP tree is too high"
+            }
+        );
+        Ok(())
+    }
+}
+
 pub struct BSPDungeonGen {
     width: usize,
     height: usize,
@@ -45,6 +72,8 @@ pub struct BSPDungeonGen {
 
     map: Map,
     rooms: Vec<Rect>,
+    vaults: Vec<Vault>,
+    placed_vaults: Vec<PlacedVault>,
 
     #[cfg(feature = "map_gen_testing")]
     history: Vec<(Map, String)>,
@@ -58,6 +87,8 @@ impl BSPDungeonGen {
             config,
             map: Map::new(width, height).with_all_solid(),
             rooms: vec![],
+            vaults: vec![],
+            placed_vaults: vec![],
 
             #[cfg(feature = "map_gen_testing")]
             history: vec![],
@@ -65,7 +96,14 @@ impl BSPDungeonGen {
         }
     }
 
+    /// Vaults are placed after rooms and corridors, if there is free space.
+    pub fn with_vaults(mut self, vaults: Vec<Vault>) -> BSPDungeonGen {
+        self.vaults = vaults;
+        self
+    }
+
     fn create_map(&mut self) -> Result<()> {
+        self.config.validate(self.width, self.height)?;
         self.map = Map::new(self.width, self.height).with_all_solid();
 
         let mut errors_count = 0;
@@ -124,7 +162,9 @@ impl BSPDungeonGen {
 
         for tree_level in (1..=tree_height).rev() {
             let start_node = ((2_u32).pow(tree_level as u32) - 1) as usize;
-            let max_node = ((2_u32).pow(tree_height as u32 + 1) - 2) as usize;
+            // tree isn't complete, if map is too small to split its nodes
+            let max_node = (((2_u32).pow(tree_height as u32 + 1) - 2) as usize)
+                .min(self.tree.nodes.len().saturating_sub(1));
 
             let i_adder = |counter: usize| {
                 if tree_level != 1 {
@@ -258,29 +298,6 @@ impl BSPDungeonGen {
         }
     }
 
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
-
-        let mut index = 0;
-        while index == 0
-            || self.map.tiles[index].blocks_movement()
-            || self.map.tiles[index] == TileType::StairsUp
-        {
-            let random_room = rng::range(0, self.rooms.len() as i32 - 1) as usize;
-            let center = self.rooms[random_room].center();
-            index = self.map.xy_to_index(center.0, center.1);
-        }
-        self.map.tiles[index] = TileType::StairsDown;
-    }
-
     fn replace_debug_walls_with_walls(&mut self) {
         for t in self.map.tiles.iter_mut() {
             if *t == TileType::TestWall {
@@ -294,17 +311,39 @@ impl MapGenerator for BSPDungeonGen {
     fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
         self.create_map()?;
 
-        if let Some(prev_stairs) = prev_down_stairs_pos {
+        if let Some(prev_stairs) =
+            prev_down_stairs_pos.filter(|pos| stairs::is_inside_map(&self.map, *pos))
+        {
             let index = self
                 .map
                 .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-            while self.map.tiles[index] != TileType::Floor {
+            let mut tries = 0;
+            while self.map.tiles[index] != TileType::Floor && tries < stairs::MAX_STAIRS_ROOM_TRIES
+            {
                 self.reset();
                 self.create_map()?;
+                tries += 1;
             }
         }
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
+        // debug walls don't block movement, they would be used by stairs placement
         self.replace_debug_walls_with_walls();
+        stairs::place_stairs_with_rooms(
+            &mut self.map,
+            prev_down_stairs_pos,
+            self.config.stairs,
+            &self.rooms,
+        );
+
+        place_doors(&mut self.map, &self.rooms, self.config.doors_perc);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing doors".to_string()));
+
+        self.placed_vaults =
+            vaults::place_vaults(&mut self.map, &self.vaults, prev_down_stairs_pos);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing vaults".to_string()));
         #[cfg(feature = "map_gen_testing")]
         {
             self.history
@@ -322,6 +361,7 @@ impl MapGenerator for BSPDungeonGen {
         }
 
         self.rooms.clear();
+        self.placed_vaults.clear();
         self.tree.nodes.clear();
         self.map = Map::new(self.width, self.height).with_all_solid();
     }
@@ -334,8 +374,27 @@ impl MapGenerator for BSPDungeonGen {
         self.rooms.iter().map(|r| r.area_within()).collect()
     }
 
+    fn placed_vaults(&self) -> Vec<PlacedVault> {
+        self.placed_vaults.clone()
+    }
+
     #[cfg(feature = "map_gen_testing")]
     fn history(&self) -> Vec<(Map, String)> {
         self.history.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::generators::{assert_generates_valid_maps, bsp::interior::BSPInteriorGen};
+
+    #[test]
+    fn bsp_maps_are_valid() {
+        let stairs = Some(Point::new(30, 20));
+        let mut dungeon = BSPDungeonGen::new(60, 40, BSPConfig::default());
+        assert_generates_valid_maps(&mut dungeon, stairs, 0..5);
+        let mut interior = BSPInteriorGen::new(60, 40, BSPConfig::default());
+        assert_generates_valid_maps(&mut interior, stairs, 0..5);
+    }
+}
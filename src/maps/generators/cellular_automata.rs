@@ -1,13 +1,22 @@
 use std::ops::Not;
 
 use rltk::Point;
+use serde::{Deserialize, Serialize};
 
 use crate::maps::errors::{Error, Result};
-use crate::maps::{Map, TileType};
+use crate::maps::{
+    vaults::{self, PlacedVault, Vault},
+    Map, TileType,
+};
 use crate::rng;
 
-use super::MapGenerator;
+use super::{
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CAMapGenConfig {
     pub alive_on_start_chance_percent: usize,
     pub step_limit: usize,
@@ -61,6 +70,8 @@ pub struct CAMapGen {
     height: usize,
     config: CAMapGenConfig,
     map: Map,
+    vaults: Vec<Vault>,
+    placed_vaults: Vec<PlacedVault>,
 
     #[cfg(feature = "map_gen_testing")]
     history: Vec<(Map, String)>,
@@ -92,6 +103,8 @@ impl CAMapGen {
             height,
             config: CAMapGenConfig::default(),
             map: Map::new(width, height),
+            vaults: vec![],
+            placed_vaults: vec![],
 
             #[cfg(feature = "map_gen_testing")]
             history: vec![],
@@ -103,6 +116,12 @@ impl CAMapGen {
         self
     }
 
+    /// Vaults are placed into rock around caves, if there is free space.
+    pub fn with_vaults(mut self, vaults: Vec<Vault>) -> CAMapGen {
+        self.vaults = vaults;
+        self
+    }
+
     pub fn make_cave_map(&mut self) -> Result<()> {
         #[cfg(feature = "map_gen_testing")]
         self.history.push((self.map.clone(), "Start".to_string()));
@@ -144,30 +163,6 @@ impl CAMapGen {
         Ok(())
     }
 
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
-
-        let mut random_point = 0;
-
-        while self.map.tiles[random_point].blocks_movement()
-            || self.map.tiles[random_point] == TileType::StairsUp
-        {
-            random_point = rng::range(
-                self.width as i32 + 1,
-                (self.width * (self.height - 1)) as i32,
-            ) as usize;
-        }
-        self.map.tiles[random_point] = TileType::StairsDown;
-    }
-
     fn set_map(&mut self) {
         for (i, place) in self.ca_map.iter().enumerate() {
             let tile_type = if place.alive {
@@ -425,13 +420,20 @@ impl MapGenerator for CAMapGen {
                 self.make_cave_map()?;
             }
         }
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, StairsPlacement::Random);
+
+        self.placed_vaults =
+            vaults::place_vaults(&mut self.map, &self.vaults, prev_down_stairs_pos);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing vaults".to_string()));
         Ok(())
     }
 
     fn reset(&mut self) {
         #[cfg(feature = "map_gen_testing")]
         self.history.clear();
+        self.placed_vaults.clear();
         let mut ca_map = vec![CAPlace::default(); self.width * self.height];
         let mut x = 0;
         let mut y = 0;
@@ -461,6 +463,10 @@ impl MapGenerator for CAMapGen {
         areas
     }
 
+    fn placed_vaults(&self) -> Vec<PlacedVault> {
+        self.placed_vaults.clone()
+    }
+
     #[cfg(feature = "map_gen_testing")]
     fn history(&self) -> Vec<(Map, String)> {
         self.history.clone()
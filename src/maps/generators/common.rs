@@ -1,4 +1,5 @@
-use crate::maps::{Map, TileType};
+use crate::maps::{rect::Rect, DoorState, Map, TileType};
+use crate::rng;
 
 pub fn apply_horizontal_tunnel(map: &mut Map, x1: usize, x2: usize, y: usize) {
     for x in x1.min(x2)..=x1.max(x2) {
@@ -13,3 +14,61 @@ pub fn apply_vertical_tunnel(map: &mut Map, y1: usize, y2: usize, x: usize) {
         map.tiles[tile_index] = TileType::Floor;
     }
 }
+
+/// Closed doors are placed (with `doors_perc` chance) where corridors go through room walls:
+/// on floor tiles of walls, which have solid tiles on both sides along the wall.
+pub fn place_doors(map: &mut Map, rooms: &[Rect], doors_perc: usize) {
+    for room in rooms.iter() {
+        let horizontal_walls =
+            (room.x1 + 1..room.x2).flat_map(|x| vec![(x, room.y1, true), (x, room.y2, true)]);
+        let vertical_walls =
+            (room.y1 + 1..room.y2).flat_map(|y| vec![(room.x1, y, false), (room.x2, y, false)]);
+
+        for (x, y, horizontal) in horizontal_walls.chain(vertical_walls) {
+            let inside_map = x > 0 && y > 0 && x < map.width_max() && y < map.height_max();
+            if !inside_map || map.tile_at_xy(x, y) != TileType::Floor {
+                continue;
+            }
+            let solid = |x: usize, y: usize| map.tile_at_xy(x, y).is_solid();
+            let is_opening = if horizontal {
+                solid(x - 1, y) && solid(x + 1, y) && !solid(x, y - 1) && !solid(x, y + 1)
+            } else {
+                solid(x, y - 1) && solid(x, y + 1) && !solid(x - 1, y) && !solid(x + 1, y)
+            };
+            if is_opening && rng::test_perc(doors_perc) {
+                let index = map.xy_to_index(x, y);
+                map.tiles[index] = TileType::Door(DoorState::Closed);
+            }
+        }
+    }
+}
+
+/// Splits tiles into regions of tiles closest to the same seed (empty regions are skipped).
+fn regions_around_seeds(
+    tiles: &[(usize, usize)],
+    seeds: &[(usize, usize)],
+) -> Vec<Vec<(usize, usize)>> {
+    let mut regions = vec![vec![]; seeds.len()];
+    for &(x, y) in tiles.iter() {
+        let nearest = seeds.iter().enumerate().min_by_key(|(_i, (sx, sy))| {
+            let (dx, dy) = (x as i64 - *sx as i64, y as i64 - *sy as i64);
+            dx * dx + dy * dy
+        });
+        if let Some((i, _seed)) = nearest {
+            regions[i].push((x, y));
+        }
+    }
+    regions.retain(|region| !region.is_empty());
+    regions
+}
+
+/// Splits area into regions of about `region_size` tiles around random tiles of area.
+pub fn split_area(area: &[(usize, usize)], region_size: usize) -> Vec<Vec<(usize, usize)>> {
+    if area.is_empty() {
+        return vec![];
+    }
+    let seeds = (0..(area.len() / region_size).max(1))
+        .map(|_| area[rng::range(0, area.len() as i32 - 1) as usize])
+        .collect::<Vec<_>>();
+    regions_around_seeds(area, &seeds)
+}
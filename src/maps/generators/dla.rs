@@ -0,0 +1,354 @@
+use rltk::{LineAlg, Point};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{
+        errors::{Error, Result},
+        Map, TileType,
+    },
+    rng,
+};
+
+use super::{
+    common,
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DLAAlgorithm {
+    /// digger walks randomly from random point until it hits floor
+    WalkInwards,
+    /// digger walks randomly from start until it leaves floor
+    WalkOutwards,
+    /// digger goes straight from random point to start until it hits floor
+    CentralAttractor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DLASymmetry {
+    None,
+    Horizontal,
+    Vertical,
+    Both,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DLAConfig {
+    pub algorithm: DLAAlgorithm,
+    pub symmetry: DLASymmetry,
+    /// size of square dug by every digger
+    pub brush_size: usize,
+    pub floor_perc: usize,
+}
+
+impl Default for DLAConfig {
+    fn default() -> DLAConfig {
+        DLAConfig::walk_inwards()
+    }
+}
+
+pub const DLA_PRESETS: [&str; 4] = [
+    "walk_inwards",
+    "walk_outwards",
+    "central_attractor",
+    "insectoid",
+];
+
+impl DLAConfig {
+    pub fn walk_inwards() -> DLAConfig {
+        DLAConfig {
+            algorithm: DLAAlgorithm::WalkInwards,
+            symmetry: DLASymmetry::None,
+            brush_size: 1,
+            floor_perc: 30,
+        }
+    }
+
+    pub fn walk_outwards() -> DLAConfig {
+        DLAConfig {
+            algorithm: DLAAlgorithm::WalkOutwards,
+            symmetry: DLASymmetry::None,
+            brush_size: 2,
+            floor_perc: 30,
+        }
+    }
+
+    pub fn central_attractor() -> DLAConfig {
+        DLAConfig {
+            algorithm: DLAAlgorithm::CentralAttractor,
+            symmetry: DLASymmetry::None,
+            brush_size: 2,
+            floor_perc: 30,
+        }
+    }
+
+    pub fn insectoid() -> DLAConfig {
+        DLAConfig {
+            algorithm: DLAAlgorithm::CentralAttractor,
+            symmetry: DLASymmetry::Horizontal,
+            brush_size: 2,
+            floor_perc: 30,
+        }
+    }
+
+    /// Config by name from `DLA_PRESETS`.
+    pub fn preset(name: &str) -> Option<DLAConfig> {
+        match name {
+            "walk_inwards" => Some(DLAConfig::walk_inwards()),
+            "walk_outwards" => Some(DLAConfig::walk_outwards()),
+            "central_attractor" => Some(DLAConfig::central_attractor()),
+            "insectoid" => Some(DLAConfig::insectoid()),
+            _ => None,
+        }
+    }
+
+    pub fn random_preset() -> DLAConfig {
+        let name = DLA_PRESETS[rng::range(0, DLA_PRESETS.len() as i32 - 1) as usize];
+        DLAConfig::preset(name).unwrap()
+    }
+}
+
+/// Higher floor percentage can't be reached with solid map edges.
+const MAX_FLOOR_PERC: usize = 70;
+
+/// Floor tiles per spawn area.
+const SPAWN_AREA_SIZE: usize = 50;
+
+/// How many diggers are sent between history steps.
+#[cfg(feature = "map_gen_testing")]
+const HISTORY_STEP_DIGGERS: usize = 25;
+
+/// Diffusion-limited aggregation, cave grows from start point as diggers stick to it.
+pub struct DLAGen {
+    width: usize,
+    height: usize,
+    config: DLAConfig,
+    map: Map,
+    regions: Vec<Vec<(usize, usize)>>,
+
+    #[cfg(feature = "map_gen_testing")]
+    history: Vec<(Map, String)>,
+}
+
+impl DLAGen {
+    pub fn new(width: usize, height: usize, config: DLAConfig) -> DLAGen {
+        DLAGen {
+            width,
+            height,
+            config,
+            map: Map::new(width, height).with_all_solid(),
+            regions: vec![],
+
+            #[cfg(feature = "map_gen_testing")]
+            history: vec![],
+        }
+    }
+
+    fn random_inner_point(&self) -> Point {
+        Point::new(
+            rng::range(1, self.width as i32 - 2),
+            rng::range(1, self.height as i32 - 2),
+        )
+    }
+
+    fn is_floor(&self, pos: Point) -> bool {
+        let index = self.map.xy_to_index(pos.x as usize, pos.y as usize);
+        !self.map.tiles[index].blocks_movement()
+    }
+
+    /// Random step which doesn't leave map edges.
+    fn stagger(&self, pos: Point) -> Point {
+        let mut pos = pos;
+        match rng::roll_dice(1, 4) {
+            1 if pos.x > 1 => pos.x -= 1,
+            2 if pos.x < self.width as i32 - 2 => pos.x += 1,
+            3 if pos.y > 1 => pos.y -= 1,
+            4 if pos.y < self.height as i32 - 2 => pos.y += 1,
+            _ => (),
+        }
+        pos
+    }
+
+    /// Digs brush sized square at `pos` and its symmetrical copies.
+    fn paint(&mut self, pos: Point) {
+        let (x, y) = (pos.x as usize, pos.y as usize);
+        let mirrored_x = self.width - 1 - x;
+        let mirrored_y = self.height - 1 - y;
+        let points = match self.config.symmetry {
+            DLASymmetry::None => vec![(x, y)],
+            DLASymmetry::Horizontal => vec![(x, y), (mirrored_x, y)],
+            DLASymmetry::Vertical => vec![(x, y), (x, mirrored_y)],
+            DLASymmetry::Both => vec![
+                (x, y),
+                (mirrored_x, y),
+                (x, mirrored_y),
+                (mirrored_x, mirrored_y),
+            ],
+        };
+
+        let brush_size = self.config.brush_size.max(1);
+        for (px, py) in points {
+            for ty in py..py + brush_size {
+                for tx in px..px + brush_size {
+                    if tx >= 1 && ty >= 1 && tx < self.width - 1 && ty < self.height - 1 {
+                        let index = self.map.xy_to_index(tx, ty);
+                        self.map.tiles[index] = TileType::Floor;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Point where digger sticks to cave.
+    fn send_digger(&self, start: Point) -> Point {
+        match self.config.algorithm {
+            DLAAlgorithm::WalkInwards => {
+                let mut digger = self.random_inner_point();
+                let mut prev = digger;
+                while !self.is_floor(digger) {
+                    prev = digger;
+                    digger = self.stagger(digger);
+                }
+                prev
+            }
+            DLAAlgorithm::WalkOutwards => {
+                let mut digger = start;
+                while self.is_floor(digger) {
+                    digger = self.stagger(digger);
+                }
+                digger
+            }
+            DLAAlgorithm::CentralAttractor => {
+                let from = self.random_inner_point();
+                let mut prev = from;
+                for pos in rltk::line2d(LineAlg::Bresenham, from, start) {
+                    if self.is_floor(pos) {
+                        break;
+                    }
+                    prev = pos;
+                }
+                prev
+            }
+        }
+    }
+
+    fn create_dla_map(&mut self, start: Point) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.push((self.map.clone(), "Start".to_string()));
+
+        self.paint(start);
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+            self.paint(Point::new(start.x + dx, start.y + dy));
+        }
+        // symmetrical copy of start has to be connected with it
+        let mirrored_start = match self.config.symmetry {
+            DLASymmetry::None => start,
+            DLASymmetry::Horizontal => Point::new(self.width as i32 - 1 - start.x, start.y),
+            DLASymmetry::Vertical => Point::new(start.x, self.height as i32 - 1 - start.y),
+            DLASymmetry::Both => Point::new(
+                self.width as i32 - 1 - start.x,
+                self.height as i32 - 1 - start.y,
+            ),
+        };
+        for pos in rltk::line2d(LineAlg::Bresenham, start, mirrored_start) {
+            self.paint(pos);
+        }
+
+        #[cfg(feature = "map_gen_testing")]
+        let mut diggers = 0;
+
+        let floor_perc = self.config.floor_perc.min(MAX_FLOOR_PERC);
+        while self.map.floor_tiles_perc() < floor_perc {
+            let pos = self.send_digger(start);
+            self.paint(pos);
+
+            #[cfg(feature = "map_gen_testing")]
+            {
+                diggers += 1;
+                if diggers % HISTORY_STEP_DIGGERS == 0 {
+                    self.history
+                        .push((self.map.clone(), "Sending diggers".to_string()));
+                }
+            }
+        }
+    }
+
+    /// Cave is split by random floor tiles, every tile belongs to closest one.
+    fn create_regions(&mut self) {
+        self.regions = common::split_area(&self.area(), SPAWN_AREA_SIZE);
+    }
+}
+
+impl MapGenerator for DLAGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        if self.width < 5 || self.height < 5 {
+            return Err(Error::IncorrectMapDimensions {
+                map_dimensions: (self.width, self.height),
+            });
+        }
+        let start = prev_down_stairs_pos
+            .filter(|pos| {
+                pos.x >= 1
+                    && pos.y >= 1
+                    && pos.x < self.width as i32 - 1
+                    && pos.y < self.height as i32 - 1
+            })
+            .unwrap_or_else(|| Point::new(self.width as i32 / 2, self.height as i32 / 2));
+        self.create_dla_map(start);
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, StairsPlacement::Random);
+        self.create_regions();
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Finished".to_string()));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.clear();
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.regions.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        self.regions.clone()
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        self.history.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::generators::assert_generates_valid_maps;
+
+    #[test]
+    fn every_preset_is_valid() {
+        for name in DLA_PRESETS.iter() {
+            let mut generator = DLAGen::new(60, 40, DLAConfig::preset(name).unwrap());
+            assert_generates_valid_maps(&mut generator, Some(Point::new(30, 20)), 0..3);
+            assert_generates_valid_maps(&mut generator, Some(Point::new(3, 3)), 3..4);
+            assert_generates_valid_maps(&mut generator, None, 4..5);
+        }
+        assert!(DLAConfig::preset("unknown").is_none());
+    }
+
+    #[test]
+    fn floor_percentage_is_reached() {
+        let mut generator = DLAGen::new(60, 40, DLAConfig::walk_inwards());
+        rng::reseed(0);
+        generator.generate(None).unwrap();
+        // stairs are walkable too
+        let walkable_perc = generator.area().len() * 100 / (60 * 40);
+        assert!(walkable_perc >= DLAConfig::walk_inwards().floor_perc);
+    }
+}
@@ -1,12 +1,18 @@
 use rltk::Point;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     maps::{errors::Result, Map, TileType},
     rng,
 };
 
-use super::MapGenerator;
+use super::{
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DrunkardWalkConfig {
     min_area_perc: usize,
     drunkard_life: usize,
@@ -133,30 +139,6 @@ impl DrunkardWalkGen {
         }
     }
 
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
-
-        let mut random_point = 0;
-
-        while self.map.tiles[random_point].blocks_movement()
-            || self.map.tiles[random_point] == TileType::StairsUp
-        {
-            random_point = rng::range(
-                self.width as i32 + 1,
-                (self.width * (self.height - 1)) as i32,
-            ) as usize;
-        }
-        self.map.tiles[random_point] = TileType::StairsDown;
-    }
-
     fn replace_debug_walls_with_walls(&mut self) {
         for t in self.map.tiles.iter_mut() {
             if *t == TileType::TestWall {
@@ -169,7 +151,7 @@ impl DrunkardWalkGen {
 impl MapGenerator for DrunkardWalkGen {
     fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
         self.create_drunkard_walk_map(prev_down_stairs_pos);
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, StairsPlacement::Random);
         self.replace_debug_walls_with_walls();
         #[cfg(feature = "map_gen_testing")]
         {
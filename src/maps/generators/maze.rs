@@ -0,0 +1,367 @@
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{
+        errors::{Error, Result},
+        Map, TileType,
+    },
+    rng,
+};
+
+use super::{
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MazeConfig {
+    /// width of corridors in tiles, walls are always one tile wide
+    pub corridor_width: usize,
+    /// percentage of dead ends connected with neighbour cell (0 - perfect maze)
+    pub braid_perc: usize,
+    /// growing tree: chance of continuing from newest cell (100 - recursive backtracker),
+    /// otherwise random cell is picked (0 - similar to Prim's algorithm)
+    pub newest_cell_perc: usize,
+    pub stairs: StairsPlacement,
+}
+
+impl Default for MazeConfig {
+    fn default() -> MazeConfig {
+        MazeConfig {
+            corridor_width: 1,
+            braid_perc: 30,
+            newest_cell_perc: 100,
+            stairs: StairsPlacement::Farthest,
+        }
+    }
+}
+
+/// How many cells form one spawn area (in both directions).
+const SPAWN_AREA_CELLS: usize = 4;
+
+/// How many cells are carved between history steps.
+#[cfg(feature = "map_gen_testing")]
+const HISTORY_STEP_CELLS: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+const SIDES: [Side; 4] = [Side::Up, Side::Down, Side::Left, Side::Right];
+
+/// Maze made with growing tree algorithm on grid of cells.
+pub struct MazeGen {
+    width: usize,
+    height: usize,
+    config: MazeConfig,
+    map: Map,
+
+    cells_x: usize,
+    cells_y: usize,
+    /// passages from cell, indexed the same as `SIDES`
+    links: Vec<[bool; 4]>,
+
+    #[cfg(feature = "map_gen_testing")]
+    history: Vec<(Map, String)>,
+}
+
+impl MazeGen {
+    pub fn new(width: usize, height: usize, config: MazeConfig) -> MazeGen {
+        MazeGen {
+            width,
+            height,
+            config,
+            map: Map::new(width, height).with_all_solid(),
+            cells_x: 0,
+            cells_y: 0,
+            links: vec![],
+
+            #[cfg(feature = "map_gen_testing")]
+            history: vec![],
+        }
+    }
+
+    fn corridor_width(&self) -> usize {
+        self.config.corridor_width.max(1)
+    }
+
+    fn cell_size(&self) -> usize {
+        self.corridor_width() + 1
+    }
+
+    fn cell_index(&self, cx: usize, cy: usize) -> usize {
+        cx + cy * self.cells_x
+    }
+
+    /// Upper left tile of cell.
+    fn cell_origin(&self, cell: usize) -> (usize, usize) {
+        let (cx, cy) = (cell % self.cells_x, cell / self.cells_x);
+        (1 + cx * self.cell_size(), 1 + cy * self.cell_size())
+    }
+
+    /// Cell containing tile, None for tiles outside of maze.
+    fn cell_at(&self, x: usize, y: usize) -> Option<usize> {
+        if x < 1 || y < 1 {
+            return None;
+        }
+        let (cx, cy) = ((x - 1) / self.cell_size(), (y - 1) / self.cell_size());
+        (cx < self.cells_x && cy < self.cells_y).then(|| self.cell_index(cx, cy))
+    }
+
+    fn neighbour(&self, cell: usize, side: Side) -> Option<usize> {
+        let (cx, cy) = (cell % self.cells_x, cell / self.cells_x);
+        match side {
+            Side::Up if cy > 0 => Some(self.cell_index(cx, cy - 1)),
+            Side::Down if cy + 1 < self.cells_y => Some(self.cell_index(cx, cy + 1)),
+            Side::Left if cx > 0 => Some(self.cell_index(cx - 1, cy)),
+            Side::Right if cx + 1 < self.cells_x => Some(self.cell_index(cx + 1, cy)),
+            _ => None,
+        }
+    }
+
+    fn carve_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for ty in y..y + height {
+            for tx in x..x + width {
+                let index = self.map.xy_to_index(tx, ty);
+                self.map.tiles[index] = TileType::Floor;
+            }
+        }
+    }
+
+    fn carve_cell(&mut self, cell: usize) {
+        let (x, y) = self.cell_origin(cell);
+        let width = self.corridor_width();
+        self.carve_rect(x, y, width, width);
+    }
+
+    /// Removes wall between cell and its neighbour.
+    fn link(&mut self, cell: usize, side_index: usize) {
+        let side = SIDES[side_index];
+        let neighbour = match self.neighbour(cell, side) {
+            Some(neighbour) => neighbour,
+            None => return,
+        };
+        let opposite = match side {
+            Side::Up => 1,
+            Side::Down => 0,
+            Side::Left => 3,
+            Side::Right => 2,
+        };
+        self.links[cell][side_index] = true;
+        self.links[neighbour][opposite] = true;
+
+        let (x, y) = self.cell_origin(cell);
+        let width = self.corridor_width();
+        match side {
+            Side::Up => self.carve_rect(x, y - 1, width, 1),
+            Side::Down => self.carve_rect(x, y + width, width, 1),
+            Side::Left => self.carve_rect(x - 1, y, 1, width),
+            Side::Right => self.carve_rect(x + width, y, 1, width),
+        }
+    }
+
+    fn create_maze(&mut self, start_cell: usize) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.push((self.map.clone(), "Start".to_string()));
+
+        let mut visited = vec![false; self.links.len()];
+        let mut active = vec![start_cell];
+        visited[start_cell] = true;
+        self.carve_cell(start_cell);
+
+        #[cfg(feature = "map_gen_testing")]
+        let mut carved_cells = 1;
+
+        while !active.is_empty() {
+            let active_index = if rng::test_perc(self.config.newest_cell_perc) {
+                active.len() - 1
+            } else {
+                rng::range(0, active.len() as i32 - 1) as usize
+            };
+            let cell = active[active_index];
+
+            let unvisited = (0..SIDES.len())
+                .filter(|side| {
+                    self.neighbour(cell, SIDES[*side])
+                        .is_some_and(|neighbour| !visited[neighbour])
+                })
+                .collect::<Vec<_>>();
+            if unvisited.is_empty() {
+                active.remove(active_index);
+                continue;
+            }
+
+            let side = unvisited[rng::range(0, unvisited.len() as i32 - 1) as usize];
+            let neighbour = self.neighbour(cell, SIDES[side]).unwrap();
+            self.carve_cell(neighbour);
+            self.link(cell, side);
+            visited[neighbour] = true;
+            active.push(neighbour);
+
+            #[cfg(feature = "map_gen_testing")]
+            {
+                carved_cells += 1;
+                if carved_cells % HISTORY_STEP_CELLS == 0 {
+                    self.history
+                        .push((self.map.clone(), "Carving maze".to_string()));
+                }
+            }
+        }
+    }
+
+    /// Connects some dead ends with one of their neighbours, so maze has loops.
+    fn braid(&mut self) {
+        for cell in 0..self.links.len() {
+            let is_dead_end = self.links[cell].iter().filter(|link| **link).count() == 1;
+            // test_perc(0) still passes sometimes
+            let braided = self.config.braid_perc > 0 && rng::test_perc(self.config.braid_perc);
+            if !is_dead_end || !braided {
+                continue;
+            }
+            let closed = (0..SIDES.len())
+                .filter(|side| {
+                    !self.links[cell][*side] && self.neighbour(cell, SIDES[*side]).is_some()
+                })
+                .collect::<Vec<_>>();
+            if !closed.is_empty() {
+                let side = closed[rng::range(0, closed.len() as i32 - 1) as usize];
+                self.link(cell, side);
+            }
+        }
+
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Removing dead ends".to_string()));
+    }
+}
+
+impl MapGenerator for MazeGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        self.cells_x = (self.width - 1) / self.cell_size();
+        self.cells_y = (self.height - 1) / self.cell_size();
+        if self.cells_x < 2 || self.cells_y < 2 {
+            return Err(Error::IncorrectMapDimensions {
+                map_dimensions: (self.width, self.height),
+            });
+        }
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.links = vec![[false; 4]; self.cells_x * self.cells_y];
+
+        let start_cell = prev_down_stairs_pos
+            .and_then(|pos| self.cell_at(pos.x as usize, pos.y as usize))
+            .unwrap_or_else(|| rng::range(0, (self.cells_x * self.cells_y) as i32 - 1) as usize);
+        self.create_maze(start_cell);
+        self.braid();
+
+        // previous stairs can lie on wall between cells, next to corridor
+        if let Some(pos) = prev_down_stairs_pos {
+            let (x, y) = (pos.x as usize, pos.y as usize);
+            let in_maze = x >= 1
+                && y >= 1
+                && x < self.cells_x * self.cell_size()
+                && y < self.cells_y * self.cell_size();
+            if in_maze {
+                let index = self.map.xy_to_index(x, y);
+                self.map.tiles[index] = TileType::Floor;
+            }
+        }
+
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, self.config.stairs);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Finished".to_string()));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.clear();
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.links.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    /// Floor tiles grouped by blocks of cells.
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        let blocks_x = self.cells_x.div_ceil(SPAWN_AREA_CELLS);
+        let mut areas = vec![vec![]; blocks_x * self.cells_y.div_ceil(SPAWN_AREA_CELLS)];
+        for (i, tile) in self.map.tiles.iter().enumerate() {
+            if *tile != TileType::Floor {
+                continue;
+            }
+            let (x, y) = self.map.index_to_xy(i);
+            if let Some(cell) = self.cell_at(x, y) {
+                let (cx, cy) = (cell % self.cells_x, cell / self.cells_x);
+                let block = cx / SPAWN_AREA_CELLS + (cy / SPAWN_AREA_CELLS) * blocks_x;
+                areas[block].push((x, y));
+            }
+        }
+        areas.retain(|area| !area.is_empty());
+        areas
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        self.history.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::generators::assert_generates_valid_maps;
+
+    #[test]
+    fn maze_is_valid() {
+        for corridor_width in 1..=3 {
+            let config = MazeConfig {
+                corridor_width,
+                ..MazeConfig::default()
+            };
+            let mut generator = MazeGen::new(80, 50, config);
+            assert_generates_valid_maps(&mut generator, Some(Point::new(40, 25)), 0..10);
+            assert_generates_valid_maps(&mut generator, None, 0..3);
+        }
+    }
+
+    #[test]
+    fn perfect_maze_has_no_loops() {
+        let config = MazeConfig {
+            braid_perc: 0,
+            ..MazeConfig::default()
+        };
+        for seed in 0..20 {
+            let mut generator = MazeGen::new(41, 31, config.clone());
+            rng::reseed(seed);
+            generator.generate(None).unwrap();
+
+            let cells = generator.links.len();
+            let links = generator
+                .links
+                .iter()
+                .flatten()
+                .filter(|link| **link)
+                .count();
+            // spanning tree, every link is counted from both cells
+            assert_eq!(links / 2, cells - 1, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn too_small_map_is_rejected() {
+        let mut generator = MazeGen::new(4, 20, MazeConfig::default());
+        assert!(matches!(
+            generator.generate(None),
+            Err(Error::IncorrectMapDimensions { .. })
+        ));
+    }
+}
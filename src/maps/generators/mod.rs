@@ -8,7 +8,13 @@ use self::{
     test_map::TestMap,
 };
 
-use super::{errors::Result, Map};
+use super::{
+    ascii::MapEntity,
+    errors::{Error, Result},
+    validation,
+    vaults::PlacedVault,
+    Map,
+};
 
 // use super::MapGenerator;
 
@@ -16,8 +22,17 @@ pub mod basic_dungeon;
 pub mod bsp;
 pub mod cellular_automata;
 mod common;
+pub mod dla;
 pub mod drunkard_walk;
+pub mod maze;
+pub mod modifiers;
+pub mod pipeline;
+pub mod prefab;
+pub mod stairs;
+pub mod terrain;
 pub mod test_map;
+pub mod voronoi;
+pub mod wfc;
 
 pub fn random_map_generator(width: usize, height: usize) -> Box<dyn MapGenerator> {
     let rand = rng::range(0, 1);
@@ -42,6 +57,16 @@ pub trait MapGenerator {
 
     fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>>;
 
+    /// entities placed on map by generator (spawned together with spawn table)
+    fn entities(&self) -> Vec<MapEntity> {
+        vec![]
+    }
+
+    /// vaults stamped into map, their entities are spawned instead of spawn table
+    fn placed_vaults(&self) -> Vec<PlacedVault> {
+        vec![]
+    }
+
     #[cfg(feature = "map_gen_testing")]
     fn history(&self) -> Vec<(Map, String)>;
 
@@ -66,12 +91,74 @@ pub trait MapGenerator {
     }
 }
 
-#[allow(clippy::type_complexity)]
-pub fn generate_map_and_spawn_areas<T: MapGenerator>(
-    mut generator: T,
+/// How many times map is generated again if it can't be repaired.
+const MAX_GENERATION_TRIES: usize = 10;
+
+/// Map with everything generator placed on it.
+pub struct GeneratedMap {
+    pub map: Map,
+    /// areas for spawn table, vaults are not included
+    pub spawn_areas: Vec<Vec<(usize, usize)>>,
+    pub vault_areas: Vec<Vec<(usize, usize)>>,
+    pub entities: Vec<MapEntity>,
+}
+
+/// Generated map is validated and repaired (see `validation::repair_map`),
+/// if it can't be repaired new map is generated.
+pub fn generate_valid_map(
+    generator: &mut dyn MapGenerator,
     prev_down_stairs_pos: Option<Point>,
-) -> Result<(Map, Vec<Vec<(usize, usize)>>)> {
-    generator.generate(prev_down_stairs_pos)?;
-    let spawn_areas = generator.spawn_areas();
-    Ok((generator.map(), spawn_areas))
+) -> Result<GeneratedMap> {
+    let mut tries = 0;
+    loop {
+        tries += 1;
+        generator.generate(prev_down_stairs_pos)?;
+        let mut map = generator.map();
+        let vaults = generator.placed_vaults();
+        let mut spawn_areas = generator.spawn_areas();
+        for area in spawn_areas.iter_mut() {
+            area.retain(|pos| !vaults.iter().any(|vault| vault.area.contains(pos)));
+        }
+
+        match validation::repair_map(&mut map, &mut spawn_areas, prev_down_stairs_pos) {
+            Ok(()) => {
+                let mut entities = generator.entities();
+                entities.extend(vaults.iter().flat_map(|vault| vault.entities.clone()));
+                return Ok(GeneratedMap {
+                    map,
+                    spawn_areas,
+                    vault_areas: vaults.into_iter().map(|vault| vault.area).collect(),
+                    entities,
+                });
+            }
+            Err(e) if tries >= MAX_GENERATION_TRIES => {
+                return Err(Error::InvalidGeneratedMap {
+                    tries,
+                    source: Box::new(e),
+                })
+            }
+            Err(_) => generator.reset(),
+        }
+    }
+}
+
+/// Generates map for every seed and checks it by `validation::validate_map`, without repair.
+#[cfg(test)]
+pub fn assert_generates_valid_maps(
+    generator: &mut dyn MapGenerator,
+    prev_down_stairs_pos: Option<Point>,
+    seeds: std::ops::Range<u64>,
+) {
+    for seed in seeds {
+        rng::reseed(seed);
+        generator.reset();
+        generator.generate(prev_down_stairs_pos).unwrap();
+        let mut map = generator.map();
+        map.update_blocked_with_blocking_tiles();
+        if let Err(e) =
+            validation::validate_map(&map, &generator.spawn_areas(), prev_down_stairs_pos)
+        {
+            panic!("seed {}: {}", seed, e);
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! Steps applied by `PipelineGen` to map made by its initial generator.
+
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::maps::{validation, Map, TileType};
+
+use super::{
+    common,
+    stairs::{self, StairsPlacement},
+    terrain::{self, TerrainConfig},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MapModifier {
+    /// tiles with more than 4 solid neighbours become walls, with less than 4 floor
+    CellularAutomata { iterations: usize },
+    /// floor in inner corners of rooms becomes wall
+    RoundRoomCorners,
+    /// one tile wide corridors get two tiles wide
+    WidenCorridors,
+    /// only the biggest area is left walkable, previous stairs are connected to it by map repair
+    CullUnreachable,
+    /// old stairs are removed and new are placed
+    Stairs(StairsPlacement),
+    /// spawn areas of initial generator are replaced
+    SpawnAreas(SpawnAreasPartition),
+    /// rivers, lava pools, chasms and vegetation on floor (see `terrain`)
+    Terrain(TerrainConfig),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SpawnAreasPartition {
+    /// regions of about `tiles` walkable tiles around random tiles
+    Regions { tiles: usize },
+    /// walkable tiles in squares of grid
+    Grid { size: usize },
+}
+
+impl MapModifier {
+    /// Name shown in generator history.
+    pub fn name(&self) -> String {
+        match self {
+            MapModifier::CellularAutomata { iterations } => {
+                format!("Cellular automata ({} iterations)", iterations)
+            }
+            MapModifier::RoundRoomCorners => "Rounding room corners".to_string(),
+            MapModifier::WidenCorridors => "Widening corridors".to_string(),
+            MapModifier::CullUnreachable => "Culling unreachable areas".to_string(),
+            MapModifier::Stairs(placement) => format!("Placing stairs ({:?})", placement),
+            MapModifier::SpawnAreas(partition) => format!("Spawn areas ({:?})", partition),
+            MapModifier::Terrain(_) => "Terrain features".to_string(),
+        }
+    }
+
+    pub fn apply(
+        &self,
+        map: &mut Map,
+        spawn_areas: &mut Vec<Vec<(usize, usize)>>,
+        prev_down_stairs_pos: Option<Point>,
+    ) {
+        match self {
+            MapModifier::CellularAutomata { iterations } => {
+                for _ in 0..*iterations {
+                    cellular_automata_step(map);
+                }
+            }
+            MapModifier::RoundRoomCorners => round_room_corners(map),
+            MapModifier::WidenCorridors => widen_corridors(map),
+            MapModifier::CullUnreachable => cull_unreachable(map),
+            MapModifier::Stairs(placement) => {
+                stairs::remove_stairs(map);
+                stairs::place_stairs(map, prev_down_stairs_pos, *placement);
+            }
+            MapModifier::SpawnAreas(partition) => {
+                *spawn_areas = partition_spawn_areas(map, *partition);
+            }
+            MapModifier::Terrain(config) => terrain::add_terrain_features(map, config),
+        }
+    }
+}
+
+fn is_inner(map: &Map, x: usize, y: usize) -> bool {
+    x > 0 && y > 0 && x < map.width_max() && y < map.height_max()
+}
+
+/// Only floor and solid tiles are changed, stairs and doors stay.
+fn cellular_automata_step(map: &mut Map) {
+    let old = map.clone();
+    for (i, tile) in old.tiles.iter().enumerate() {
+        let (x, y) = old.index_to_xy(i);
+        let changeable = *tile == TileType::Floor || tile.is_solid();
+        if !changeable || !is_inner(&old, x, y) {
+            continue;
+        }
+        let solid_neighbours = (y - 1..=y + 1)
+            .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+            .filter(|&(nx, ny)| (nx, ny) != (x, y))
+            .filter(|&(nx, ny)| old.tile_at_xy(nx, ny).is_solid())
+            .count();
+        if solid_neighbours > 4 && !tile.is_solid() {
+            map.tiles[i] = TileType::Wall;
+        } else if solid_neighbours < 4 && tile.is_solid() {
+            map.tiles[i] = TileType::Floor;
+        }
+    }
+}
+
+/// Floor with walls on two touching sides and at least 2x2 floor on the other side.
+fn round_room_corners(map: &mut Map) {
+    let old = map.clone();
+    let solid = |x: usize, y: usize| old.tile_at_xy(x, y).is_solid();
+    for (i, tile) in old.tiles.iter().enumerate() {
+        let (x, y) = old.index_to_xy(i);
+        if *tile != TileType::Floor || !is_inner(&old, x, y) {
+            continue;
+        }
+        for &(dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter() {
+            let (nx, ny) = ((x as i32 + dx) as usize, (y as i32 + dy) as usize);
+            let (ox, oy) = ((x as i32 - dx) as usize, (y as i32 - dy) as usize);
+            if solid(nx, y) && solid(x, ny) && !solid(ox, y) && !solid(x, oy) && !solid(ox, oy) {
+                map.tiles[i] = TileType::Wall;
+                break;
+            }
+        }
+    }
+}
+
+/// Wall right of (or below) corridor is dug out. Doors aren't widened, so they aren't bypassed.
+fn widen_corridors(map: &mut Map) {
+    let old = map.clone();
+    let solid = |x: usize, y: usize| old.tile_at_xy(x, y).is_solid();
+    for (i, tile) in old.tiles.iter().enumerate() {
+        let (x, y) = old.index_to_xy(i);
+        if tile.is_solid() || tile.is_door() || !is_inner(&old, x, y) {
+            continue;
+        }
+        if solid(x - 1, y) && solid(x + 1, y) && is_inner(&old, x + 1, y) {
+            let index = map.xy_to_index(x + 1, y);
+            map.tiles[index] = TileType::Floor;
+        }
+        if solid(x, y - 1) && solid(x, y + 1) && is_inner(&old, x, y + 1) {
+            let index = map.xy_to_index(x, y + 1);
+            map.tiles[index] = TileType::Floor;
+        }
+    }
+}
+
+fn cull_unreachable(map: &mut Map) {
+    map.update_blocked_with_blocking_tiles();
+    for region in validation::connected_regions(map).iter().skip(1) {
+        for index in region.iter() {
+            map.tiles[*index] = TileType::Wall;
+        }
+    }
+    map.update_blocked_with_blocking_tiles();
+}
+
+fn partition_spawn_areas(map: &Map, partition: SpawnAreasPartition) -> Vec<Vec<(usize, usize)>> {
+    let area = (0..map.tiles.len())
+        .filter(|i| map.tiles[*i] == TileType::Floor)
+        .map(|i| map.index_to_xy(i))
+        .collect::<Vec<_>>();
+    match partition {
+        SpawnAreasPartition::Regions { tiles } => common::split_area(&area, tiles.max(1)),
+        SpawnAreasPartition::Grid { size } => {
+            let size = size.max(1);
+            let squares_x = map.width.div_ceil(size);
+            let mut areas = vec![vec![]; squares_x * map.height.div_ceil(size)];
+            for (x, y) in area {
+                areas[x / size + (y / size) * squares_x].push((x, y));
+            }
+            areas.retain(|area| !area.is_empty());
+            areas
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{
+        ascii::{map_from_ascii, map_to_ascii},
+        DoorState,
+    };
+
+    fn apply(modifier: MapModifier, text: &str) -> (Map, Vec<Vec<(usize, usize)>>) {
+        let mut map = map_from_ascii(text).unwrap().map;
+        let mut spawn_areas = vec![];
+        modifier.apply(&mut map, &mut spawn_areas, None);
+        (map, spawn_areas)
+    }
+
+    #[test]
+    fn cull_unreachable_leaves_single_region() {
+        let (map, _) = apply(
+            MapModifier::CullUnreachable,
+            "##########\n\
+             #....#..##\n\
+             #....#..##\n\
+             #....###.#\n\
+             ##########\n",
+        );
+
+        assert_eq!(validation::connected_regions(&map).len(), 1);
+        assert_eq!(
+            map_to_ascii(&map, &[]).unwrap(),
+            "##########\n\
+             #....#####\n\
+             #....#####\n\
+             #....#####\n\
+             ##########\n"
+        );
+    }
+
+    #[test]
+    fn corridors_are_widened_except_doors() {
+        let (map, _) = apply(
+            MapModifier::WidenCorridors,
+            "#########\n\
+             #...#...#\n\
+             #.......#\n\
+             #...#...#\n\
+             #...+...#\n\
+             #...#...#\n\
+             #########\n",
+        );
+
+        assert_eq!(
+            map_to_ascii(&map, &[]).unwrap(),
+            "#########\n\
+             #...#...#\n\
+             #.......#\n\
+             #.......#\n\
+             #...+...#\n\
+             #...#...#\n\
+             #########\n"
+        );
+    }
+
+    #[test]
+    fn room_corners_are_rounded() {
+        let (map, _) = apply(
+            MapModifier::RoundRoomCorners,
+            "######\n\
+             #....#\n\
+             #....#\n\
+             #....#\n\
+             ######\n",
+        );
+
+        assert_eq!(
+            map_to_ascii(&map, &[]).unwrap(),
+            "######\n\
+             ##..##\n\
+             #....#\n\
+             ##..##\n\
+             ######\n"
+        );
+    }
+
+    #[test]
+    fn cellular_automata_keeps_stairs_and_doors() {
+        let (map, _) = apply(
+            MapModifier::CellularAutomata { iterations: 2 },
+            "#######\n\
+             #.....#\n\
+             #..#..#\n\
+             #.<.+.#\n\
+             ###.###\n\
+             ###.###\n\
+             #######\n",
+        );
+
+        assert_eq!(map.tile_at_xy(3, 2), TileType::Floor);
+        assert_eq!(map.tile_at_xy(2, 3), TileType::StairsUp);
+        assert_eq!(map.tile_at_xy(4, 3), TileType::Door(DoorState::Closed));
+        assert!(map.tile_at_xy(3, 5).is_solid());
+    }
+
+    #[test]
+    fn stairs_are_placed_again() {
+        let mut map = map_from_ascii(
+            "############\n\
+             #>........<#\n\
+             ############\n",
+        )
+        .unwrap()
+        .map;
+        let modifier = MapModifier::Stairs(StairsPlacement::Farthest);
+        modifier.apply(&mut map, &mut vec![], Some(Point::new(1, 1)));
+
+        assert_eq!(map.tile_at_xy(1, 1), TileType::StairsUp);
+        assert_eq!(map.tile_at_xy(10, 1), TileType::StairsDown);
+        assert_eq!(map.floor_tiles_count(), 8);
+    }
+
+    #[test]
+    fn spawn_areas_are_split_by_grid() {
+        let (_map, spawn_areas) = apply(
+            MapModifier::SpawnAreas(SpawnAreasPartition::Grid { size: 4 }),
+            "########\n\
+             #......#\n\
+             #..<...#\n\
+             #......#\n\
+             #......#\n\
+             ########\n",
+        );
+
+        assert_eq!(spawn_areas.len(), 4);
+        assert_eq!(spawn_areas.iter().map(|area| area.len()).sum::<usize>(), 23);
+        for area in spawn_areas.iter() {
+            let square = (area[0].0 / 4, area[0].1 / 4);
+            assert!(area.iter().all(|&(x, y)| (x / 4, y / 4) == square));
+        }
+    }
+}
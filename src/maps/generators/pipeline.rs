@@ -0,0 +1,114 @@
+use rltk::Point;
+
+use crate::maps::{
+    ascii::MapEntity,
+    errors::Result,
+    vaults::{self, PlacedVault, Vault},
+    Map,
+};
+
+use super::{modifiers::MapModifier, MapGenerator};
+
+/// Map of initial generator changed by chain of modifiers (see `modifiers`).
+pub struct PipelineGen {
+    initial: Box<dyn MapGenerator>,
+    modifiers: Vec<MapModifier>,
+    map: Map,
+    spawn_areas: Vec<Vec<(usize, usize)>>,
+    vaults: Vec<Vault>,
+    placed_vaults: Vec<PlacedVault>,
+
+    #[cfg(feature = "map_gen_testing")]
+    history: Vec<(Map, String)>,
+}
+
+impl PipelineGen {
+    pub fn new(initial: Box<dyn MapGenerator>, modifiers: Vec<MapModifier>) -> PipelineGen {
+        PipelineGen {
+            map: initial.map(),
+            initial,
+            modifiers,
+            spawn_areas: vec![],
+            vaults: vec![],
+            placed_vaults: vec![],
+
+            #[cfg(feature = "map_gen_testing")]
+            history: vec![],
+        }
+    }
+
+    /// Vaults are placed after all modifiers, so they aren't changed by them.
+    pub fn with_vaults(mut self, vaults: Vec<Vault>) -> PipelineGen {
+        self.vaults = vaults;
+        self
+    }
+}
+
+impl MapGenerator for PipelineGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        self.initial.generate(prev_down_stairs_pos)?;
+        self.map = self.initial.map();
+        self.spawn_areas = self.initial.spawn_areas();
+        #[cfg(feature = "map_gen_testing")]
+        {
+            self.history = self.initial.history();
+        }
+
+        for modifier in self.modifiers.iter() {
+            modifier.apply(&mut self.map, &mut self.spawn_areas, prev_down_stairs_pos);
+            #[cfg(feature = "map_gen_testing")]
+            self.history.push((self.map.clone(), modifier.name()));
+        }
+
+        let map = &self.map;
+        for area in self.spawn_areas.iter_mut() {
+            area.retain(|&(x, y)| {
+                let tile = map.tile_at_xy(x, y);
+                !tile.blocks_movement() && !tile.is_hazard()
+            });
+        }
+        self.spawn_areas.retain(|area| !area.is_empty());
+
+        self.placed_vaults =
+            vaults::place_vaults(&mut self.map, &self.vaults, prev_down_stairs_pos);
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Placing vaults".to_string()));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.clear();
+        self.initial.reset();
+        self.map = self.initial.map();
+        self.spawn_areas.clear();
+        self.placed_vaults.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        self.spawn_areas.clone()
+    }
+
+    /// Entities of initial generator, which weren't walled in by modifiers.
+    fn entities(&self) -> Vec<MapEntity> {
+        self.initial
+            .entities()
+            .into_iter()
+            .filter(|e| !self.map.tile_at_xy(e.x, e.y).blocks_movement())
+            .collect()
+    }
+
+    fn placed_vaults(&self) -> Vec<PlacedVault> {
+        self.placed_vaults.clone()
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        self.history.clone()
+    }
+}
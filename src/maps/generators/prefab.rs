@@ -0,0 +1,113 @@
+use rltk::Point;
+
+use crate::maps::{
+    ascii::{self, MapEntity},
+    errors::Result,
+    Map, TileType,
+};
+
+use super::{stairs, MapGenerator};
+
+/// Level loaded from ascii file (see `maps::ascii`) in prefabs directory.
+/// If prefab is smaller than level, it's placed in the middle, surrounded by walls.
+pub struct PrefabGen {
+    name: String,
+    width: usize,
+    height: usize,
+    map: Map,
+    entities: Vec<MapEntity>,
+    /// marked in prefab, empty if there were none
+    spawn_areas: Vec<Vec<(usize, usize)>>,
+}
+
+impl PrefabGen {
+    pub fn new(name: &str, width: usize, height: usize) -> PrefabGen {
+        PrefabGen {
+            name: name.to_string(),
+            width,
+            height,
+            map: Map::new(width, height).with_all_solid(),
+            entities: vec![],
+            spawn_areas: vec![],
+        }
+    }
+}
+
+impl MapGenerator for PrefabGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        let prefab = ascii::load_ascii_map(&ascii::prefab_path(&self.name))?;
+
+        let width = self.width.max(prefab.map.width);
+        let height = self.height.max(prefab.map.height);
+        let offset_x = (width - prefab.map.width) / 2;
+        let offset_y = (height - prefab.map.height) / 2;
+
+        self.map = Map::new(width, height).with_all_solid();
+        for (i, tile) in prefab.map.tiles.iter().enumerate() {
+            let (x, y) = prefab.map.index_to_xy(i);
+            let index = self.map.xy_to_index(x + offset_x, y + offset_y);
+            self.map.tiles[index] = *tile;
+        }
+        self.entities = prefab
+            .entities
+            .into_iter()
+            .map(|entity| MapEntity {
+                x: entity.x + offset_x,
+                y: entity.y + offset_y,
+                ..entity
+            })
+            .collect();
+        self.spawn_areas = prefab
+            .spawn_areas
+            .into_iter()
+            .map(|area| {
+                area.into_iter()
+                    .map(|(x, y)| (x + offset_x, y + offset_y))
+                    .collect()
+            })
+            .collect();
+
+        stairs::place_up_stairs(&mut self.map, prev_down_stairs_pos);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.entities.clear();
+        self.spawn_areas.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    /// Areas marked in prefab, all free floor tiles if there are none.
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        if !self.spawn_areas.is_empty() {
+            return self.spawn_areas.clone();
+        }
+        let area: Vec<(usize, usize)> = self
+            .map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_i, tile)| **tile == TileType::Floor)
+            .map(|(i, _tile)| self.map.index_to_xy(i))
+            .filter(|(x, y)| !self.entities.iter().any(|e| e.x == *x && e.y == *y))
+            .collect();
+        if area.is_empty() {
+            vec![]
+        } else {
+            vec![area]
+        }
+    }
+
+    fn entities(&self) -> Vec<MapEntity> {
+        self.entities.clone()
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        vec![]
+    }
+}
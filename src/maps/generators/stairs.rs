@@ -0,0 +1,214 @@
+//! Stairs placement shared by generators and pipeline modifiers.
+
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{rect::Rect, validation, Map, TileType},
+    rng,
+};
+
+/// Room generators make rooms again at most this many times, until a room covers position
+/// of up stairs. Otherwise up stairs are connected by map repair.
+pub const MAX_STAIRS_ROOM_TRIES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StairsPlacement {
+    /// down stairs on random walkable tile
+    Random,
+    /// down stairs on walkable tile farthest from up stairs
+    Farthest,
+    /// down stairs in center of random room, maps without rooms use random walkable tile
+    RoomCenter,
+}
+
+pub fn is_inside_map(map: &Map, pos: Point) -> bool {
+    pos.x >= 0 && pos.y >= 0 && (pos.x as usize) < map.width && (pos.y as usize) < map.height
+}
+
+/// Up stairs are placed where down stairs of previous level were, if that tile is walkable.
+/// Position outside of map is skipped, map repair reports it.
+pub fn place_up_stairs(map: &mut Map, prev_down_stairs_pos: Option<Point>) {
+    if let Some(prev_stairs) = prev_down_stairs_pos {
+        if !is_inside_map(map, prev_stairs) {
+            return;
+        }
+        let index = map.xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
+
+        if !map.tiles[index].blocks_movement() {
+            map.tiles[index] = TileType::StairsUp;
+        }
+    }
+}
+
+/// Stairs aren't placed if there is no walkable tile other than up stairs.
+pub fn place_down_stairs_randomly(map: &mut Map) {
+    let candidates = (0..map.tiles.len())
+        .filter(|i| !map.tiles[*i].blocks_movement() && map.tiles[*i] != TileType::StairsUp)
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return;
+    }
+    let index = candidates[rng::range(0, candidates.len() as i32 - 1) as usize];
+    map.tiles[index] = TileType::StairsDown;
+}
+
+/// Rooms with blocked center (e.g. up stairs) are skipped, random walkable tile is used
+/// if there is no such room.
+pub fn place_down_stairs_in_room_center(map: &mut Map, rooms: &[Rect]) {
+    let candidates = rooms
+        .iter()
+        .map(|room| map.xy_to_index(room.center().0, room.center().1))
+        .filter(|i| !map.tiles[*i].blocks_movement() && map.tiles[*i] != TileType::StairsUp)
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        place_down_stairs_randomly(map);
+        return;
+    }
+    let index = candidates[rng::range(0, candidates.len() as i32 - 1) as usize];
+    map.tiles[index] = TileType::StairsDown;
+}
+
+/// Distance is measured from up stairs, or from random walkable tile if there are none.
+pub fn place_down_stairs_farthest(map: &mut Map) {
+    let walkable = (0..map.tiles.len())
+        .filter(|i| !map.tiles[*i].blocks_movement())
+        .collect::<Vec<_>>();
+    if walkable.is_empty() {
+        return;
+    }
+    let start = map
+        .tiles
+        .iter()
+        .position(|tile| *tile == TileType::StairsUp)
+        .unwrap_or_else(|| walkable[rng::range(0, walkable.len() as i32 - 1) as usize]);
+
+    map.update_blocked_with_blocking_tiles();
+    let farthest = validation::distances_from(map, start)
+        .iter()
+        .enumerate()
+        .filter(|(i, _distance)| *i != start)
+        .filter_map(|(i, distance)| distance.map(|distance| (i, distance)))
+        .max_by_key(|(_i, distance)| *distance)
+        .map(|(i, _distance)| i);
+    if let Some(index) = farthest {
+        map.tiles[index] = TileType::StairsDown;
+    }
+}
+
+/// Stairs are replaced with floor.
+pub fn remove_stairs(map: &mut Map) {
+    for tile in map.tiles.iter_mut() {
+        if *tile == TileType::StairsDown || *tile == TileType::StairsUp {
+            *tile = TileType::Floor;
+        }
+    }
+}
+
+/// Up stairs at position of previous level down stairs, down stairs by `placement`.
+pub fn place_stairs(
+    map: &mut Map,
+    prev_down_stairs_pos: Option<Point>,
+    placement: StairsPlacement,
+) {
+    place_stairs_with_rooms(map, prev_down_stairs_pos, placement, &[]);
+}
+
+/// The same as `place_stairs`, for generators which know their rooms.
+pub fn place_stairs_with_rooms(
+    map: &mut Map,
+    prev_down_stairs_pos: Option<Point>,
+    placement: StairsPlacement,
+    rooms: &[Rect],
+) {
+    place_up_stairs(map, prev_down_stairs_pos);
+    match placement {
+        StairsPlacement::Random => place_down_stairs_randomly(map),
+        StairsPlacement::Farthest => place_down_stairs_farthest(map),
+        StairsPlacement::RoomCenter => place_down_stairs_in_room_center(map, rooms),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::ascii::map_from_ascii;
+
+    const MAP: &str = "##########\n\
+                       #........#\n\
+                       #.####...#\n\
+                       #........#\n\
+                       ##########\n";
+
+    #[test]
+    fn up_stairs_outside_of_map_are_skipped() {
+        let mut map = map_from_ascii(MAP).unwrap().map;
+        for &pos in [Point::new(10, 1), Point::new(1, 5), Point::new(-1, 2)].iter() {
+            place_up_stairs(&mut map, Some(pos));
+        }
+        place_up_stairs(&mut map, Some(Point::new(2, 2)));
+
+        assert!(!map.tiles.contains(&TileType::StairsUp));
+    }
+
+    #[test]
+    fn down_stairs_are_placed_farthest_from_up_stairs() {
+        let mut map = map_from_ascii(MAP).unwrap().map;
+        place_stairs(&mut map, Some(Point::new(1, 1)), StairsPlacement::Farthest);
+
+        assert_eq!(map.tile_at_xy(1, 1), TileType::StairsUp);
+        let down_stairs = map
+            .tiles
+            .iter()
+            .position(|tile| *tile == TileType::StairsDown)
+            .unwrap();
+        assert_eq!(map.index_to_xy(down_stairs).0, 8);
+    }
+
+    #[test]
+    fn random_down_stairs_dont_replace_up_stairs() {
+        for seed in 0..20 {
+            rng::reseed(seed);
+            let mut map = map_from_ascii(MAP).unwrap().map;
+            place_stairs(&mut map, Some(Point::new(1, 1)), StairsPlacement::Random);
+
+            assert_eq!(map.tile_at_xy(1, 1), TileType::StairsUp);
+            assert_eq!(
+                map.tiles
+                    .iter()
+                    .filter(|tile| **tile == TileType::StairsDown)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn random_down_stairs_are_skipped_without_free_tile() {
+        let mut map = map_from_ascii("###\n#.#\n###\n").unwrap().map;
+        place_stairs(&mut map, Some(Point::new(1, 1)), StairsPlacement::Random);
+        assert_eq!(map.tile_at_xy(1, 1), TileType::StairsUp);
+
+        let mut map = map_from_ascii("###\n###\n###\n").unwrap().map;
+        place_stairs(&mut map, None, StairsPlacement::RoomCenter);
+        assert!(!map.tiles.contains(&TileType::StairsDown));
+    }
+
+    #[test]
+    fn down_stairs_are_placed_in_room_center() {
+        let rooms = [Rect::new(1, 1, 2, 2), Rect::new(6, 1, 2, 2)];
+        for seed in 0..10 {
+            rng::reseed(seed);
+            let mut map = map_from_ascii(MAP).unwrap().map;
+            place_stairs_with_rooms(
+                &mut map,
+                Some(Point::new(2, 2)),
+                StairsPlacement::RoomCenter,
+                &rooms,
+            );
+
+            // center of the first room is a wall
+            assert_eq!(map.tile_at_xy(7, 2), TileType::StairsDown, "seed {}", seed);
+        }
+    }
+}
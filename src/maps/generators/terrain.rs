@@ -0,0 +1,355 @@
+//! Terrain features added to finished map: rivers crossed by bridges, lava pools, chasms,
+//! grass with trees and rubble. Only floor tiles are changed, so stairs, doors and vaults stay.
+//! Hazards never split map: pool which would cut off part of map isn't placed.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{validation, Map, TileType},
+    rng,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    /// rivers going from one map edge to the other, deep in the middle
+    pub rivers: usize,
+    pub lava_pools: usize,
+    pub chasms: usize,
+    /// tiles of one lava pool or chasm
+    pub pool_size: usize,
+    pub grass_patches: usize,
+    /// chance of tree on grass
+    pub trees_perc: usize,
+    /// chance of rubble on floor next to wall
+    pub rubble_perc: usize,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> TerrainConfig {
+        TerrainConfig {
+            rivers: 1,
+            lava_pools: 2,
+            chasms: 1,
+            pool_size: 12,
+            grass_patches: 3,
+            trees_perc: 15,
+            rubble_perc: 5,
+        }
+    }
+}
+
+/// Hazards are kept at least this far from stairs.
+const STAIRS_CLEARANCE: usize = 3;
+
+pub fn add_terrain_features(map: &mut Map, config: &TerrainConfig) {
+    for _ in 0..config.rivers {
+        add_river(map);
+    }
+    build_bridges(map);
+
+    for _ in 0..config.lava_pools {
+        add_hazard_pool(map, TileType::Lava, config.pool_size);
+    }
+    for _ in 0..config.chasms {
+        add_hazard_pool(map, TileType::Chasm, config.pool_size);
+    }
+
+    for _ in 0..config.grass_patches {
+        add_grass_patch(map, config.trees_perc);
+    }
+    add_rubble(map, config.rubble_perc);
+
+    map.update_blocked_with_blocking_tiles();
+}
+
+/// River meanders from top to bottom (or left to right) edge, deep water with shallow banks.
+fn add_river(map: &mut Map) {
+    let vertical = rng::rand_bool();
+    let (length, breadth) = if vertical {
+        (map.height, map.width)
+    } else {
+        (map.width, map.height)
+    };
+    if breadth < 8 {
+        return;
+    }
+
+    let mut across = rng::range(3, breadth as i32 - 4);
+    for along in 1..length - 1 {
+        across = (across + rng::range(-1, 1)).clamp(2, breadth as i32 - 3);
+        for offset in -1..=1 {
+            let c = (across + offset) as usize;
+            let (x, y) = if vertical { (c, along) } else { (along, c) };
+            let index = map.xy_to_index(x, y);
+            if map.tiles[index] == TileType::Floor {
+                map.tiles[index] = if offset == 0 {
+                    TileType::DeepWater
+                } else {
+                    TileType::ShallowWater
+                };
+            }
+        }
+    }
+}
+
+/// Regions split by deep water are connected to the biggest one by bridges.
+fn build_bridges(map: &mut Map) {
+    map.update_blocked_with_blocking_tiles();
+    let mut regions = validation::connected_regions(map);
+    while regions.len() > 1 {
+        match path_across_water(map, &regions[0]) {
+            Some(path) => {
+                for index in path {
+                    if map.tiles[index] == TileType::DeepWater {
+                        map.tiles[index] = TileType::Bridge;
+                    }
+                }
+            }
+            // regions separated by walls are left for map repair
+            None => break,
+        }
+        map.update_blocked_with_blocking_tiles();
+        regions = validation::connected_regions(map);
+    }
+}
+
+/// Shortest path (orthogonal moves) from `region` through deep water to other walkable tile.
+fn path_across_water(map: &Map, region: &[usize]) -> Option<Vec<usize>> {
+    let mut in_region = vec![false; map.tiles.len()];
+    let mut came_from = vec![None; map.tiles.len()];
+    let mut queue = VecDeque::new();
+    for &index in region.iter() {
+        in_region[index] = true;
+        came_from[index] = Some(index);
+        queue.push_back(index);
+    }
+
+    while let Some(index) = queue.pop_front() {
+        if !in_region[index] && !map.blocked[index] {
+            let mut path = vec![index];
+            let mut current = index;
+            while !in_region[current] {
+                current = came_from[current].unwrap();
+                path.push(current);
+            }
+            return Some(path);
+        }
+
+        let (x, y) = map.index_to_xy(index);
+        for (nx, ny) in orthogonal_neighbours(map, x, y) {
+            let neighbour = map.xy_to_index(nx, ny);
+            let passable = !map.blocked[neighbour] || map.tiles[neighbour] == TileType::DeepWater;
+            if passable && came_from[neighbour].is_none() {
+                came_from[neighbour] = Some(index);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    None
+}
+
+/// Pool is placed only if everything walkable stays reachable without crossing hazards.
+fn add_hazard_pool(map: &mut Map, hazard: TileType, size: usize) {
+    let stairs = (0..map.tiles.len())
+        .filter(|i| matches!(map.tiles[*i], TileType::StairsUp | TileType::StairsDown))
+        .map(|i| map.index_to_xy(i))
+        .collect::<Vec<_>>();
+    let far_from_stairs = |(x, y): (usize, usize)| {
+        stairs.iter().all(|&(sx, sy)| {
+            (x as i32 - sx as i32)
+                .abs()
+                .max((y as i32 - sy as i32).abs())
+                > STAIRS_CLEARANCE as i32
+        })
+    };
+
+    let start = match random_floor_tile(map, far_from_stairs) {
+        Some(start) => start,
+        None => return,
+    };
+
+    let regions_before = safe_regions_count(map);
+    let old = map.clone();
+    for index in floor_blob(map, start, size) {
+        if far_from_stairs(map.index_to_xy(index)) {
+            map.tiles[index] = hazard;
+        }
+    }
+    if safe_regions_count(map) > regions_before {
+        *map = old;
+    }
+}
+
+fn add_grass_patch(map: &mut Map, trees_perc: usize) {
+    if let Some(start) = random_floor_tile(map, |_pos| true) {
+        let size = rng::range(10, 30) as usize;
+        for index in floor_blob(map, start, size) {
+            map.tiles[index] = if rng::test_perc(trees_perc) {
+                TileType::Tree
+            } else {
+                TileType::Grass
+            };
+        }
+    }
+}
+
+fn add_rubble(map: &mut Map, rubble_perc: usize) {
+    let old = map.clone();
+    for (i, tile) in old.tiles.iter().enumerate() {
+        let (x, y) = old.index_to_xy(i);
+        let next_to_wall =
+            orthogonal_neighbours(&old, x, y).any(|(nx, ny)| old.tile_at_xy(nx, ny).is_solid());
+        if *tile == TileType::Floor && next_to_wall && rng::test_perc(rubble_perc) {
+            map.tiles[i] = TileType::Rubble;
+        }
+    }
+}
+
+/// Number of regions reachable from each other, if hazards were walls.
+fn safe_regions_count(map: &Map) -> usize {
+    let mut map = map.clone();
+    map.update_blocked_with_blocking_tiles();
+    for (i, tile) in map.tiles.iter().enumerate() {
+        if tile.is_hazard() {
+            map.blocked[i] = true;
+        }
+    }
+    validation::connected_regions(&map).len()
+}
+
+fn random_floor_tile(map: &Map, filter: impl Fn((usize, usize)) -> bool) -> Option<usize> {
+    let floor = (0..map.tiles.len())
+        .filter(|i| map.tiles[*i] == TileType::Floor && filter(map.index_to_xy(*i)))
+        .collect::<Vec<_>>();
+    if floor.is_empty() {
+        return None;
+    }
+    Some(floor[rng::range(0, floor.len() as i32 - 1) as usize])
+}
+
+/// Up to `size` floor tiles connected to `start`, grown in random directions.
+fn floor_blob(map: &Map, start: usize, size: usize) -> Vec<usize> {
+    let mut blob = vec![start];
+    let mut frontier = vec![start];
+    while blob.len() < size && !frontier.is_empty() {
+        let i = rng::range(0, frontier.len() as i32 - 1) as usize;
+        let (x, y) = map.index_to_xy(frontier[i]);
+        let candidates = orthogonal_neighbours(map, x, y)
+            .map(|(nx, ny)| map.xy_to_index(nx, ny))
+            .filter(|n| map.tiles[*n] == TileType::Floor && !blob.contains(n))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            frontier.swap_remove(i);
+            continue;
+        }
+        let next = candidates[rng::range(0, candidates.len() as i32 - 1) as usize];
+        blob.push(next);
+        frontier.push(next);
+    }
+    blob
+}
+
+/// Neighbours inside map border.
+fn orthogonal_neighbours(
+    map: &Map,
+    x: usize,
+    y: usize,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let (x, y) = (x as i32, y as i32);
+    vec![(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+        .into_iter()
+        .filter(move |&(nx, ny)| {
+            nx > 0 && ny > 0 && nx < map.width_max() as i32 && ny < map.height_max() as i32
+        })
+        .map(|(nx, ny)| (nx as usize, ny as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::ascii::map_from_ascii;
+
+    #[test]
+    fn river_splitting_map_is_crossed_by_bridge() {
+        let mut map = map_from_ascii(
+            "##########\n\
+             #..~_~...#\n\
+             #..~_~...#\n\
+             #..~_~...#\n\
+             ##########\n",
+        )
+        .unwrap()
+        .map;
+        build_bridges(&mut map);
+        map.update_blocked_with_blocking_tiles();
+
+        assert_eq!(validation::connected_regions(&map).len(), 1);
+        assert_eq!(
+            map.tiles
+                .iter()
+                .filter(|tile| **tile == TileType::Bridge)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn map_with_river_stays_connected() {
+        for seed in 0..10 {
+            rng::reseed(seed);
+            let mut map = Map::new(40, 30).with_edges_solid();
+            add_river(&mut map);
+            assert!(map.tiles.contains(&TileType::DeepWater));
+
+            build_bridges(&mut map);
+            map.update_blocked_with_blocking_tiles();
+            assert_eq!(
+                validation::connected_regions(&map).len(),
+                1,
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn pool_which_would_split_map_is_reverted() {
+        let map = map_from_ascii("################\n#<............>#\n################\n")
+            .unwrap()
+            .map;
+        for seed in 0..10 {
+            rng::reseed(seed);
+            let mut pooled = map.clone();
+            add_hazard_pool(&mut pooled, TileType::Lava, 4);
+            assert_eq!(pooled.tiles, map.tiles, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn pool_is_placed_away_from_stairs() {
+        let mut map = Map::new(30, 20).with_edges_solid();
+        let stairs = map.xy_to_index(5, 5);
+        map.tiles[stairs] = TileType::StairsUp;
+        for seed in 0..10 {
+            rng::reseed(seed);
+            let mut pooled = map.clone();
+            add_hazard_pool(&mut pooled, TileType::Chasm, 12);
+
+            let pool = (0..pooled.tiles.len())
+                .filter(|i| pooled.tiles[*i] == TileType::Chasm)
+                .map(|i| pooled.index_to_xy(i))
+                .collect::<Vec<_>>();
+            assert!(!pool.is_empty(), "seed {}", seed);
+            for (x, y) in pool {
+                assert!(
+                    x.max(5) - x.min(5) > STAIRS_CLEARANCE
+                        || y.max(5) - y.min(5) > STAIRS_CLEARANCE
+                );
+            }
+            assert_eq!(safe_regions_count(&pooled), 1);
+        }
+    }
+}
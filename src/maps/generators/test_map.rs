@@ -1,11 +1,11 @@
 use rltk::Point;
 
-use crate::{
-    maps::{errors::Result, Map, TileType},
-    rng,
-};
+use crate::maps::{errors::Result, Map};
 
-use super::MapGenerator;
+use super::{
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
 
 pub struct TestMap {
     pub width: usize,
@@ -21,35 +21,12 @@ impl TestMap {
             map: Map::new(width, height),
         }
     }
-
-    fn add_up_and_down_stairs(&mut self, prev_down_stairs_pos: Option<Point>) {
-        let mut random_point = 0;
-        if let Some(prev_stairs) = prev_down_stairs_pos {
-            let index = self
-                .map
-                .xy_to_index(prev_stairs.x as usize, prev_stairs.y as usize);
-
-            if !self.map.tiles[index].blocks_movement() {
-                self.map.tiles[index] = TileType::StairsUp;
-            }
-        }
-
-        while self.map.tiles[random_point].blocks_movement()
-            || self.map.tiles[random_point] == TileType::StairsUp
-        {
-            random_point = rng::range(
-                self.width as i32 + 1,
-                (self.width * (self.height - 1)) as i32,
-            ) as usize;
-        }
-        self.map.tiles[random_point] = TileType::StairsDown;
-    }
 }
 
 impl MapGenerator for TestMap {
     fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
         self.map = self.map.clone().with_edges_solid();
-        self.add_up_and_down_stairs(prev_down_stairs_pos);
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, StairsPlacement::Random);
         Ok(())
     }
 
@@ -0,0 +1,375 @@
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{
+        errors::{Error, Result},
+        validation, Map, TileType,
+    },
+    rng,
+};
+
+use super::{
+    common,
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoronoiDistance {
+    Pythagoras,
+    Manhattan,
+    Chebyshev,
+}
+
+impl VoronoiDistance {
+    fn distance(&self, a: (usize, usize), b: (usize, usize)) -> usize {
+        let dx = (a.0 as i64 - b.0 as i64).unsigned_abs() as usize;
+        let dy = (a.1 as i64 - b.1 as i64).unsigned_abs() as usize;
+        match self {
+            // squared, only used for comparing
+            VoronoiDistance::Pythagoras => dx * dx + dy * dy,
+            VoronoiDistance::Manhattan => dx + dy,
+            VoronoiDistance::Chebyshev => dx.max(dy),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VoronoiConfig {
+    pub seeds_count: usize,
+    pub distance: VoronoiDistance,
+    /// chance of cell being carved, cell with previous stairs is always carved
+    pub open_cells_perc: usize,
+    pub stairs: StairsPlacement,
+}
+
+impl Default for VoronoiConfig {
+    fn default() -> VoronoiConfig {
+        VoronoiConfig::caverns()
+    }
+}
+
+pub const VORONOI_PRESETS: [&str; 3] = ["caverns", "chambers", "crystals"];
+
+impl VoronoiConfig {
+    pub fn caverns() -> VoronoiConfig {
+        VoronoiConfig {
+            seeds_count: 64,
+            distance: VoronoiDistance::Pythagoras,
+            open_cells_perc: 60,
+            stairs: StairsPlacement::Farthest,
+        }
+    }
+
+    pub fn chambers() -> VoronoiConfig {
+        VoronoiConfig {
+            seeds_count: 16,
+            distance: VoronoiDistance::Manhattan,
+            open_cells_perc: 70,
+            stairs: StairsPlacement::Farthest,
+        }
+    }
+
+    pub fn crystals() -> VoronoiConfig {
+        VoronoiConfig {
+            seeds_count: 40,
+            distance: VoronoiDistance::Chebyshev,
+            open_cells_perc: 50,
+            stairs: StairsPlacement::Farthest,
+        }
+    }
+
+    /// Config by name from `VORONOI_PRESETS`.
+    pub fn preset(name: &str) -> Option<VoronoiConfig> {
+        match name {
+            "caverns" => Some(VoronoiConfig::caverns()),
+            "chambers" => Some(VoronoiConfig::chambers()),
+            "crystals" => Some(VoronoiConfig::crystals()),
+            _ => None,
+        }
+    }
+
+    pub fn random_preset() -> VoronoiConfig {
+        let name = VORONOI_PRESETS[rng::range(0, VORONOI_PRESETS.len() as i32 - 1) as usize];
+        VoronoiConfig::preset(name).unwrap()
+    }
+}
+
+/// How many cells are carved between history steps.
+#[cfg(feature = "map_gen_testing")]
+const HISTORY_STEP_CELLS: usize = 5;
+
+/// Map is split into cells around random seeds, some of them are carved
+/// (walls are left on cells borders) and connected with corridors.
+pub struct VoronoiGen {
+    width: usize,
+    height: usize,
+    config: VoronoiConfig,
+    map: Map,
+    seeds: Vec<(usize, usize)>,
+    /// floor tiles of every carved cell
+    regions: Vec<Vec<(usize, usize)>>,
+
+    #[cfg(feature = "map_gen_testing")]
+    history: Vec<(Map, String)>,
+}
+
+impl VoronoiGen {
+    pub fn new(width: usize, height: usize, config: VoronoiConfig) -> VoronoiGen {
+        VoronoiGen {
+            width,
+            height,
+            config,
+            map: Map::new(width, height).with_all_solid(),
+            seeds: vec![],
+            regions: vec![],
+
+            #[cfg(feature = "map_gen_testing")]
+            history: vec![],
+        }
+    }
+
+    fn place_seeds(&mut self) {
+        let seeds_count = self
+            .config
+            .seeds_count
+            .clamp(2, (self.width - 4) * (self.height - 4));
+        while self.seeds.len() < seeds_count {
+            let seed = (
+                rng::range(2, self.width as i32 - 3) as usize,
+                rng::range(2, self.height as i32 - 3) as usize,
+            );
+            if !self.seeds.contains(&seed) {
+                self.seeds.push(seed);
+            }
+        }
+    }
+
+    fn nearest_seed(&self, pos: (usize, usize)) -> usize {
+        self.seeds
+            .iter()
+            .enumerate()
+            .min_by_key(|(_i, seed)| self.config.distance.distance(pos, **seed))
+            .map(|(i, _seed)| i)
+            .unwrap_or(0)
+    }
+
+    /// Cell index of every tile, `None` for map edges.
+    fn cells(&self) -> Vec<Option<usize>> {
+        (0..self.map.tiles.len())
+            .map(|i| {
+                let (x, y) = self.map.index_to_xy(i);
+                let is_edge = x == 0 || y == 0 || x == self.width - 1 || y == self.height - 1;
+                (!is_edge).then(|| self.nearest_seed((x, y)))
+            })
+            .collect()
+    }
+
+    /// Tiles of cell which don't touch other cells, so walls are left between cells.
+    fn cell_interior(&self, cells: &[Option<usize>], cell: usize) -> Vec<(usize, usize)> {
+        cells
+            .iter()
+            .enumerate()
+            .filter(|(_i, c)| **c == Some(cell))
+            .map(|(i, _c)| self.map.index_to_xy(i))
+            .filter(|&(x, y)| {
+                [(x + 1, y), (x, y + 1), (x + 1, y + 1), (x - 1, y + 1)]
+                    .iter()
+                    .all(|&(nx, ny)| {
+                        let neighbour = cells[self.map.xy_to_index(nx, ny)];
+                        neighbour.is_none() || neighbour == Some(cell)
+                    })
+            })
+            .collect()
+    }
+
+    fn carve_cells(&mut self, start_cell: usize) -> Vec<usize> {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.push((self.map.clone(), "Start".to_string()));
+
+        let cells = self.cells();
+        let mut open_cells = vec![];
+        for cell in 0..self.seeds.len() {
+            if cell != start_cell && !rng::test_perc(self.config.open_cells_perc) {
+                continue;
+            }
+            let interior = self.cell_interior(&cells, cell);
+            if interior.is_empty() {
+                continue;
+            }
+            for &(x, y) in interior.iter() {
+                let index = self.map.xy_to_index(x, y);
+                self.map.tiles[index] = TileType::Floor;
+            }
+            open_cells.push(cell);
+
+            #[cfg(feature = "map_gen_testing")]
+            if open_cells.len() % HISTORY_STEP_CELLS == 0 {
+                self.history
+                    .push((self.map.clone(), "Carving cells".to_string()));
+            }
+        }
+        open_cells
+    }
+
+    /// Every cell is connected with closest already connected cell.
+    fn connect_cells(&mut self, open_cells: &[usize]) {
+        let mut connected = vec![open_cells[0]];
+        let mut not_connected = open_cells[1..].to_vec();
+        while !not_connected.is_empty() {
+            let (i, from, to) = not_connected
+                .iter()
+                .enumerate()
+                .flat_map(|(i, cell)| connected.iter().map(move |other| (i, *cell, *other)))
+                .min_by_key(|(_i, cell, other)| {
+                    VoronoiDistance::Pythagoras.distance(self.seeds[*cell], self.seeds[*other])
+                })
+                .unwrap();
+            let (x1, y1) = self.seeds[from];
+            let (x2, y2) = self.seeds[to];
+            if rng::rand_bool() {
+                common::apply_horizontal_tunnel(&mut self.map, x1, x2, y1);
+                common::apply_vertical_tunnel(&mut self.map, y1, y2, x2);
+            } else {
+                common::apply_vertical_tunnel(&mut self.map, y1, y2, x1);
+                common::apply_horizontal_tunnel(&mut self.map, x1, x2, y2);
+            }
+            connected.push(not_connected.remove(i));
+        }
+
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Connecting cells".to_string()));
+    }
+
+    /// Cells split by ties in distance can leave pieces which aren't reachable from the rest,
+    /// only the biggest region is kept.
+    fn fill_unreachable_fragments(&mut self) {
+        self.map.update_blocked_with_blocking_tiles();
+        for region in validation::connected_regions(&self.map).into_iter().skip(1) {
+            for index in region {
+                self.map.tiles[index] = TileType::Wall;
+            }
+        }
+    }
+}
+
+impl MapGenerator for VoronoiGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        if self.width < 6 || self.height < 6 {
+            return Err(Error::IncorrectMapDimensions {
+                map_dimensions: (self.width, self.height),
+            });
+        }
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.seeds.clear();
+        self.place_seeds();
+
+        let start_cell = prev_down_stairs_pos
+            .map(|pos| self.nearest_seed((pos.x as usize, pos.y as usize)))
+            .unwrap_or(0);
+        let open_cells = self.carve_cells(start_cell);
+        if open_cells.is_empty() {
+            return Err(Error::NoWalkableTiles);
+        }
+        self.connect_cells(&open_cells);
+
+        // previous stairs can lie on wall between cells or in cell which wasn't carved
+        if let Some(pos) = prev_down_stairs_pos {
+            let (x, y) = (pos.x as usize, pos.y as usize);
+            let index = self.map.xy_to_index(x, y);
+            let inside = x >= 1 && y >= 1 && x < self.width - 1 && y < self.height - 1;
+            if inside && self.map.tiles[index].blocks_movement() {
+                let (seed_x, seed_y) = open_cells
+                    .iter()
+                    .map(|cell| self.seeds[*cell])
+                    .min_by_key(|seed| VoronoiDistance::Pythagoras.distance((x, y), *seed))
+                    .unwrap();
+                common::apply_horizontal_tunnel(&mut self.map, x, seed_x, y);
+                common::apply_vertical_tunnel(&mut self.map, y, seed_y, seed_x);
+            }
+        }
+        self.fill_unreachable_fragments();
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, self.config.stairs);
+
+        let mut regions = vec![vec![]; self.seeds.len()];
+        for pos in self.area() {
+            let cell = open_cells
+                .iter()
+                .min_by_key(|cell| self.config.distance.distance(pos, self.seeds[**cell]))
+                .unwrap();
+            regions[*cell].push(pos);
+        }
+        regions.retain(|region| !region.is_empty());
+        self.regions = regions;
+
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Finished".to_string()));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.clear();
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.seeds.clear();
+        self.regions.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    /// Carved cells, corridors belong to the closest cell.
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        self.regions.clone()
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        self.history.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{ascii::map_from_ascii, generators::assert_generates_valid_maps};
+
+    #[test]
+    fn every_preset_is_valid() {
+        for name in VORONOI_PRESETS.iter() {
+            let mut generator = VoronoiGen::new(60, 40, VoronoiConfig::preset(name).unwrap());
+            assert_generates_valid_maps(&mut generator, Some(Point::new(30, 20)), 0..3);
+            assert_generates_valid_maps(&mut generator, Some(Point::new(1, 1)), 3..4);
+            assert_generates_valid_maps(&mut generator, None, 4..5);
+        }
+        assert!(VoronoiConfig::preset("unknown").is_none());
+    }
+
+    #[test]
+    fn too_small_map_is_rejected() {
+        let mut generator = VoronoiGen::new(5, 20, VoronoiConfig::default());
+        assert!(matches!(
+            generator.generate(None),
+            Err(Error::IncorrectMapDimensions { .. })
+        ));
+    }
+
+    #[test]
+    fn only_biggest_region_is_kept() {
+        let mut generator = VoronoiGen::new(8, 4, VoronoiConfig::default());
+        generator.map = map_from_ascii("########\n#.#....#\n#.#....#\n########\n")
+            .unwrap()
+            .map;
+        generator.fill_unreachable_fragments();
+
+        assert_eq!(generator.map.tile_at_xy(1, 1), TileType::Wall);
+        assert_eq!(generator.map.tile_at_xy(1, 2), TileType::Wall);
+        assert_eq!(generator.map.tile_at_xy(3, 1), TileType::Floor);
+        assert_eq!(generator.map.tile_at_xy(6, 2), TileType::Floor);
+    }
+}
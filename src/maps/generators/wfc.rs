@@ -0,0 +1,497 @@
+//! Wave function collapse. Square chunks of tiles and their neighbours are learned
+//! from sample map (made by other generator or loaded from ascii file), then new map
+//! is filled with chunks so every two neighbouring chunks were neighbours in sample.
+
+use std::collections::HashMap;
+
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    maps::{
+        ascii,
+        errors::{Error, Result},
+        validation, Map, TileType,
+    },
+    rng,
+};
+
+use super::{
+    common,
+    stairs::{self, StairsPlacement},
+    MapGenerator,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WfcConfig {
+    /// size of square chunks learned from sample
+    pub chunk_size: usize,
+    /// collapse is started again after contradiction, at most this many times
+    pub max_tries: usize,
+}
+
+impl Default for WfcConfig {
+    fn default() -> WfcConfig {
+        WfcConfig {
+            chunk_size: 3,
+            max_tries: 10,
+        }
+    }
+}
+
+/// Hand-drawn sample in prefabs directory.
+pub const WFC_SAMPLE_PREFAB: &str = "wfc_ruins";
+
+/// Walkable areas smaller than this (except the biggest one) are filled.
+const MIN_AREA_SIZE: usize = 20;
+
+/// Floor tiles per spawn area.
+const SPAWN_AREA_SIZE: usize = 50;
+
+/// How many chunks are collapsed between history steps.
+#[cfg(feature = "map_gen_testing")]
+const HISTORY_STEP_CHUNKS: usize = 20;
+
+/// Up, down, left, right.
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// Set of chunk indexes.
+type ChunkSet = Vec<u64>;
+
+fn set_contains(set: &[u64], i: usize) -> bool {
+    set[i / 64] & (1 << (i % 64)) != 0
+}
+
+fn set_insert(set: &mut ChunkSet, i: usize) {
+    set[i / 64] |= 1 << (i % 64);
+}
+
+fn set_len(set: &[u64]) -> usize {
+    set.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+fn set_iter(set: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    (0..set.len() * 64).filter(move |i| set_contains(set, *i))
+}
+
+/// Chunks found in sample.
+struct Chunks {
+    size: usize,
+    tiles: Vec<Vec<TileType>>,
+    /// how many times chunk appears in sample
+    weights: Vec<usize>,
+    /// chunks which can be next to chunk in every direction
+    neighbours: Vec<[ChunkSet; 4]>,
+}
+
+impl Chunks {
+    /// Every chunk sized window of sample is used, neighbours are windows `size` tiles away.
+    fn learn(sample: &Map, size: usize) -> Result<Chunks> {
+        if sample.width < size * 2 || sample.height < size * 2 {
+            return Err(Error::WfcSampleTooSmall {
+                width: sample.width,
+                height: sample.height,
+                chunk_size: size,
+            });
+        }
+
+        let windows_x = sample.width - size + 1;
+        let windows_y = sample.height - size + 1;
+        let mut indexes: HashMap<String, usize> = HashMap::new();
+        let mut tiles = vec![];
+        let mut weights = vec![];
+        let mut window_chunks = vec![0; windows_x * windows_y];
+        for y in 0..windows_y {
+            for x in 0..windows_x {
+                let window = (0..size * size)
+                    .map(|i| sample.tiles[sample.xy_to_index(x + i % size, y + i / size)])
+                    .collect::<Vec<_>>();
+                let key = window
+                    .iter()
+                    .map(|tile| ascii::tile_to_char(*tile))
+                    .collect::<String>();
+                let chunk = *indexes.entry(key).or_insert_with(|| {
+                    tiles.push(window);
+                    weights.push(0);
+                    tiles.len() - 1
+                });
+                weights[chunk] += 1;
+                window_chunks[x + y * windows_x] = chunk;
+            }
+        }
+
+        let words = tiles.len().div_ceil(64);
+        let mut neighbours = vec![
+            [
+                vec![0; words],
+                vec![0; words],
+                vec![0; words],
+                vec![0; words]
+            ];
+            tiles.len()
+        ];
+        for y in 0..windows_y {
+            for x in 0..windows_x {
+                let chunk = window_chunks[x + y * windows_x];
+                for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                    let nx = x as i32 + dx * size as i32;
+                    let ny = y as i32 + dy * size as i32;
+                    if nx < 0 || ny < 0 || nx >= windows_x as i32 || ny >= windows_y as i32 {
+                        continue;
+                    }
+                    let neighbour = window_chunks[nx as usize + ny as usize * windows_x];
+                    set_insert(&mut neighbours[chunk][direction], neighbour);
+                }
+            }
+        }
+
+        Ok(Chunks {
+            size,
+            tiles,
+            weights,
+            neighbours,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.tiles.len()
+    }
+}
+
+pub struct WfcGen {
+    width: usize,
+    height: usize,
+    config: WfcConfig,
+    sample_gen: Box<dyn MapGenerator>,
+    map: Map,
+    regions: Vec<Vec<(usize, usize)>>,
+
+    #[cfg(feature = "map_gen_testing")]
+    history: Vec<(Map, String)>,
+}
+
+impl WfcGen {
+    /// Map generated by `sample_gen` is used as sample.
+    pub fn new(
+        width: usize,
+        height: usize,
+        sample_gen: Box<dyn MapGenerator>,
+        config: WfcConfig,
+    ) -> WfcGen {
+        WfcGen {
+            width,
+            height,
+            config,
+            sample_gen,
+            map: Map::new(width, height).with_all_solid(),
+            regions: vec![],
+
+            #[cfg(feature = "map_gen_testing")]
+            history: vec![],
+        }
+    }
+
+    /// Stairs and debug tiles are not learned.
+    fn sample(&mut self) -> Result<Map> {
+        self.sample_gen.reset();
+        self.sample_gen.generate(None)?;
+        let mut sample = self.sample_gen.map();
+        for tile in sample.tiles.iter_mut() {
+            *tile = match *tile {
+                TileType::StairsDown | TileType::StairsUp => TileType::Floor,
+                TileType::TestWall => TileType::Wall,
+                tile => tile,
+            };
+        }
+        Ok(sample)
+    }
+
+    fn draw_chunk(&mut self, chunks: &Chunks, cell: usize, cells_x: usize, chunk: usize) {
+        let size = chunks.size;
+        let (x, y) = (1 + (cell % cells_x) * size, 1 + (cell / cells_x) * size);
+        for (i, tile) in chunks.tiles[chunk].iter().enumerate() {
+            let index = self.map.xy_to_index(x + i % size, y + i / size);
+            self.map.tiles[index] = *tile;
+        }
+    }
+
+    /// Draws chunk for every cell, false if collapse ended with contradiction.
+    fn collapse(&mut self, chunks: &Chunks, cells_x: usize, cells_y: usize) -> bool {
+        let mut all_chunks = vec![0; chunks.len().div_ceil(64)];
+        for chunk in 0..chunks.len() {
+            set_insert(&mut all_chunks, chunk);
+        }
+        let mut wave = vec![all_chunks; cells_x * cells_y];
+        let mut collapsed = vec![false; cells_x * cells_y];
+
+        #[cfg(feature = "map_gen_testing")]
+        let mut collapsed_count = 0;
+
+        loop {
+            // cell with the least possible chunks is collapsed first
+            let options = (0..wave.len())
+                .filter(|cell| !collapsed[*cell])
+                .map(|cell| (cell, set_len(&wave[cell])))
+                .collect::<Vec<_>>();
+            let min_options = match options.iter().map(|(_cell, count)| *count).min() {
+                Some(min_options) => min_options,
+                None => return true,
+            };
+            if min_options == 0 {
+                return false;
+            }
+            let candidates = options
+                .iter()
+                .filter(|(_cell, count)| *count == min_options)
+                .map(|(cell, _count)| *cell)
+                .collect::<Vec<_>>();
+            let cell = candidates[rng::range(0, candidates.len() as i32 - 1) as usize];
+
+            let total_weight: usize = set_iter(&wave[cell]).map(|c| chunks.weights[c]).sum();
+            let mut roll = rng::range(1, total_weight as i32) as usize;
+            let chunk = set_iter(&wave[cell])
+                .find(|c| {
+                    if roll <= chunks.weights[*c] {
+                        true
+                    } else {
+                        roll -= chunks.weights[*c];
+                        false
+                    }
+                })
+                .unwrap();
+
+            wave[cell] = vec![0; wave[cell].len()];
+            set_insert(&mut wave[cell], chunk);
+            collapsed[cell] = true;
+            self.draw_chunk(chunks, cell, cells_x, chunk);
+            if !Self::propagate(chunks, &mut wave, cell, cells_x, cells_y) {
+                return false;
+            }
+
+            #[cfg(feature = "map_gen_testing")]
+            {
+                collapsed_count += 1;
+                if collapsed_count % HISTORY_STEP_CHUNKS == 0 {
+                    self.history
+                        .push((self.map.clone(), "Collapsing".to_string()));
+                }
+            }
+        }
+    }
+
+    /// Removes chunks which can't be next to neighbours, false on contradiction.
+    fn propagate(
+        chunks: &Chunks,
+        wave: &mut [ChunkSet],
+        cell: usize,
+        cells_x: usize,
+        cells_y: usize,
+    ) -> bool {
+        let mut stack = vec![cell];
+        while let Some(cell) = stack.pop() {
+            let (x, y) = ((cell % cells_x) as i32, (cell / cells_x) as i32);
+            for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= cells_x as i32 || ny >= cells_y as i32 {
+                    continue;
+                }
+                let neighbour = nx as usize + ny as usize * cells_x;
+
+                let mut allowed = vec![0; wave[cell].len()];
+                for chunk in set_iter(&wave[cell]) {
+                    for (word, neighbour_word) in allowed
+                        .iter_mut()
+                        .zip(chunks.neighbours[chunk][direction].iter())
+                    {
+                        *word |= neighbour_word;
+                    }
+                }
+                let reduced = wave[neighbour]
+                    .iter()
+                    .zip(allowed.iter())
+                    .map(|(word, allowed_word)| word & allowed_word)
+                    .collect::<Vec<_>>();
+                if reduced != wave[neighbour] {
+                    if set_len(&reduced) == 0 {
+                        return false;
+                    }
+                    wave[neighbour] = reduced;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        true
+    }
+
+    /// Small pockets are filled, bigger areas are connected later by map repair.
+    fn fill_small_areas(&mut self) {
+        self.map.update_blocked_with_blocking_tiles();
+        let regions = validation::connected_regions(&self.map);
+        for region in regions.iter().skip(1).filter(|r| r.len() < MIN_AREA_SIZE) {
+            for index in region.iter() {
+                self.map.tiles[*index] = TileType::Wall;
+            }
+        }
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Filling small areas".to_string()));
+    }
+}
+
+impl MapGenerator for WfcGen {
+    fn generate(&mut self, prev_down_stairs_pos: Option<Point>) -> Result<()> {
+        let chunk_size = self.config.chunk_size.max(1);
+        let cells_x = self.width.saturating_sub(2) / chunk_size;
+        let cells_y = self.height.saturating_sub(2) / chunk_size;
+        if cells_x == 0 || cells_y == 0 {
+            return Err(Error::IncorrectMapDimensions {
+                map_dimensions: (self.width, self.height),
+            });
+        }
+        let sample = self.sample()?;
+        let chunks = Chunks::learn(&sample, chunk_size)?;
+
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            self.map = Map::new(self.width, self.height).with_all_solid();
+            #[cfg(feature = "map_gen_testing")]
+            self.history.push((self.map.clone(), "Start".to_string()));
+
+            if self.collapse(&chunks, cells_x, cells_y) {
+                break;
+            }
+            #[cfg(feature = "map_gen_testing")]
+            self.history
+                .push((self.map.clone(), "Contradiction".to_string()));
+            if tries >= self.config.max_tries {
+                return Err(Error::WfcContradiction { tries });
+            }
+        }
+        self.map = self.map.clone().with_edges_solid();
+
+        self.fill_small_areas();
+        if self.area().is_empty() {
+            return Err(Error::NoWalkableTiles);
+        }
+        stairs::place_stairs(&mut self.map, prev_down_stairs_pos, StairsPlacement::Random);
+        self.regions = common::split_area(&self.area(), SPAWN_AREA_SIZE);
+
+        #[cfg(feature = "map_gen_testing")]
+        self.history
+            .push((self.map.clone(), "Finished".to_string()));
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        #[cfg(feature = "map_gen_testing")]
+        self.history.clear();
+        self.map = Map::new(self.width, self.height).with_all_solid();
+        self.regions.clear();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        self.regions.clone()
+    }
+
+    #[cfg(feature = "map_gen_testing")]
+    fn history(&self) -> Vec<(Map, String)> {
+        self.history.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::generators::{
+        bsp::{interior::BSPInteriorGen, BSPConfig},
+        generate_valid_map,
+        prefab::PrefabGen,
+    };
+
+    /// Always gives the same sample map.
+    struct SampleGen {
+        map: Map,
+    }
+
+    impl SampleGen {
+        fn boxed(text: &str) -> Box<dyn MapGenerator> {
+            let map = ascii::map_from_ascii(text).unwrap().map;
+            Box::new(SampleGen { map })
+        }
+    }
+
+    impl MapGenerator for SampleGen {
+        fn generate(&mut self, _prev_down_stairs_pos: Option<Point>) -> Result<()> {
+            Ok(())
+        }
+
+        fn reset(&mut self) {}
+
+        fn map(&self) -> Map {
+            self.map.clone()
+        }
+
+        fn spawn_areas(&self) -> Vec<Vec<(usize, usize)>> {
+            vec![]
+        }
+
+        #[cfg(feature = "map_gen_testing")]
+        fn history(&self) -> Vec<(Map, String)> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn generated_maps_are_valid() {
+        let samples: Vec<Box<dyn Fn() -> Box<dyn MapGenerator>>> = vec![
+            Box::new(|| Box::new(BSPInteriorGen::new(60, 40, BSPConfig::default()))),
+            Box::new(|| Box::new(PrefabGen::new(WFC_SAMPLE_PREFAB, 0, 0))),
+        ];
+        let stairs = Some(Point::new(30, 20));
+        for sample in samples.iter() {
+            for seed in 0..3 {
+                rng::reseed(seed);
+                let mut generator = WfcGen::new(60, 40, sample(), WfcConfig::default());
+                let generated = generate_valid_map(&mut generator, stairs).unwrap();
+
+                let mut map = generated.map;
+                map.update_blocked_with_blocking_tiles();
+                validation::validate_map(&map, &generated.spawn_areas, stairs).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn contradiction_is_reported_after_last_try() {
+        // floor is always on the right of wall and has no right neighbour,
+        // so no row of three chunks can be made
+        let sample = SampleGen::boxed("#.\n#.\n");
+        let config = WfcConfig {
+            chunk_size: 1,
+            max_tries: 3,
+        };
+        let mut generator = WfcGen::new(20, 20, sample, config);
+
+        assert!(matches!(
+            generator.generate(None),
+            Err(Error::WfcContradiction { tries: 3 })
+        ));
+    }
+
+    #[test]
+    fn too_small_sample_is_rejected() {
+        let sample = SampleGen::boxed("#.#\n...\n#.#\n");
+        let mut generator = WfcGen::new(40, 30, sample, WfcConfig::default());
+
+        assert!(matches!(
+            generator.generate(None),
+            Err(Error::WfcSampleTooSmall { chunk_size: 3, .. })
+        ));
+    }
+}
@@ -12,17 +12,91 @@ pub enum TileType {
 
     StairsDown,
     StairsUp,
+
+    Door(DoorState),
+
+    ShallowWater,
+    /// can't be entered, only crossed by bridge
+    DeepWater,
+    Lava,
+    /// entering it drops entity to next level
+    Chasm,
+    Rubble,
+    Grass,
+    Tree,
+    Bridge,
+    /// drops entity two levels down (see `levels::level::LevelExit`)
+    Trapdoor,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    Closed,
+    /// can't be opened by player nor monsters
+    Locked,
+}
+
+/// Extra pathing cost of going through closed door (opening it takes a turn).
+pub const DOOR_OPENING_COST: f32 = 2.0;
+
+/// Damage taken by entity stepping into lava.
+pub const LAVA_DAMAGE: i32 = 10;
+
+/// What happens to entity, which enters tile.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TerrainEffect {
+    Damage(i32),
+    /// entity falls to level below (chasm) or to destination of trapdoor
+    Fall,
 }
 
 impl TileType {
     pub fn blocks_visibility(self) -> bool {
-        self == TileType::Wall || self == TileType::Rock
+        self.is_solid() || self.is_closed_door() || self == TileType::Tree
     }
 
     pub fn blocks_movement(self) -> bool {
+        self.is_solid() || self.is_closed_door() || self == TileType::DeepWater
+    }
+
+    /// Walls and rock, which can't be passed in any way. Closed doors aren't solid.
+    pub fn is_solid(self) -> bool {
         self == TileType::Wall || self == TileType::Rock
     }
 
+    pub fn is_door(self) -> bool {
+        matches!(self, TileType::Door(_))
+    }
+
+    /// Closed or locked door.
+    pub fn is_closed_door(self) -> bool {
+        self == TileType::Door(DoorState::Closed) || self == TileType::Door(DoorState::Locked)
+    }
+
+    /// Walkable tiles with harmful on-enter effect.
+    pub fn is_hazard(self) -> bool {
+        self.on_enter_effect().is_some()
+    }
+
+    /// Pathing cost of entering tile (floor is 1), hazards are expensive so AI avoids them.
+    pub fn movement_cost(self) -> f32 {
+        match self {
+            TileType::ShallowWater | TileType::Rubble | TileType::Tree => 2.0,
+            TileType::Lava => 100.0,
+            TileType::Door(DoorState::Closed) => 1.0 + DOOR_OPENING_COST,
+            _ => 1.0,
+        }
+    }
+
+    pub fn on_enter_effect(self) -> Option<TerrainEffect> {
+        match self {
+            TileType::Lava => Some(TerrainEffect::Damage(LAVA_DAMAGE)),
+            TileType::Chasm | TileType::Trapdoor => Some(TerrainEffect::Fall),
+            _ => None,
+        }
+    }
+
     pub fn draw(self) -> (FontCharType, RGB) {
         match self {
             TileType::Floor => (rltk::to_cp437('.'), RGB::named(rltk::GREEN)),
@@ -31,6 +105,18 @@ impl TileType {
             TileType::TestWall => (rltk::to_cp437('#'), RGB::named(rltk::RED)),
             TileType::StairsDown => (rltk::to_cp437('>'), RGB::named(rltk::PINK2)),
             TileType::StairsUp => (rltk::to_cp437('<'), RGB::named(rltk::PINK2)),
+            TileType::Door(DoorState::Open) => (rltk::to_cp437('\''), RGB::named(rltk::BROWN1)),
+            TileType::Door(DoorState::Closed) => (rltk::to_cp437('+'), RGB::named(rltk::BROWN1)),
+            TileType::Door(DoorState::Locked) => (rltk::to_cp437('+'), RGB::named(rltk::GOLD)),
+            TileType::ShallowWater => (rltk::to_cp437('~'), RGB::named(rltk::CYAN)),
+            TileType::DeepWater => (rltk::to_cp437('≈'), RGB::named(rltk::BLUE)),
+            TileType::Lava => (rltk::to_cp437('≈'), RGB::named(rltk::ORANGE_RED)),
+            TileType::Chasm => (rltk::to_cp437('░'), RGB::named(rltk::DIM_GRAY)),
+            TileType::Rubble => (rltk::to_cp437(';'), RGB::named(rltk::GRAY)),
+            TileType::Grass => (rltk::to_cp437('"'), RGB::named(rltk::LIME_GREEN)),
+            TileType::Tree => (rltk::to_cp437('♣'), RGB::named(rltk::FOREST_GREEN)),
+            TileType::Bridge => (rltk::to_cp437('='), RGB::named(rltk::BROWN1)),
+            TileType::Trapdoor => (rltk::to_cp437('▼'), RGB::named(rltk::BROWN1)),
         }
     }
 
@@ -42,6 +128,18 @@ impl TileType {
             TileType::TestWall => Some(16),
             TileType::StairsDown => Some(16),
             TileType::StairsUp => Some(17),
+            TileType::Door(DoorState::Closed) => Some(20),
+            TileType::Door(DoorState::Open) => Some(21),
+            TileType::Door(DoorState::Locked) => Some(22),
+            TileType::ShallowWater => Some(23),
+            TileType::DeepWater => Some(24),
+            TileType::Lava => Some(25),
+            TileType::Chasm => Some(26),
+            TileType::Rubble => Some(27),
+            TileType::Grass => Some(28),
+            TileType::Tree => Some(29),
+            TileType::Bridge => Some(30),
+            TileType::Trapdoor => Some(31),
         }
     }
 }
@@ -80,9 +178,10 @@ impl Map {
         self
     }
 
+    /// Doors don't block, so map stays connected through them. Pathing handles them separately.
     pub fn update_blocked_with_blocking_tiles(&mut self) {
         for (i, tile) in self.tiles.iter().enumerate() {
-            self.blocked[i] = tile.blocks_movement();
+            self.blocked[i] = tile.blocks_movement() && !tile.is_door();
         }
     }
 
@@ -160,11 +259,48 @@ impl Map {
         (x, y)
     }
 
-    fn is_exit_valid(&self, x: usize, y: usize) -> bool {
+    /// Cost of entering tile (see `TileType::movement_cost`), None if it can't be entered.
+    /// Nobody jumps into chasm on purpose.
+    fn exit_cost(&self, x: usize, y: usize, can_open_doors: bool) -> Option<f32> {
         if x < 1 || x > self.width_max() || y < 1 || y > self.height_max() {
-            return false;
+            return None;
+        }
+        let index = self.xy_to_index(x, y);
+        match self.tiles[index] {
+            _ if self.blocked[index] => None,
+            TileType::Door(DoorState::Locked) | TileType::Chasm | TileType::Trapdoor => None,
+            TileType::Door(DoorState::Closed) if !can_open_doors => None,
+            tile => Some(tile.movement_cost()),
+        }
+    }
+
+    fn available_exits(
+        &self,
+        index: usize,
+        can_open_doors: bool,
+    ) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let mut exits = rltk::SmallVec::new();
+        let x = index % self.width;
+        let y = index / self.width;
+        let w = self.width;
+
+        let neighbours = [
+            (x - 1, y, index - 1, 1.0),
+            (x + 1, y, index + 1, 1.0),
+            (x, y - 1, index - w, 1.0),
+            (x, y + 1, index + w, 1.0),
+            (x - 1, y - 1, (index - 1) - w, 1.45),
+            (x + 1, y - 1, (index + 1) - w, 1.45),
+            (x - 1, y + 1, (index + w) - 1, 1.45),
+            (x + 1, y + 1, (index + w) + 1, 1.45),
+        ];
+        for &(nx, ny, neighbour, distance) in neighbours.iter() {
+            if let Some(cost) = self.exit_cost(nx, ny, can_open_doors) {
+                exits.push((neighbour, distance * cost));
+            }
         }
-        !self.blocked[self.xy_to_index(x, y)]
+
+        exits
     }
 
     pub fn clear_tiles_contents(&mut self) {
@@ -173,6 +309,20 @@ impl Map {
         }
     }
 
+    /// Closest tile (by straight distance), where entity can stand without harm.
+    pub fn closest_safe_position(&self, pos: (usize, usize)) -> Option<(usize, usize)> {
+        (0..self.tiles.len())
+            .filter(|i| {
+                let tile = self.tiles[*i];
+                !tile.blocks_movement() && !tile.is_door() && !tile.is_hazard()
+            })
+            .map(|i| self.index_to_xy(i))
+            .min_by_key(|&(x, y)| {
+                let (dx, dy) = (x as i64 - pos.0 as i64, y as i64 - pos.1 as i64);
+                dx * dx + dy * dy
+            })
+    }
+
     /// TODO check if pos in map
     pub fn closest_not_blocked_positions(
         &self,
@@ -195,6 +345,8 @@ impl Map {
                     }
                     let index = self.xy_to_index(x, y);
                     if !self.blocked[index]
+                        && !self.tiles[index].is_door()
+                        && !self.tiles[index].is_hazard()
                         && self.tile_content[index].is_empty()
                         && !positions.contains(&(x, y))
                         && !excluded_positions.contains(&(x, y))
@@ -222,44 +374,38 @@ impl BaseMap for Map {
         rltk::DistanceAlg::Pythagoras.distance2d(p1, p2)
     }
 
+    /// Closed doors aren't passable, see `DoorOpenerMap`.
     fn get_available_exits(&self, index: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
-        let mut exits = rltk::SmallVec::new();
-        let x = index % self.width;
-        let y = index / self.width;
-        let w = self.width;
+        self.available_exits(index, false)
+    }
+}
 
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((index - 1, 1.0));
-        }
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((index + 1, 1.0));
-        }
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((index - w, 1.0));
-        }
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((index + w, 1.0));
-        }
+impl Algorithm2D for Map {
+    fn dimensions(&self) -> rltk::Point {
+        rltk::Point::new(self.width, self.height)
+    }
+}
 
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((index - 1) - w, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((index + 1) - w, 1.45));
-        }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((index + w) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((index + w) + 1, 1.45));
-        }
+/// Map used for pathing of entities which can open doors, closed doors are passable
+/// at extra cost.
+pub struct DoorOpenerMap<'a>(pub &'a Map);
 
-        exits
+impl BaseMap for DoorOpenerMap<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.0.is_opaque(idx)
+    }
+
+    fn get_pathing_distance(&self, index1: usize, index2: usize) -> f32 {
+        self.0.get_pathing_distance(index1, index2)
+    }
+
+    fn get_available_exits(&self, index: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.0.available_exits(index, true)
     }
 }
 
-impl Algorithm2D for Map {
+impl Algorithm2D for DoorOpenerMap<'_> {
     fn dimensions(&self) -> rltk::Point {
-        rltk::Point::new(self.width, self.height)
+        self.0.dimensions()
     }
 }
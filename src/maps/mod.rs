@@ -1,8 +1,11 @@
+pub mod ascii;
 pub mod errors;
 pub mod generators;
 pub mod map;
 pub mod rect;
+pub mod validation;
+pub mod vaults;
 
 pub use errors::Error;
 
-pub use self::map::{Map, TileType};
+pub use self::map::{DoorOpenerMap, DoorState, Map, TerrainEffect, TileType};
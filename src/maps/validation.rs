@@ -0,0 +1,304 @@
+//! Checks if generated map is playable: all walkable tiles are reachable from each other
+//! (moving in 8 directions, the same as player and pathfinding), there are stairs down
+//! (and up, if level is entered from previous one) and spawn areas lie on walkable tiles.
+//! `Map::blocked` is used, so it has to be updated first. Open and closed doors connect
+//! regions, locked ones can't be opened and split map like walls. Hazards (lava, chasm)
+//! aren't blocked, terrain features pass keeps them from splitting map
+//! (see `generators::terrain`).
+
+use std::collections::VecDeque;
+
+use rltk::Point;
+use snafu::{ensure, OptionExt};
+
+use super::errors::{
+    MapNotConnected, NoStairsDown, NoStairsUp, NoWalkableTiles, Result, SpawnAreaNotWalkable,
+    StairsUpOutsideMap,
+};
+use super::{DoorState, Map, TileType};
+
+/// Walkable tiles (indexes) split into groups reachable from each other, biggest first.
+pub fn connected_regions(map: &Map) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; map.tiles.len()];
+    let mut regions = vec![];
+
+    for start in 0..map.tiles.len() {
+        if !is_passable(map, start) || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut region = vec![start];
+        let mut i = 0;
+        while i < region.len() {
+            for neighbour in neighbours(map, region[i]) {
+                if is_passable(map, neighbour) && !visited[neighbour] {
+                    visited[neighbour] = true;
+                    region.push(neighbour);
+                }
+            }
+            i += 1;
+        }
+        regions.push(region);
+    }
+
+    regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+    regions
+}
+
+/// Locked doors aren't blocked (they are drawn and handled like other doors),
+/// but nobody can go through them.
+fn is_passable(map: &Map, index: usize) -> bool {
+    !map.blocked[index] && map.tiles[index] != TileType::Door(DoorState::Locked)
+}
+
+fn neighbours(map: &Map, index: usize) -> Vec<usize> {
+    let (x, y) = map.index_to_xy(index);
+    let mut neighbours = Vec::with_capacity(8);
+    for dy in -1..=1_i32 {
+        for dx in -1..=1_i32 {
+            let nx = x as i32 + dx;
+            let ny = y as i32 + dy;
+            if (dx != 0 || dy != 0)
+                && nx >= 0
+                && ny >= 0
+                && nx < map.width as i32
+                && ny < map.height as i32
+            {
+                neighbours.push(map.xy_to_index(nx as usize, ny as usize));
+            }
+        }
+    }
+    neighbours
+}
+
+/// Number of steps needed to reach each tile from `start`, None for unreachable tiles.
+pub fn distances_from(map: &Map, start: usize) -> Vec<Option<usize>> {
+    let mut distances = vec![None; map.tiles.len()];
+    distances[start] = Some(0);
+    let mut queue = VecDeque::from(vec![start]);
+    while let Some(index) = queue.pop_front() {
+        let distance = distances[index].unwrap_or(0);
+        for neighbour in neighbours(map, index) {
+            if is_passable(map, neighbour) && distances[neighbour].is_none() {
+                distances[neighbour] = Some(distance + 1);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    distances
+}
+
+/// Returns first problem found in map.
+pub fn validate_map(
+    map: &Map,
+    spawn_areas: &[Vec<(usize, usize)>],
+    prev_down_stairs_pos: Option<Point>,
+) -> Result<()> {
+    let regions = connected_regions(map);
+    ensure!(!regions.is_empty(), NoWalkableTiles);
+    ensure!(
+        regions.len() == 1,
+        MapNotConnected {
+            regions: regions.len()
+        }
+    );
+
+    ensure!(map.tiles.contains(&TileType::StairsDown), NoStairsDown);
+    if let Some(pos) = prev_down_stairs_pos {
+        let index = stairs_up_index(map, pos)?;
+        ensure!(
+            map.tiles[index] == TileType::StairsUp,
+            NoStairsUp { x: pos.x, y: pos.y }
+        );
+    }
+
+    for &(x, y) in spawn_areas.iter().flatten() {
+        ensure!(
+            x < map.width
+                && y < map.height
+                && !map.blocked[map.xy_to_index(x, y)]
+                && !map.tile_at_xy(x, y).is_hazard(),
+            SpawnAreaNotWalkable { x, y }
+        );
+    }
+    Ok(())
+}
+
+/// Connects all regions by tunnels, places missing up stairs and removes spawn positions
+/// on blocked tiles. Error is returned if map can't be repaired (it should be generated again).
+pub fn repair_map(
+    map: &mut Map,
+    spawn_areas: &mut Vec<Vec<(usize, usize)>>,
+    prev_down_stairs_pos: Option<Point>,
+) -> Result<()> {
+    map.update_blocked_with_blocking_tiles();
+
+    // player arrives from previous level at position of its stairs down
+    if let Some(pos) = prev_down_stairs_pos {
+        let index = stairs_up_index(map, pos)?;
+        if map.tiles[index] != TileType::StairsUp {
+            map.tiles[index] = TileType::StairsUp;
+            map.blocked[index] = false;
+        }
+    }
+
+    let mut regions = connected_regions(map);
+    ensure!(!regions.is_empty(), NoWalkableTiles);
+    while regions.len() > 1 {
+        // smallest region is connected to the biggest one
+        let tunnel =
+            tunnel_path(map, &regions[0], regions.last().unwrap()).context(MapNotConnected {
+                regions: regions.len(),
+            })?;
+        dig_tunnel(map, &tunnel);
+        regions = connected_regions(map);
+    }
+
+    for area in spawn_areas.iter_mut() {
+        area.retain(|&(x, y)| {
+            x < map.width
+                && y < map.height
+                && !map.blocked[map.xy_to_index(x, y)]
+                && !map.tile_at_xy(x, y).is_door()
+                && !map.tile_at_xy(x, y).is_hazard()
+        });
+    }
+    spawn_areas.retain(|area| !area.is_empty());
+
+    validate_map(map, spawn_areas, prev_down_stairs_pos)
+}
+
+fn stairs_up_index(map: &Map, pos: Point) -> Result<usize> {
+    let inside_map = pos.x > 0
+        && pos.y > 0
+        && (pos.x as usize) < map.width_max()
+        && (pos.y as usize) < map.height_max();
+    inside_map
+        .then(|| map.xy_to_index(pos.x as usize, pos.y as usize))
+        .context(StairsUpOutsideMap { x: pos.x, y: pos.y })
+}
+
+/// Shortest path (moving in 4 directions) from any tile of `region_a` to the nearest tile
+/// of `region_b`, ending on that tile. Path goes around locked doors and doesn't dig
+/// through map edges. None if regions can't be connected.
+fn tunnel_path(map: &Map, region_a: &[usize], region_b: &[usize]) -> Option<Vec<usize>> {
+    let mut in_b = vec![false; map.tiles.len()];
+    for &index in region_b.iter() {
+        in_b[index] = true;
+    }
+    let mut came_from = vec![None; map.tiles.len()];
+    let mut visited = vec![false; map.tiles.len()];
+    for &index in region_a.iter() {
+        visited[index] = true;
+    }
+    let mut queue = region_a.iter().copied().collect::<VecDeque<_>>();
+
+    while let Some(index) = queue.pop_front() {
+        let (x, y) = map.index_to_xy(index);
+        let (x, y) = (x as i32, y as i32);
+        for &(nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter() {
+            if nx < 0 || ny < 0 || nx >= map.width as i32 || ny >= map.height as i32 {
+                continue;
+            }
+            let neighbour = map.xy_to_index(nx as usize, ny as usize);
+            if visited[neighbour] {
+                continue;
+            }
+            visited[neighbour] = true;
+            came_from[neighbour] = Some(index);
+
+            if in_b[neighbour] {
+                let mut path = vec![neighbour];
+                while let Some(previous) = came_from[*path.last().unwrap()] {
+                    path.push(previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let on_edge = nx == 0
+                || ny == 0
+                || nx as usize == map.width_max()
+                || ny as usize == map.height_max();
+            if !on_edge && map.tiles[neighbour] != TileType::Door(DoorState::Locked) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    None
+}
+
+/// Only solid tiles are changed (stairs and doors stay in place), deep water is crossed
+/// by bridge.
+fn dig_tunnel(map: &mut Map, path: &[usize]) {
+    for &index in path.iter() {
+        if map.tiles[index].is_solid() {
+            map.tiles[index] = TileType::Floor;
+            map.blocked[index] = false;
+        } else if map.tiles[index] == TileType::DeepWater {
+            map.tiles[index] = TileType::Bridge;
+            map.blocked[index] = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::ascii::map_from_ascii;
+
+    #[test]
+    fn repair_connects_disjoint_rooms() {
+        let mut map = map_from_ascii(
+            "##########\n\
+             #...######\n\
+             #.>.######\n\
+             #...###..#\n\
+             #######..#\n\
+             ##########\n",
+        )
+        .unwrap()
+        .map;
+        let mut spawn_areas = vec![vec![(7, 4)]];
+        assert_eq!(connected_regions(&map).len(), 2);
+
+        repair_map(&mut map, &mut spawn_areas, Some(Point::new(1, 1))).unwrap();
+
+        assert_eq!(connected_regions(&map).len(), 1);
+        assert_eq!(map.tile_at_xy(1, 1), TileType::StairsUp);
+        assert_eq!(spawn_areas, vec![vec![(7, 4)]]);
+    }
+
+    #[test]
+    fn locked_door_doesnt_connect_regions() {
+        let mut map = map_from_ascii(
+            "#########\n\
+             #...#...#\n\
+             #.>.=...#\n\
+             #...#...#\n\
+             #########\n",
+        )
+        .unwrap()
+        .map;
+        assert_eq!(connected_regions(&map).len(), 2);
+        assert!(validate_map(&map, &[], None).is_err());
+
+        repair_map(&mut map, &mut vec![], None).unwrap();
+
+        assert_eq!(connected_regions(&map).len(), 1);
+        assert_eq!(map.tile_at_xy(4, 2), TileType::Door(DoorState::Locked));
+    }
+
+    #[test]
+    fn regions_split_by_map_edge_cant_be_repaired() {
+        let mut map = map_from_ascii(
+            ".####\n\
+             ##>.#\n\
+             #####\n",
+        )
+        .unwrap()
+        .map;
+
+        assert!(repair_map(&mut map, &mut vec![], None).is_err());
+    }
+}
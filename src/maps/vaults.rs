@@ -0,0 +1,405 @@
+//! Small hand-made rooms (vaults, shrines, treasure rooms) placed by generators into
+//! unused solid parts of their maps. Vault is drawn the same way as ascii map
+//! (see `maps::ascii`), `+` (closed door) marks connection which is always tunnelled to rest
+//! of map. Entities from legend are spawned instead of spawn table entries.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::sync::Mutex;
+
+use rltk::Point;
+use serde::Deserialize;
+use snafu::{ensure, ResultExt};
+
+use crate::rng;
+
+use super::ascii::{self, MapEntity};
+use super::errors::{
+    Error, Result, VaultConnectionNotOnEdge, VaultWithoutConnections, VaultsFileIo, VaultsFileSerde,
+};
+use super::{Map, TileType};
+
+pub const VAULTS_PATH: &str = "./resources/vaults.json";
+
+/// How many vaults generator tries to place in one level.
+const MAX_VAULTS_PER_LEVEL: usize = 2;
+
+/// How many random positions are checked for every vault.
+const PLACEMENT_TRIES: usize = 200;
+
+const CONNECTION_CHAR: char = '+';
+
+lazy_static! {
+    static ref VAULTS: Mutex<Vec<Vault>> = Mutex::new(vec![]);
+}
+
+/// Format of vaults file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VaultsFile {
+    vaults: Vec<VaultTemplate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VaultTemplate {
+    name: String,
+    rows: Vec<String>,
+    #[serde(default)]
+    legend: BTreeMap<String, String>,
+    #[serde(default)]
+    min_depth: usize,
+    max_depth: Option<usize>,
+    #[serde(default = "default_chance_perc")]
+    chance_perc: usize,
+}
+
+fn default_chance_perc() -> usize {
+    100
+}
+
+#[derive(Debug, Clone)]
+pub struct Vault {
+    pub name: String,
+    /// footprint of vault, connections are closed doors
+    pub map: Map,
+    pub entities: Vec<MapEntity>,
+    /// tiles on edge of vault which have to be connected to rest of map
+    pub connections: Vec<(usize, usize)>,
+    pub min_depth: usize,
+    pub max_depth: Option<usize>,
+    /// chance of being placed in level of allowed depth
+    pub chance_perc: usize,
+}
+
+impl Vault {
+    fn from_template(template: VaultTemplate) -> Result<Vault> {
+        let name = template.name;
+        let mut connections = vec![];
+        for (y, row) in template.rows.iter().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                if ch == CONNECTION_CHAR {
+                    connections.push((x, y));
+                }
+            }
+        }
+        let legend = template
+            .legend
+            .iter()
+            .map(|(ch, entity_name)| format!("{} = {}", ch, entity_name))
+            .collect::<Vec<_>>();
+        let text = format!("{}\n\n{}", template.rows.join("\n"), legend.join("\n"));
+        let ascii_map = ascii::map_from_ascii(&text).map_err(|e| Error::InvalidVault {
+            name: name.clone(),
+            source: Box::new(e),
+        })?;
+
+        ensure!(!connections.is_empty(), VaultWithoutConnections { name });
+        let (width, height) = (ascii_map.map.width, ascii_map.map.height);
+        for &(x, y) in connections.iter() {
+            ensure!(
+                x == 0 || y == 0 || x == width - 1 || y == height - 1,
+                VaultConnectionNotOnEdge { name: &name, x, y }
+            );
+        }
+
+        Ok(Vault {
+            name,
+            map: ascii_map.map,
+            entities: ascii_map.entities,
+            connections,
+            min_depth: template.min_depth,
+            max_depth: template.max_depth,
+            chance_perc: template.chance_perc,
+        })
+    }
+
+    pub fn allowed_at_depth(&self, depth: usize) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max| depth <= max)
+    }
+}
+
+/// Vault stamped into map.
+#[derive(Debug, Clone)]
+pub struct PlacedVault {
+    pub name: String,
+    /// all tiles of vault footprint
+    pub area: Vec<(usize, usize)>,
+    pub entities: Vec<MapEntity>,
+}
+
+/// Loads vaults from `path` and replaces currently used ones.
+pub fn load_vaults(path: &str) -> Result<()> {
+    let contents = fs::read_to_string(path).context(VaultsFileIo { path })?;
+    let file: VaultsFile = serde_json::from_str(&contents).context(VaultsFileSerde { path })?;
+    let vaults = file
+        .vaults
+        .into_iter()
+        .map(Vault::from_template)
+        .collect::<Result<Vec<_>>>()?;
+    *VAULTS.lock().unwrap() = vaults;
+    Ok(())
+}
+
+/// Rolls vaults which generator should try to place in level of given depth.
+pub fn roll_vaults_for_depth(depth: usize) -> Vec<Vault> {
+    roll_vaults(&VAULTS.lock().unwrap(), depth)
+}
+
+/// Vaults are rolled in random order, so the ones at the end of file get their chance too.
+fn roll_vaults(vaults: &[Vault], depth: usize) -> Vec<Vault> {
+    let mut allowed: Vec<&Vault> = vaults
+        .iter()
+        .filter(|vault| vault.allowed_at_depth(depth))
+        .collect();
+    rng::shuffle(&mut allowed);
+    allowed
+        .into_iter()
+        .filter(|vault| rng::test_perc(vault.chance_perc))
+        .take(MAX_VAULTS_PER_LEVEL)
+        .cloned()
+        .collect()
+}
+
+/// Stamps vaults into solid parts of map and connects them with nearest walkable tiles.
+/// Vaults which don't fit are skipped. `prev_down_stairs_pos` is never covered.
+pub fn place_vaults(
+    map: &mut Map,
+    vaults: &[Vault],
+    prev_down_stairs_pos: Option<Point>,
+) -> Vec<PlacedVault> {
+    let mut placed = vec![];
+    for vault in vaults.iter() {
+        if let Some((x, y)) = find_vault_place(map, vault, prev_down_stairs_pos) {
+            placed.push(stamp_vault(map, vault, x, y));
+        }
+    }
+    placed
+}
+
+/// Vault with one tile margin has to lie on solid tiles inside map.
+fn find_vault_place(
+    map: &Map,
+    vault: &Vault,
+    prev_down_stairs_pos: Option<Point>,
+) -> Option<(usize, usize)> {
+    let (width, height) = (vault.map.width, vault.map.height);
+    if width + 4 > map.width || height + 4 > map.height {
+        return None;
+    }
+
+    let fits = |x: usize, y: usize| {
+        let covers_stairs = prev_down_stairs_pos.is_some_and(|pos| {
+            (x - 1..x + width + 1).contains(&(pos.x as usize))
+                && (y - 1..y + height + 1).contains(&(pos.y as usize))
+        });
+        !covers_stairs
+            && (y - 1..y + height + 1).all(|ty| {
+                (x - 1..x + width + 1).all(|tx| map.tiles[map.xy_to_index(tx, ty)].is_solid())
+            })
+    };
+
+    (0..PLACEMENT_TRIES)
+        .map(|_| {
+            (
+                rng::range(2, (map.width - width - 2) as i32) as usize,
+                rng::range(2, (map.height - height - 2) as i32) as usize,
+            )
+        })
+        .find(|&(x, y)| fits(x, y))
+}
+
+fn stamp_vault(map: &mut Map, vault: &Vault, x: usize, y: usize) -> PlacedVault {
+    let mut area = vec![];
+    for (i, tile) in vault.map.tiles.iter().enumerate() {
+        let (vx, vy) = vault.map.index_to_xy(i);
+        let index = map.xy_to_index(x + vx, y + vy);
+        map.tiles[index] = *tile;
+        area.push((x + vx, y + vy));
+    }
+
+    for &(cx, cy) in vault.connections.iter() {
+        let outside = match (cx, cy) {
+            (_, 0) => (x + cx, y - 1),
+            (_, cy) if cy == vault.map.height - 1 => (x + cx, y + cy + 1),
+            (0, _) => (x - 1, y + cy),
+            _ => (x + cx + 1, y + cy),
+        };
+        dig_to_walkable(map, outside, &area);
+    }
+
+    PlacedVault {
+        name: vault.name.clone(),
+        area,
+        entities: vault
+            .entities
+            .iter()
+            .map(|entity| MapEntity {
+                name: entity.name.clone(),
+                x: entity.x + x,
+                y: entity.y + y,
+            })
+            .collect(),
+    }
+}
+
+/// Digs shortest corridor (going around vault) from `start` to closest walkable tile,
+/// deep water on the way is crossed by bridge.
+fn dig_to_walkable(map: &mut Map, start: (usize, usize), vault_area: &[(usize, usize)]) {
+    let start = map.xy_to_index(start.0, start.1);
+    let mut came_from = vec![None; map.tiles.len()];
+    came_from[start] = Some(start);
+    let mut queue = VecDeque::from(vec![start]);
+
+    while let Some(index) = queue.pop_front() {
+        if index != start && !map.tiles[index].is_solid() && map.tiles[index] != TileType::DeepWater
+        {
+            let mut current = index;
+            while current != start {
+                current = came_from[current].unwrap();
+                if map.tiles[current].is_solid() {
+                    map.tiles[current] = TileType::Floor;
+                } else if map.tiles[current] == TileType::DeepWater {
+                    map.tiles[current] = TileType::Bridge;
+                }
+            }
+            return;
+        }
+
+        let (x, y) = map.index_to_xy(index);
+        let neighbours = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        for &(nx, ny) in neighbours.iter() {
+            let inside = nx > 0 && ny > 0 && nx < map.width_max() && ny < map.height_max();
+            if inside && !vault_area.contains(&(nx, ny)) {
+                let neighbour = map.xy_to_index(nx, ny);
+                if came_from[neighbour].is_none() {
+                    came_from[neighbour] = Some(index);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::{
+        rect::{apply_room_to_map, Rect},
+        validation, DoorState,
+    };
+
+    fn vault(rows: &[&str]) -> Result<Vault> {
+        Vault::from_template(VaultTemplate {
+            name: "Shrine".to_string(),
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+            legend: vec![("g".to_string(), "Goblin".to_string())]
+                .into_iter()
+                .collect(),
+            min_depth: 0,
+            max_depth: None,
+            chance_perc: 100,
+        })
+    }
+
+    #[test]
+    fn every_vault_can_be_rolled() {
+        let vaults = (0..MAX_VAULTS_PER_LEVEL + 2)
+            .map(|i| {
+                let mut vault = vault(&["#+#", "#.#", "###"]).unwrap();
+                vault.name = format!("Shrine {}", i);
+                vault
+            })
+            .collect::<Vec<_>>();
+
+        let mut rolled_names = vec![];
+        for seed in 0..20 {
+            rng::reseed(seed);
+            let rolled = roll_vaults(&vaults, 0);
+            assert_eq!(rolled.len(), MAX_VAULTS_PER_LEVEL);
+            rolled_names.extend(rolled.into_iter().map(|vault| vault.name));
+        }
+        for vault in vaults.iter() {
+            assert!(rolled_names.contains(&vault.name), "{}", vault.name);
+        }
+    }
+
+    #[test]
+    fn vaults_file_is_valid() {
+        let contents = fs::read_to_string(VAULTS_PATH).unwrap();
+        let file: VaultsFile = serde_json::from_str(&contents).unwrap();
+        for template in file.vaults {
+            Vault::from_template(template).unwrap();
+        }
+    }
+
+    #[test]
+    fn vault_is_stamped_on_solid_tiles_and_connected() {
+        let vaults = [vault(&["##+##", "#...#", "#.g.#", "#####"]).unwrap()];
+        let mut map = Map::new(40, 20).with_all_solid();
+        apply_room_to_map(&Rect::new(2, 2, 6, 6), &mut map);
+        let stairs = Point::new(4, 4);
+
+        for seed in 0..10 {
+            rng::reseed(seed);
+            let mut map = map.clone();
+            let solid_before = map.tiles.clone();
+            let placed = place_vaults(&mut map, &vaults, Some(stairs));
+
+            assert_eq!(placed.len(), 1);
+            let placed = &placed[0];
+            assert_eq!(placed.area.len(), 5 * 4);
+            assert!(!placed
+                .area
+                .contains(&(stairs.x as usize, stairs.y as usize)));
+            for &(x, y) in placed.area.iter() {
+                assert!(solid_before[map.xy_to_index(x, y)].is_solid());
+            }
+            let (x, y) = placed.area[0];
+            assert_eq!(map.tile_at_xy(x + 2, y), TileType::Door(DoorState::Closed));
+            assert_eq!(
+                placed.entities,
+                vec![MapEntity {
+                    name: "Goblin".to_string(),
+                    x: x + 2,
+                    y: y + 2,
+                }]
+            );
+
+            map.update_blocked_with_blocking_tiles();
+            assert_eq!(
+                validation::connected_regions(&map).len(),
+                1,
+                "seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn vault_without_space_is_skipped() {
+        let shrine = vault(&["##+##", "#...#", "#####"]).unwrap();
+        let mut map = Map::new(40, 20).with_all_solid();
+        apply_room_to_map(&Rect::new(1, 1, 37, 17), &mut map);
+        let tiles = map.tiles.clone();
+
+        assert!(place_vaults(&mut map, &[shrine], None).is_empty());
+        assert_eq!(map.tiles, tiles);
+    }
+
+    #[test]
+    fn vault_connections_are_checked() {
+        assert!(matches!(
+            vault(&["###", "#.#", "###"]),
+            Err(Error::VaultWithoutConnections { .. })
+        ));
+        assert!(matches!(
+            vault(&["###", "#+#", "###"]),
+            Err(Error::VaultConnectionNotOnEdge { x: 1, y: 1, .. })
+        ));
+        assert!(matches!(
+            vault(&["#+#", "#?#", "###"]),
+            Err(Error::InvalidVault { .. })
+        ));
+    }
+}
@@ -1,21 +1,54 @@
 use rltk::prelude::*;
-use std::sync::Mutex;
+use std::cell::RefCell;
 
-lazy_static! {
-    static ref RNG: Mutex<RandomNumberGenerator> = Mutex::new(RandomNumberGenerator::new());
+thread_local! {
+    // one generator per thread, so seeded runs (e.g. in parallel tests) don't interfere
+    static RNG: RefCell<RandomNumberGenerator> = RefCell::new(RandomNumberGenerator::new());
 }
 
 pub fn reseed(seed: u64) {
-    *RNG.lock().unwrap() = RandomNumberGenerator::seeded(seed);
+    RNG.with(|rng| *rng.borrow_mut() = RandomNumberGenerator::seeded(seed));
+}
+
+/// New random seed (not taken from global generator, so it doesn't depend on current seed).
+pub fn random_seed() -> u64 {
+    RandomNumberGenerator::new().next_u64()
+}
+
+/// Seed for generating level at `depth`, so every level of run is always the same
+/// no matter in which order levels are created.
+pub fn seed_for_depth(run_seed: u64, depth: usize) -> u64 {
+    // splitmix64 finalizer, so close depths get unrelated seeds
+    let mut z = run_seed.wrapping_add((depth as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Seed for level at `depth` of dungeon branch, main dungeon (branch 0) uses `seed_for_depth`.
+pub fn seed_for_branch_depth(run_seed: u64, branch: usize, depth: usize) -> u64 {
+    if branch == 0 {
+        seed_for_depth(run_seed, depth)
+    } else {
+        seed_for_depth(seed_for_depth(run_seed, depth), branch)
+    }
 }
 
 pub fn roll_dice(n: i32, die_type: i32) -> i32 {
-    RNG.lock().unwrap().roll_dice(n, die_type)
+    RNG.with(|rng| rng.borrow_mut().roll_dice(n, die_type))
 }
 
 /// <min, max> INCLUSIVE on both sides
 pub fn range(min: i32, max: i32) -> i32 {
-    RNG.lock().unwrap().range(min, max + 1)
+    RNG.with(|rng| rng.borrow_mut().range(min, max + 1))
+}
+
+/// Fisher-Yates shuffle.
+pub fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        let j = range(0, i as i32) as usize;
+        items.swap(i, j);
+    }
 }
 
 pub fn rand_bool() -> bool {
@@ -31,3 +64,8 @@ pub fn test_perc(chance: usize) -> bool {
     let rand = random_perc() as usize;
     rand <= chance
 }
+
+/// Probability of `test_perc(chance)` being true, there are 101 possible rolls.
+pub fn test_perc_probability(chance: usize) -> f32 {
+    (chance.min(100) + 1) as f32 / 101.0
+}
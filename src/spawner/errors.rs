@@ -0,0 +1,49 @@
+use snafu::Snafu;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum Error {
+    #[snafu(display("Unable to read raws directory {}: {}", path, source))]
+    RawsDirIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to read raw file {}: {}", path, source))]
+    RawFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to parse raw file {}: {}", path, source))]
+    RawFileSerde {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[snafu(display("Entity \"{}\" defined more than once (second time in {})", name, path))]
+    DuplicateEntityName { name: String, path: String },
+
+    #[snafu(display("Unknown entity \"{}\" referenced by \"{}\"", name, referenced_by))]
+    UnknownEntityReference { name: String, referenced_by: String },
+
+    #[snafu(display("Unable to read spawn tables file {}: {}", path, source))]
+    SpawnTablesFileIo {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unable to parse spawn tables file {}: {}", path, source))]
+    SpawnTablesFileSerde {
+        source: serde_json::Error,
+        path: String,
+    },
+
+    #[snafu(display("Unknown spawn pack \"{}\" used in spawn table \"{}\"", pack, table))]
+    UnknownSpawnPack { pack: String, table: String },
+
+    #[snafu(display("Unknown spawn table \"{}\"", name))]
+    UnknownSpawnTable { name: String },
+}
@@ -1,2 +0,0 @@
-pub mod armors;
-pub mod weapons;
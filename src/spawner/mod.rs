@@ -1,33 +1,29 @@
-use specs::{Entity, World};
-
-use crate::{levels::level::Level, rng, spawner::monsters::spawn_goblin};
-
-use self::{
-    items::{
-        eq::{
-            armors::{
-                spawn_chain_armor, spawn_leather_armor, spawn_leather_boots, spawn_plate_armor,
-            },
-            weapons::{
-                spawn_champion_sword, spawn_dagger, spawn_longsword, spawn_shortsword,
-                spawn_zweihander,
-            },
-        },
-        potions::{spawn_great_healing_potion, spawn_healing_potion},
-        scrolls::{
-            spawn_area_sleep_scroll, spawn_fireball_scroll, spawn_magic_missile_scroll,
-            spawn_sleep_scroll, spawn_teleport_scroll,
-        },
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use specs::{Entity, World, WorldExt};
+
+use crate::{
+    ecs::{
+        components,
+        systems::inventory::{insert_item_in_eq, insert_item_in_inv},
     },
-    monsters::{spawn_small_slime, spawn_slime, spawn_knight, spawn_mighty_slime, spawn_orc, spawn_rogue},
-    spawn_tables::SpawnTable,
+    levels::level::Level,
+    rng,
 };
 
-pub mod items;
-pub mod monsters;
+use self::placement::SpawnPlacement;
+use self::spawn_tables::{EliteModifier, SpawnTable};
+
+pub mod errors;
+pub mod placement;
 pub mod player;
+pub mod raws;
 pub mod spawn_tables;
 
+pub use errors::Error;
+
+/// Spawns entity defined in raw files (see `raws::RAWS_DIR`).
 pub fn spawn_entity(
     ecs: &mut World,
     name: &str,
@@ -35,84 +31,236 @@ pub fn spawn_entity(
     y: usize,
     level: usize,
 ) -> Option<Entity> {
-    match name {
-        "Orc" => Some(spawn_orc(ecs, x, y, level)),
-        "Goblin" => Some(spawn_goblin(ecs, x, y, level)),
-        "Knight" => Some(spawn_knight(ecs, x, y, level)),
-        "Rogue" => Some(spawn_rogue(ecs, x, y, level)),
-
-        "Small slime" => Some(spawn_small_slime(ecs, x, y, level)),
-        "Slime" => Some(spawn_slime(ecs, x, y, level)),
-
-        "Health potion" => Some(spawn_healing_potion(ecs, x, y, level)),
-        "Great health potion" => Some(spawn_great_healing_potion(ecs, x, y, level)),
-
-        "Magic missile scroll" => Some(spawn_magic_missile_scroll(ecs, x, y, level)),
-        "Sleep scroll" => Some(spawn_sleep_scroll(ecs, x, y, level)),
-        "Area sleep scroll" => Some(spawn_area_sleep_scroll(ecs, x, y, level)),
-        "Fireball scroll" => Some(spawn_fireball_scroll(ecs, x, y, level)),
-        "Teleport scroll" => Some(spawn_teleport_scroll(ecs, x, y, level)),
-
-        "Dagger" => Some(spawn_dagger(ecs, x, y, level)),
-        "Zweihander" => Some(spawn_zweihander(ecs, x, y, level)),
-        "Leather armor" => Some(spawn_leather_armor(ecs, x, y, level)),
-        "Leather boots" => Some(spawn_leather_boots(ecs, x, y, level)),
-        "Chain armor" => Some(spawn_chain_armor(ecs, x, y, level)),
-        "Plate armor" => Some(spawn_plate_armor(ecs, x, y, level)),
-        "Long sword" => Some(spawn_longsword(ecs, x, y, level)),
-        "Short sword" => Some(spawn_shortsword(ecs, x, y, level)),
-        "Mighty slime" => Some(spawn_mighty_slime(ecs, x, y, level)),
-        "Champion sword" => Some(spawn_champion_sword(ecs, x, y, level)),
-
-        _ => {
-            println!("Cannot spawn {}. Unknown entity", name);
+    if let Some(template) = raws::get_template(name) {
+        Some(raws::spawn_from_template(ecs, template, x, y, level))
+    } else {
+        println!("Cannot spawn {}. Unknown entity", name);
+        None
+    }
+}
+
+pub fn spawn_item_into_inventory(
+    ecs: &mut World,
+    owner: Entity,
+    item_name: String,
+    x: usize,
+    y: usize,
+    level: usize,
+) -> Option<Entity> {
+    if let Some(item) = spawn_entity(ecs, &item_name, x, y, level) {
+        let is_item;
+        {
+            let items = ecs.read_storage::<components::Item>();
+            is_item = items.contains(item);
+        }
+        if is_item {
+            insert_item_in_inv(ecs, owner, item);
+            Some(item)
+        } else {
+            println!("{} isnt item", item_name);
             None
         }
+    } else {
+        println!("Unable to create {}", item_name);
+        None
+    }
+}
+
+pub fn spawn_item_in_eq(
+    ecs: &mut World,
+    owner: Entity,
+    item_name: String,
+    x: usize,
+    y: usize,
+    level: usize,
+) {
+    if let Some(item) = spawn_item_into_inventory(ecs, owner, item_name, x, y, level) {
+        insert_item_in_eq(ecs, owner, item);
+    }
+}
+
+/// What spawn table spawned on one level, for balance debugging.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpawnReport {
+    /// how many times was each pack spawned
+    pub packs: BTreeMap<String, usize>,
+    pub entities: BTreeMap<String, usize>,
+    pub monsters: usize,
+    pub elites: usize,
+    /// average number of elites for spawned monsters
+    pub expected_elites: f32,
+    /// spawn areas without pack
+    pub empty_areas: usize,
+    /// guaranteed packs spawned less than `min_spawns` times (no area fits them)
+    pub missing_guaranteed_packs: Vec<String>,
+    /// walking distance of the closest monster from arrival points
+    pub nearest_monster_distance: Option<f32>,
+}
+
+impl SpawnReport {
+    pub fn to_text(&self) -> String {
+        let counts = |map: &BTreeMap<String, usize>| {
+            map.iter()
+                .map(|(name, num)| format!("{} x{}", name, num))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut lines = vec![
+            format!("  packs: {}", counts(&self.packs)),
+            format!("  entities: {}", counts(&self.entities)),
+            format!(
+                "  monsters: {}, elites: {} (expected {:.1}), empty areas: {}",
+                self.monsters, self.elites, self.expected_elites, self.empty_areas
+            ),
+        ];
+        if let Some(distance) = self.nearest_monster_distance {
+            lines.push(format!("  nearest monster from stairs: {:.1}", distance));
+        }
+        if !self.missing_guaranteed_packs.is_empty() {
+            lines.push(format!(
+                "  missing guaranteed packs: {}",
+                self.missing_guaranteed_packs.join(", ")
+            ));
+        }
+        lines.join("\n")
     }
 }
 
-pub fn spawn_from_spawn_table(ecs: &mut World, level: &Level, mut spawn_table: SpawnTable) {
+/// Spawns packs planned for spawn areas of level (see `SpawnTable::plan_spawn_packs`).
+/// Nothing is spawned in safe zone around arrival points (up stairs and `arrivals`),
+/// areas farther from them are filled first and items prefer nooks (see `placement`).
+pub fn spawn_from_spawn_table(
+    ecs: &mut World,
+    level: &Level,
+    mut spawn_table: SpawnTable,
+    arrivals: &[(usize, usize)],
+) -> SpawnReport {
+    let mut report = SpawnReport::default();
     if level.spawn_areas.is_empty() {
         println!("Can't spawn on level without spawn areas!");
-        return;
+        return report;
     }
 
-    for spawn_area in level.spawn_areas.iter() {
-        let spawn_pack_index = spawn_table.roll_spawn_pack_index(spawn_area.len());
-        if let Some(index) = spawn_pack_index {
-            let mut spawned_points = vec![];
+    let elite = spawn_table.elite_modifier().cloned();
+    let placement = SpawnPlacement::new(&level.map, &spawn_table.placement, arrivals);
+    let spawn_areas = placement
+        .areas_by_distance(&level.spawn_areas)
+        .into_iter()
+        .map(|i| placement.spawnable_points(&level.spawn_areas[i]))
+        .collect::<Vec<_>>();
+    let areas = spawn_areas
+        .iter()
+        .map(|area| area.len())
+        .collect::<Vec<_>>();
+    let plan = spawn_table.plan_spawn_packs(&areas);
 
-            for entry in spawn_table.spawn_packs[index].entities.iter() {
+    for (spawn_area, pack_index) in spawn_areas.iter().zip(plan) {
+        let pack = match pack_index {
+            Some(index) => &spawn_table.spawn_packs[index],
+            None => {
+                report.empty_areas += 1;
+                continue;
+            }
+        };
+        *report.packs.entry(pack.name.clone()).or_default() += 1;
+
+        let nooks = spawn_area
+            .iter()
+            .copied()
+            .filter(|&(x, y)| placement.is_nook(x, y))
+            .collect::<Vec<_>>();
+        let mut spawned_points = vec![];
+        for _ in 0..pack.area_scale(spawn_area.len()) {
+            for entry in pack.entities.iter() {
                 let num = entry.roll_spawn_num();
-                let spawn_points = random_spawn_points(num, &spawned_points, spawn_area);
+                let spawn_points = if is_item_template(&entry.entity_name) {
+                    let mut points = random_spawn_points(num, &spawned_points, &nooks);
+                    let taken = [spawned_points.as_slice(), points.as_slice()].concat();
+                    points.extend(random_spawn_points(num - points.len(), &taken, spawn_area));
+                    points
+                } else {
+                    random_spawn_points(num, &spawned_points, spawn_area)
+                };
 
                 for (x, y) in spawn_points.into_iter() {
-                    if let Some(_ent) =
+                    if let Some(ent) =
                         spawn_entity(ecs, &entry.entity_name, x, y, level.level_index)
                     {
                         spawned_points.push((x, y));
+                        *report
+                            .entities
+                            .entry(entry.entity_name.clone())
+                            .or_default() += 1;
+                        if is_monster(ecs, ent) {
+                            report.monsters += 1;
+                            if let Some(distance) = placement.distance(x, y) {
+                                report.nearest_monster_distance = Some(
+                                    report
+                                        .nearest_monster_distance
+                                        .map_or(distance, |nearest| nearest.min(distance)),
+                                );
+                            }
+                            if let Some(elite) = &elite {
+                                report.expected_elites += elite.chance();
+                                if try_make_elite(ecs, ent, elite) {
+                                    report.elites += 1;
+                                }
+                            }
+                        }
                     }
                 }
             }
         }
     }
+    report.missing_guaranteed_packs = spawn_table.missing_guaranteed_packs();
+    report
+}
+
+/// Entities with AI and hp.
+fn is_monster(ecs: &World, entity: Entity) -> bool {
+    ecs.read_storage::<components::AI>().contains(entity)
+        && ecs.read_storage::<components::Hp>().contains(entity)
+}
+
+fn is_item_template(name: &str) -> bool {
+    raws::get_template(name).is_some_and(|template| template.item.is_some())
+}
+
+/// Monster becomes elite with chance of modifier.
+fn try_make_elite(ecs: &mut World, entity: Entity, elite: &EliteModifier) -> bool {
+    if elite.chance_perc == 0 || !rng::test_perc(elite.chance_perc) {
+        return false;
+    }
+
+    if let Some(hp) = ecs.write_storage::<components::Hp>().get_mut(entity) {
+        hp.max_hp += hp.max_hp * elite.hp_bonus_perc as i32 / 100;
+        hp.hp = hp.max_hp;
+    }
+    if let Some(stats) = ecs
+        .write_storage::<components::CombatBaseStats>()
+        .get_mut(entity)
+    {
+        stats.attack += elite.attack_bonus;
+        stats.defense += elite.defense_bonus;
+    }
+    if let Some(name) = ecs.write_storage::<components::Name>().get_mut(entity) {
+        name.name = format!("Elite {}", name.name);
+    }
+    true
 }
 
+/// Spawn area can be smaller than `num`, then all its free points are returned.
 fn random_spawn_points(
     num: usize,
     exclude_points: &[(usize, usize)],
     spawn_area: &[(usize, usize)],
 ) -> Vec<(usize, usize)> {
-    let mut spawn_points = vec![];
-    for _ in 0..num {
-        loop {
-            let i = rng::range(0, spawn_area.len() as i32 - 1) as usize;
-            let point = spawn_area[i];
-            if !spawn_points.contains(&point) && !exclude_points.contains(&point) {
-                spawn_points.push(point);
-                break;
-            }
-        }
-    }
-    spawn_points
+    let mut free_points = spawn_area
+        .iter()
+        .copied()
+        .filter(|point| !exclude_points.contains(point))
+        .collect::<Vec<_>>();
+    (0..num.min(free_points.len()))
+        .map(|_| free_points.swap_remove(rng::range(0, free_points.len() as i32 - 1) as usize))
+        .collect()
 }
@@ -0,0 +1,208 @@
+//! Where spawn packs are placed on level. Distances are walking costs from arrival points
+//! (up stairs, start of the run, landings below chasms and trapdoors), measured by dijkstra map,
+//! so player doesn't arrive next to monsters.
+
+use serde::{Deserialize, Serialize};
+
+use crate::maps::{DoorOpenerMap, Map, TileType};
+
+/// Dijkstra map isn't searched further than this.
+const MAX_DISTANCE: f32 = 10_000.0;
+
+/// Placement rules of spawn tables file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlacementRules {
+    /// nothing is spawned closer to arrival points than this (walking distance)
+    pub safe_radius: f32,
+}
+
+impl Default for PlacementRules {
+    fn default() -> PlacementRules {
+        PlacementRules { safe_radius: 6.0 }
+    }
+}
+
+/// Distances from arrival points and nooks (dead-ends, room corners) of level.
+pub struct SpawnPlacement {
+    map: Map,
+    /// dijkstra map, None if level has no arrival points
+    distances: Option<Vec<f32>>,
+    safe_radius: f32,
+}
+
+impl SpawnPlacement {
+    /// Up stairs of map are arrival points, `arrivals` are the other ones
+    /// (points outside of map are skipped).
+    pub fn new(map: &Map, rules: &PlacementRules, arrivals: &[(usize, usize)]) -> SpawnPlacement {
+        let mut map = map.clone();
+        map.update_blocked_with_blocking_tiles();
+        let mut starts = map
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_i, tile)| **tile == TileType::StairsUp)
+            .map(|(i, _tile)| i)
+            .collect::<Vec<_>>();
+        starts.extend(
+            arrivals
+                .iter()
+                .filter(|&&(x, y)| x < map.width && y < map.height)
+                .map(|&(x, y)| map.xy_to_index(x, y)),
+        );
+        let distances = if starts.is_empty() {
+            None
+        } else {
+            let dijkstra_map = rltk::DijkstraMap::new(
+                map.width,
+                map.height,
+                &starts,
+                &DoorOpenerMap(&map),
+                MAX_DISTANCE,
+            );
+            Some(dijkstra_map.map)
+        };
+        SpawnPlacement {
+            map,
+            distances,
+            safe_radius: rules.safe_radius,
+        }
+    }
+
+    /// Walking distance from the closest arrival point,
+    /// None if there are no arrival points or point is unreachable.
+    pub fn distance(&self, x: usize, y: usize) -> Option<f32> {
+        let distances = self.distances.as_ref()?;
+        let distance = distances[self.map.xy_to_index(x, y)];
+        if distance < MAX_DISTANCE {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_safe_zone(&self, x: usize, y: usize) -> bool {
+        self.distance(x, y)
+            .is_some_and(|distance| distance < self.safe_radius)
+    }
+
+    /// Points of spawn area outside of safe zone.
+    pub fn spawnable_points(&self, area: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        area.iter()
+            .copied()
+            .filter(|&(x, y)| !self.is_safe_zone(x, y))
+            .collect()
+    }
+
+    /// Distance of the closest reachable point of area, 0 for unreachable areas.
+    pub fn area_distance(&self, area: &[(usize, usize)]) -> f32 {
+        area.iter()
+            .filter_map(|&(x, y)| self.distance(x, y))
+            .fold(None, |min: Option<f32>, distance| {
+                Some(min.map_or(distance, |min| min.min(distance)))
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Indexes of spawn areas, the farthest from arrival points first.
+    pub fn areas_by_distance(&self, areas: &[Vec<(usize, usize)>]) -> Vec<usize> {
+        let distances = areas
+            .iter()
+            .map(|area| self.area_distance(area))
+            .collect::<Vec<_>>();
+        let mut order = (0..areas.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| distances[b].partial_cmp(&distances[a]).unwrap());
+        order
+    }
+
+    /// Floor in dead-end (walls on three sides) or in corner (walls on two touching sides).
+    pub fn is_nook(&self, x: usize, y: usize) -> bool {
+        if self.map.tile_at_xy(x, y) != TileType::Floor {
+            return false;
+        }
+        // outside of map counts as wall
+        let solid = |dx: i32, dy: i32| {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            nx < 0
+                || ny < 0
+                || nx >= self.map.width as i32
+                || ny >= self.map.height as i32
+                || self.map.tile_at_xy(nx as usize, ny as usize).is_solid()
+        };
+        let (up, down) = (solid(0, -1), solid(0, 1));
+        let (left, right) = (solid(-1, 0), solid(1, 0));
+        let solid_sides = [up, down, left, right].iter().filter(|s| **s).count();
+        solid_sides >= 3 || (solid_sides == 2 && up != down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maps::ascii::map_from_ascii;
+
+    fn placement(ascii: &str, arrivals: &[(usize, usize)]) -> SpawnPlacement {
+        let map = map_from_ascii(ascii).unwrap().map;
+        SpawnPlacement::new(&map, &PlacementRules::default(), arrivals)
+    }
+
+    #[test]
+    fn dead_ends_and_corners_are_nooks() {
+        let placement = placement(
+            "######\n\
+             #.####\n\
+             #....#\n\
+             #.##.#\n\
+             #....#\n\
+             ######\n",
+            &[],
+        );
+
+        // dead-end, corners
+        assert!(placement.is_nook(1, 1));
+        assert!(placement.is_nook(4, 2));
+        assert!(placement.is_nook(1, 4));
+        // corridors, crossing and wall
+        assert!(!placement.is_nook(2, 2));
+        assert!(!placement.is_nook(1, 3));
+        assert!(!placement.is_nook(1, 2));
+        assert!(!placement.is_nook(0, 0));
+    }
+
+    #[test]
+    fn areas_are_ordered_from_the_farthest() {
+        let map = "##########\n\
+                   #<.......#\n\
+                   ##########\n";
+        let areas = vec![vec![(2, 1)], vec![(8, 1)], vec![(5, 1), (6, 1)], vec![]];
+
+        assert_eq!(
+            placement(map, &[]).areas_by_distance(&areas),
+            vec![1, 2, 0, 3]
+        );
+        // area without reachable points counts as the closest one
+        assert_eq!(placement(map, &[]).area_distance(&areas[3]), 0.0);
+    }
+
+    #[test]
+    fn safe_zone_is_around_every_arrival_point() {
+        let map = "####################\n\
+                   #..................#\n\
+                   ####################\n";
+
+        let without_arrivals = placement(map, &[]);
+        assert_eq!(without_arrivals.distance(1, 1), None);
+        assert!(!without_arrivals.is_safe_zone(1, 1));
+
+        let placement = placement(map, &[(1, 1), (18, 1), (30, 30)]);
+        assert_eq!(placement.distance(3, 1), Some(2.0));
+        assert_eq!(placement.distance(16, 1), Some(2.0));
+        assert!(placement.is_safe_zone(6, 1));
+        assert!(!placement.is_safe_zone(7, 1));
+        assert_eq!(placement.spawnable_points(&[(1, 1), (9, 1)]), vec![(9, 1)]);
+        assert_eq!(
+            placement.areas_by_distance(&[vec![(2, 1)], vec![(9, 1)], vec![(16, 1)]]),
+            vec![1, 2, 0]
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use snafu::{ensure, ResultExt};
+use specs::{Builder, Entity, World, WorldExt};
+
+use crate::ecs::components::{self, BodyPart};
+
+use super::errors::{
+    DuplicateEntityName, RawFileIo, RawFileSerde, RawsDirIo, Result, UnknownEntityReference,
+};
+use super::{spawn_item_in_eq, spawn_item_into_inventory};
+
+/// Directory with entity definitions. Every `.json` file inside is loaded.
+pub const RAWS_DIR: &str = "./resources/jsons";
+
+lazy_static! {
+    static ref RAWS: Mutex<Raws> = Mutex::new(Raws::default());
+}
+
+/// Single raw file, holds any number of entity definitions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawFile {
+    pub entities: Vec<EntityTemplate>,
+}
+
+/// Entity definition, every field (except name) is optional component.
+/// Position is not part of template, it is given when spawning.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityTemplate {
+    pub name: components::Name,
+    pub renderable: Option<components::Renderable>,
+    pub description: Option<components::Description>,
+
+    pub ai: Option<components::AI>,
+    pub blocks_tile: Option<components::BlocksTile>,
+    pub opens_doors: Option<components::OpensDoors>,
+    pub view: Option<components::View>,
+    pub hp: Option<components::Hp>,
+    pub combat_base_stats: Option<components::CombatBaseStats>,
+    pub spawns_after_death: Option<components::SpawnsAfterDeath>,
+    pub final_boss: Option<components::FinalBoss>,
+
+    pub item: Option<components::Item>,
+    pub usable: Option<components::Usable>,
+    pub ranged: Option<components::Ranged>,
+    pub heal: Option<components::Heal>,
+    pub inflicts_damage: Option<components::InflictsDamage>,
+    pub area_of_effect: Option<components::AreaOfEffect>,
+    pub sleeping: Option<components::Sleeping>,
+    pub teleporting: Option<components::Teleporting>,
+    pub equippable: Option<components::Equippable>,
+    pub melee_damage_bonus: Option<components::MeleeDamageBonus>,
+    pub defense_bonus: Option<components::DefenseBonus>,
+
+    pub inventory: Option<InventoryTemplate>,
+    pub body_parts: Option<BodyPartsTemplate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InventoryTemplate {
+    /// names of items spawned into inventory
+    #[serde(default)]
+    pub items: Vec<String>,
+    /// names of items spawned into inventory and equipped (needs `body_parts`)
+    #[serde(default)]
+    pub equipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyPartsTemplate {
+    pub parts: BodyPartsPreset,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyPartsPreset {
+    DefaultHumanoid,
+    Custom(Vec<BodyPart>),
+}
+
+impl BodyPartsPreset {
+    pub fn to_body_parts(&self) -> components::BodyParts {
+        match self {
+            BodyPartsPreset::DefaultHumanoid => components::BodyParts::default_humanoid(),
+            BodyPartsPreset::Custom(parts) => components::BodyParts {
+                parts_with_equipped: parts.iter().map(|part| (*part, None)).collect(),
+            },
+        }
+    }
+}
+
+impl EntityTemplate {
+    /// names of other entities this template spawns
+    fn referenced_entities(&self) -> Vec<&String> {
+        let mut names = vec![];
+        if let Some(inventory) = &self.inventory {
+            names.extend(inventory.items.iter());
+            names.extend(inventory.equipped.iter());
+        }
+        if let Some(spawns) = &self.spawns_after_death {
+            names.extend(spawns.spawns.iter().map(|entry| &entry.entity_name));
+        }
+        names
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Raws {
+    templates: HashMap<String, EntityTemplate>,
+}
+
+impl Raws {
+    /// Loads all `.json` files from `dir` (in alphabetical order).
+    pub fn load_from_dir(dir: &str) -> Result<Raws> {
+        let mut paths = fs::read_dir(dir)
+            .context(RawsDirIo { path: dir })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut raws = Raws::default();
+        for path in paths.iter() {
+            let path = path.to_string_lossy().to_string();
+            let contents = fs::read_to_string(&path).context(RawFileIo { path: &path })?;
+            raws.add_raw_file(&contents, &path)?;
+        }
+        raws.validate()?;
+        Ok(raws)
+    }
+
+    /// `path` is only used in error messages
+    pub fn add_raw_file(&mut self, contents: &str, path: &str) -> Result<()> {
+        let raw_file: RawFile = serde_json::from_str(contents).context(RawFileSerde { path })?;
+        for template in raw_file.entities.into_iter() {
+            let name = template.name.name.clone();
+            ensure!(
+                !self.templates.contains_key(&name),
+                DuplicateEntityName { name, path }
+            );
+            self.templates.insert(name, template);
+        }
+        Ok(())
+    }
+
+    /// Checks if all entities referenced by templates are defined.
+    pub fn validate(&self) -> Result<()> {
+        for (name, template) in self.templates.iter() {
+            for referenced in template.referenced_entities() {
+                ensure!(
+                    self.templates.contains_key(referenced),
+                    UnknownEntityReference {
+                        name: referenced,
+                        referenced_by: name,
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EntityTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    pub fn entity_names(&self) -> Vec<String> {
+        let mut names = self.templates.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+/// Loads raws from `dir` and replaces currently used ones.
+pub fn load_raws(dir: &str) -> Result<()> {
+    let raws = Raws::load_from_dir(dir)?;
+    *RAWS.lock().unwrap() = raws;
+    Ok(())
+}
+
+pub fn is_known_entity(name: &str) -> bool {
+    RAWS.lock().unwrap().contains(name)
+}
+
+pub fn entity_names() -> Vec<String> {
+    RAWS.lock().unwrap().entity_names()
+}
+
+pub fn get_template(name: &str) -> Option<EntityTemplate> {
+    RAWS.lock().unwrap().get(name).cloned()
+}
+
+macro_rules! with_optional_components {
+    ($builder:ident, $template:ident; $($field:ident),*) => {
+        $(
+            if let Some(component) = $template.$field {
+                $builder = $builder.with(component);
+            }
+        )*
+    };
+}
+
+pub fn spawn_from_template(
+    ecs: &mut World,
+    template: EntityTemplate,
+    x: usize,
+    y: usize,
+    level: usize,
+) -> Entity {
+    let inventory = template.inventory.clone();
+
+    let mut builder = ecs
+        .create_entity()
+        .with(components::Position { x, y, level })
+        .with(template.name);
+    if inventory.is_some() {
+        builder = builder.with(components::Inventory::new_empty());
+    }
+    if let Some(body_parts) = template.body_parts {
+        builder = builder.with(body_parts.parts.to_body_parts());
+    }
+    with_optional_components!(builder, template;
+        renderable,
+        description,
+        ai,
+        blocks_tile,
+        opens_doors,
+        view,
+        hp,
+        combat_base_stats,
+        spawns_after_death,
+        final_boss,
+        item,
+        usable,
+        ranged,
+        heal,
+        inflicts_damage,
+        area_of_effect,
+        sleeping,
+        teleporting,
+        equippable,
+        melee_damage_bonus,
+        defense_bonus
+    );
+    let entity = builder.build();
+
+    if let Some(inventory) = inventory {
+        for item in inventory.items.into_iter() {
+            spawn_item_into_inventory(ecs, entity, item, x, y, level);
+        }
+        for item in inventory.equipped.into_iter() {
+            spawn_item_in_eq(ecs, entity, item, x, y, level);
+        }
+    }
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawner::Error;
+
+    #[test]
+    fn every_raw_file_is_loaded() {
+        let raws = Raws::load_from_dir(RAWS_DIR).unwrap();
+        for entry in fs::read_dir(RAWS_DIR).unwrap() {
+            let path = entry.unwrap().path();
+            let raw_file: RawFile =
+                serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            for template in raw_file.entities.iter() {
+                assert!(raws.contains(&template.name.name), "{:?}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let contents = r#"{ "entities": [ { "name": { "name": "Goblin" } } ] }"#;
+        let mut raws = Raws::default();
+        raws.add_raw_file(contents, "a.json").unwrap();
+
+        let result = raws.add_raw_file(contents, "b.json");
+        assert!(matches!(
+            result,
+            Err(Error::DuplicateEntityName { name, path }) if name == "Goblin" && path == "b.json"
+        ));
+    }
+
+    #[test]
+    fn unknown_references_are_rejected() {
+        let contents = r#"{ "entities": [
+            { "name": { "name": "Goblin" }, "inventory": { "items": ["Unknown potion"] } }
+        ] }"#;
+        let mut raws = Raws::default();
+        raws.add_raw_file(contents, "a.json").unwrap();
+
+        let result = raws.validate();
+        assert!(matches!(
+            result,
+            Err(Error::UnknownEntityReference { name, referenced_by })
+                if name == "Unknown potion" && referenced_by == "Goblin"
+        ));
+    }
+}
@@ -1,4 +1,27 @@
-use crate::{levels::level::LevelType, rng};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt, ResultExt};
+
+use crate::rng;
+
+use super::errors::{
+    Result, SpawnTablesFileIo, SpawnTablesFileSerde, UnknownEntityReference, UnknownSpawnPack,
+    UnknownSpawnTable,
+};
+use super::placement::PlacementRules;
+use super::raws;
+
+pub const SPAWN_TABLES_PATH: &str = "./resources/spawn_tables.json";
+
+/// Entities of pack are rolled at most this many times for one spawn area.
+const MAX_AREA_SCALE: usize = 3;
+
+lazy_static! {
+    static ref SPAWN_TABLES: Mutex<SpawnTables> = Mutex::new(SpawnTables::default());
+}
 
 #[derive(Clone, Debug)]
 pub struct SpawnPoint {
@@ -6,7 +29,7 @@ pub struct SpawnPoint {
     pub entity_name: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SpawnEntry {
     pub entity_name: String,
     /// inclusive range (random number, how many to spawn)
@@ -16,9 +39,14 @@ pub struct SpawnEntry {
     /// Usage examples:
     ///     SpawnEntry::new("Goblin".to_string(), 2, 6) // always spawns 2-6 goblins
     ///     SpawnEntry::new("Goblin".to_string(), 1, 2).with_chance(10) // 10% chance to spawn 1-2 goblins
+    #[serde(default = "default_chance_perc")]
     pub chance_perc: usize,
 }
 
+fn default_chance_perc() -> usize {
+    100
+}
+
 impl SpawnEntry {
     pub fn new(name: String, range_min: usize, range_max: usize) -> SpawnEntry {
         SpawnEntry {
@@ -42,25 +70,53 @@ impl SpawnEntry {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SpawnPack {
+    /// name of pack in spawn tables file
+    #[serde(skip)]
+    pub name: String,
     /// this pack will not be spawned more times than `max_spawns`
     pub max_spawns: usize,
+    /// this pack is spawned at least `min_spawns` times, if there are spawn areas it fits
+    pub min_spawns: usize,
+    #[serde(skip)]
     pub spawns_counter: usize,
-    /// chance percentage % for this pack to be spawned, default to 100
+    /// chance percentage % for this pack to be spawned, default to 100,
+    /// it multiplies `weight` when pack is rolled
     pub chance_perc: usize,
     pub entities: Vec<SpawnEntry>,
     pub min_area: usize,
+    /// pack is spawned only on levels with level weight (depth) in <min_depth, max_depth>
+    pub min_depth: usize,
+    pub max_depth: usize,
+    /// rarity, packs with bigger weight are rolled more often, default to 1
+    pub weight: usize,
+    /// `max_spawns` grows by this for every level deeper than `min_depth`
+    pub extra_spawns_per_depth: f32,
+    /// entities are rolled once more for every `scale_area` tiles of spawn area
+    /// above `min_area`, 0 - no scaling
+    pub scale_area: usize,
+    /// boss pack is spawned in the spawn area farthest from arrival points (it fits)
+    pub boss: bool,
 }
 
 impl Default for SpawnPack {
     fn default() -> SpawnPack {
         SpawnPack {
+            name: String::new(),
             chance_perc: 100,
             entities: vec![],
             min_area: 0,
             max_spawns: usize::MAX,
+            min_spawns: 0,
             spawns_counter: 0,
+            min_depth: 0,
+            max_depth: usize::MAX,
+            weight: 1,
+            extra_spawns_per_depth: 0.0,
+            scale_area: 0,
+            boss: false,
         }
     }
 }
@@ -74,263 +130,494 @@ impl SpawnPack {
         self.max_spawns = max_spawns;
         self
     }
+    pub fn with_depths(mut self, min_depth: usize, max_depth: usize) -> SpawnPack {
+        self.min_depth = min_depth;
+        self.max_depth = max_depth;
+        self
+    }
+    pub fn with_weight(mut self, weight: usize) -> SpawnPack {
+        self.weight = weight;
+        self
+    }
+    pub fn with_min_spawns(mut self, min_spawns: usize) -> SpawnPack {
+        self.min_spawns = min_spawns;
+        self
+    }
+    pub fn as_boss(mut self) -> SpawnPack {
+        self.boss = true;
+        self
+    }
 
-    pub fn goblins_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 8,
-            entities: vec![
-                SpawnEntry::new("Goblin".to_string(), 2, 6),
-                SpawnEntry::new("Health potion".to_string(), 0, 1),
-                SpawnEntry::new("Sleep scroll".to_string(), 1, 1).with_chance(20),
-            ],
-            ..SpawnPack::default()
-        }
+    /// Pack can be spawned again in spawn area of `area` tiles (empty areas fit nothing).
+    pub fn fits(&self, area: usize) -> bool {
+        self.spawns_counter < self.max_spawns && self.min_area <= area && area > 0
     }
 
-    pub fn orcs_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 4,
-            entities: vec![
-                SpawnEntry::new("Orc".to_string(), 1, 3),
-                SpawnEntry::new("Health potion".to_string(), 1, 2),
-                SpawnEntry::new("Magic missile scroll".to_string(), 1, 1).with_chance(40),
-            ],
-            ..SpawnPack::default()
+    /// How many times entities of pack are rolled for spawn area of `area` tiles.
+    pub fn area_scale(&self, area: usize) -> usize {
+        match area
+            .saturating_sub(self.min_area)
+            .checked_div(self.scale_area)
+        {
+            Some(extra) => (1 + extra).min(MAX_AREA_SCALE),
+            None => 1,
         }
     }
+}
 
-    pub fn goblins_with_orc_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 5,
-            entities: vec![
-                SpawnEntry::new("Orc".to_string(), 1, 1),
-                SpawnEntry::new("Goblin".to_string(), 2, 4),
-                SpawnEntry::new("Health potion".to_string(), 1, 2),
-                SpawnEntry::new("Great health potion".to_string(), 1, 1).with_chance(20),
-                SpawnEntry::new("Magic missile scroll".to_string(), 1, 2).with_chance(70),
-                SpawnEntry::new("Sleep scroll".to_string(), 1, 1).with_chance(30),
-            ],
-            ..SpawnPack::default()
+/// Stronger variant of monsters spawned on levels with level weight (depth) from `min_depth`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EliteModifier {
+    pub min_depth: usize,
+    /// chance percentage % for monster to be elite
+    pub chance_perc: usize,
+    /// max hp is increased by this percentage
+    #[serde(default)]
+    pub hp_bonus_perc: usize,
+    #[serde(default)]
+    pub attack_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+}
+
+impl EliteModifier {
+    /// Probability of monster becoming elite, the same as the roll of spawner has.
+    pub fn chance(&self) -> f32 {
+        if self.chance_perc == 0 {
+            0.0
+        } else {
+            rng::test_perc_probability(self.chance_perc)
         }
     }
+}
 
-    pub fn knight_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 5,
-            entities: vec![
-                SpawnEntry::new("Knight".to_string(), 1, 1),
-                SpawnEntry::new("Health potion".to_string(), 2, 3),
-                SpawnEntry::new("Great health potion".to_string(), 1, 1).with_chance(80),
-                SpawnEntry::new("Fireball scroll".to_string(), 1, 2).with_chance(70),
-                SpawnEntry::new("Teleport scroll".to_string(), 1, 1).with_chance(45),
-            ],
-            ..SpawnPack::default()
-        }
+#[derive(Clone, Debug)]
+pub struct SpawnTable {
+    pub spawn_packs: Vec<SpawnPack>,
+    /// see `with_level_weight`
+    pub level_weight: usize,
+    pub elites: Vec<EliteModifier>,
+    pub placement: PlacementRules,
+}
+
+impl SpawnTable {
+    /// Picks pack for spawn area of `area` tiles by weights of packs which fit it.
+    /// Returns None, if no pack fits the area.
+    pub fn roll_spawn_pack_index(&mut self, area: usize) -> Option<usize> {
+        let weights = self
+            .spawn_packs
+            .iter()
+            .map(|pack| {
+                if pack.fits(area) {
+                    pack.weight * pack.chance_perc
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<_>>();
+        let index = roll_weighted_index(&weights)?;
+        self.spawn_packs[index].spawns_counter += 1;
+        Some(index)
     }
 
-    pub fn rogues_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 5,
-            entities: vec![
-                SpawnEntry::new("Rogue".to_string(), 2, 4),
-                SpawnEntry::new("Great health potion".to_string(), 2, 3).with_chance(90),
-                SpawnEntry::new("Fireball scroll".to_string(), 1, 2).with_chance(70),
-                SpawnEntry::new("Teleport scroll".to_string(), 1, 1).with_chance(70),
-            ],
-            ..SpawnPack::default()
+    /// Pack index for every spawn area (given by its size), areas are ordered by preference
+    /// (the farthest from arrival first). Boss packs take the first areas they fit,
+    /// guaranteed packs (`min_spawns`) the smallest ones, packs for the rest are rolled in order,
+    /// so last areas stay empty when packs run out.
+    pub fn plan_spawn_packs(&mut self, areas: &[usize]) -> Vec<Option<usize>> {
+        let mut plan = vec![None; areas.len()];
+        for (index, pack) in self.spawn_packs.iter_mut().enumerate() {
+            if !pack.boss {
+                continue;
+            }
+            let free_area = (0..areas.len()).find(|&i| plan[i].is_none() && pack.fits(areas[i]));
+            if let Some(i) = free_area {
+                plan[i] = Some(index);
+                pack.spawns_counter += 1;
+            }
         }
-    }
 
-    pub fn slime_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 8,
-            entities: vec![
-                SpawnEntry::new("Slime".to_string(), 1, 1),
-                SpawnEntry::new("Small slime".to_string(), 2, 6),
-                SpawnEntry::new("Area sleep scroll".to_string(), 1, 1),
-                SpawnEntry::new("Great health potion".to_string(), 1, 1),
-            ],
-            ..SpawnPack::default()
+        let mut by_size = (0..areas.len()).collect::<Vec<_>>();
+        by_size.sort_by_key(|&i| areas[i]);
+
+        for (index, pack) in self.spawn_packs.iter_mut().enumerate() {
+            for _ in 0..pack.min_spawns {
+                let free_area = by_size
+                    .iter()
+                    .copied()
+                    .find(|&i| plan[i].is_none() && pack.fits(areas[i]));
+                match free_area {
+                    Some(i) => {
+                        plan[i] = Some(index);
+                        pack.spawns_counter += 1;
+                    }
+                    None => break,
+                }
+            }
         }
-    }
 
-    pub fn small_slimes_pack() -> SpawnPack {
-        SpawnPack {
-            min_area: 7,
-            entities: vec![SpawnEntry::new("Small slime".to_string(), 2, 6)],
-            ..SpawnPack::default()
+        for (i, &area) in areas.iter().enumerate() {
+            if plan[i].is_none() {
+                plan[i] = self.roll_spawn_pack_index(area);
+            }
         }
+        plan
     }
 
-    pub fn armory_low_tier() -> SpawnPack {
-        SpawnPack {
-            min_area: 4,
-            entities: vec![
-                SpawnEntry::new("Short sword".to_string(), 1, 1),
-                SpawnEntry::new("Leather armor".to_string(), 1, 1),
-                SpawnEntry::new("Long sword".to_string(), 1, 1).with_chance(10),
-                SpawnEntry::new("Health potion".to_string(), 0, 2),
-            ],
-            ..SpawnPack::default()
-        }
+    /// Names of guaranteed packs which were spawned less than `min_spawns` times.
+    pub fn missing_guaranteed_packs(&self) -> Vec<String> {
+        self.spawn_packs
+            .iter()
+            .filter(|pack| pack.spawns_counter < pack.min_spawns)
+            .map(|pack| pack.name.clone())
+            .collect()
     }
 
-    pub fn armory_medium_tier() -> SpawnPack {
-        SpawnPack {
-            min_area: 4,
-            entities: vec![
-                SpawnEntry::new("Leather boots".to_string(), 1, 1).with_chance(30),
-                SpawnEntry::new("Chain armor".to_string(), 1, 1),
-                SpawnEntry::new("Long sword".to_string(), 1, 1),
-                SpawnEntry::new("Health potion".to_string(), 2, 3),
-                SpawnEntry::new("Sleep scroll".to_string(), 0, 1),
-            ],
-            ..SpawnPack::default()
+    /// Only packs allowed at `level_weight` (used as depth) are kept,
+    /// their max spawns grow with it.
+    pub fn with_level_weight(mut self, level_weight: usize) -> SpawnTable {
+        self.spawn_packs
+            .retain(|pack| pack.min_depth <= level_weight && level_weight <= pack.max_depth);
+        for pack in self.spawn_packs.iter_mut() {
+            let extra =
+                (pack.extra_spawns_per_depth * (level_weight - pack.min_depth) as f32) as usize;
+            pack.max_spawns = pack.max_spawns.saturating_add(extra);
         }
+        self.level_weight = level_weight;
+        self
     }
 
-    pub fn armory_high_tier() -> SpawnPack {
-        SpawnPack {
-            min_area: 4,
-            entities: vec![
-                SpawnEntry::new("Leather boots".to_string(), 1, 1),
-                SpawnEntry::new("Plate armor".to_string(), 1, 1).with_chance(50),
-                SpawnEntry::new("Zweihander".to_string(), 1, 1),
-                SpawnEntry::new("Health potion".to_string(), 2, 3),
-                SpawnEntry::new("Great health potion".to_string(), 0, 1),
-                SpawnEntry::new("Magic missile scroll".to_string(), 1, 2),
-            ],
-            ..SpawnPack::default()
+    /// Elite modifier with the biggest `min_depth` reached by level weight.
+    pub fn elite_modifier(&self) -> Option<&EliteModifier> {
+        self.elites
+            .iter()
+            .filter(|elite| elite.min_depth <= self.level_weight)
+            .max_by_key(|elite| elite.min_depth)
+    }
+
+    pub fn empty() -> SpawnTable {
+        SpawnTable {
+            spawn_packs: vec![],
+            level_weight: 0,
+            elites: vec![],
+            placement: PlacementRules::default(),
         }
     }
+}
 
-    pub fn armory_highest_tier() -> SpawnPack {
-        SpawnPack {
-            min_area: 4,
-            entities: vec![
-                SpawnEntry::new("Leather boots".to_string(), 1, 1),
-                SpawnEntry::new("Plate armor".to_string(), 1, 1),
-                SpawnEntry::new("Champion sword".to_string(), 1, 1),
-                SpawnEntry::new("Great health potion".to_string(), 2, 4),
-                SpawnEntry::new("Fireball scroll".to_string(), 2, 4),
-                SpawnEntry::new("Teleport scroll".to_string(), 1, 2),
-            ],
-            ..SpawnPack::default()
+/// Random index, chance of every index is proportional to its weight.
+/// Returns None, if all weights are 0.
+fn roll_weighted_index(weights: &[usize]) -> Option<usize> {
+    let total_weight: usize = weights.iter().sum();
+    if total_weight == 0 {
+        return None;
+    }
+    let mut roll = rng::range(0, total_weight as i32 - 1) as usize;
+    weights.iter().position(|&weight| {
+        if roll < weight {
+            true
+        } else {
+            roll -= weight;
+            false
         }
+    })
+}
+
+/// Pack used in table, optional fields override values from pack definition.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnPackRef {
+    pub pack: String,
+    pub max_spawns: Option<usize>,
+    pub min_spawns: Option<usize>,
+    pub chance_perc: Option<usize>,
+    pub min_depth: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub weight: Option<usize>,
+}
+
+/// Format of spawn tables file. Packs are defined once and referenced by name in tables.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnTables {
+    pub packs: HashMap<String, SpawnPack>,
+    pub tables: HashMap<String, Vec<SpawnPackRef>>,
+    /// elite monsters of all tables
+    #[serde(default)]
+    pub elites: Vec<EliteModifier>,
+    /// where packs of all tables are placed
+    #[serde(default)]
+    pub placement: PlacementRules,
+}
+
+impl SpawnTables {
+    pub fn load_from_file(path: &str) -> Result<SpawnTables> {
+        let contents = fs::read_to_string(path).context(SpawnTablesFileIo { path })?;
+        let spawn_tables: SpawnTables =
+            serde_json::from_str(&contents).context(SpawnTablesFileSerde { path })?;
+        spawn_tables.validate()?;
+        Ok(spawn_tables)
     }
-    pub fn boss() -> SpawnPack {
-        SpawnPack {
-            min_area: 1,
-            entities: vec![SpawnEntry::new("Mighty slime".to_string(), 1, 1)],
-            ..SpawnPack::default()
+
+    /// Checks if all packs used in tables exist, and all entities in packs are defined in raws.
+    pub fn validate(&self) -> Result<()> {
+        for (table_name, pack_refs) in self.tables.iter() {
+            for pack_ref in pack_refs.iter() {
+                ensure!(
+                    self.packs.contains_key(&pack_ref.pack),
+                    UnknownSpawnPack {
+                        pack: &pack_ref.pack,
+                        table: table_name,
+                    }
+                );
+            }
         }
+        for (pack_name, pack) in self.packs.iter() {
+            for entry in pack.entities.iter() {
+                ensure!(
+                    raws::is_known_entity(&entry.entity_name),
+                    UnknownEntityReference {
+                        name: &entry.entity_name,
+                        referenced_by: format!("spawn pack {}", pack_name),
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_table(&self, name: &str) -> Result<SpawnTable> {
+        let pack_refs = self.tables.get(name).context(UnknownSpawnTable { name })?;
+        let spawn_packs = pack_refs
+            .iter()
+            .map(|pack_ref| {
+                let mut pack = self.packs[&pack_ref.pack].clone();
+                pack.name = pack_ref.pack.clone();
+                if let Some(max_spawns) = pack_ref.max_spawns {
+                    pack = pack.with_max_spawns(max_spawns);
+                }
+                if let Some(min_spawns) = pack_ref.min_spawns {
+                    pack = pack.with_min_spawns(min_spawns);
+                }
+                if let Some(chance_perc) = pack_ref.chance_perc {
+                    pack = pack.with_chance_perc(chance_perc);
+                }
+                let min_depth = pack_ref.min_depth.unwrap_or(pack.min_depth);
+                let max_depth = pack_ref.max_depth.unwrap_or(pack.max_depth);
+                pack = pack.with_depths(min_depth, max_depth);
+                if let Some(weight) = pack_ref.weight {
+                    pack = pack.with_weight(weight);
+                }
+                pack
+            })
+            .collect();
+        Ok(SpawnTable {
+            spawn_packs,
+            level_weight: 0,
+            elites: self.elites.clone(),
+            placement: self.placement.clone(),
+        })
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names = self.tables.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
     }
 }
 
-pub struct SpawnTable {
-    pub level_type: LevelType,
-    pub spawn_packs: Vec<SpawnPack>,
+/// Loads spawn tables from `path` and replaces currently used ones.
+/// Raws have to be loaded first (used for validation).
+pub fn load_spawn_tables(path: &str) -> Result<()> {
+    let spawn_tables = SpawnTables::load_from_file(path)?;
+    *SPAWN_TABLES.lock().unwrap() = spawn_tables;
+    Ok(())
 }
 
-impl SpawnTable {
-    /// returns None, if no spawn can happen for this area.
-    pub fn roll_spawn_pack_index(&mut self, area: usize) -> Option<usize> {
-        let max_rolls = 1000;
-        let mut i = 0;
-        loop {
-            if i >= max_rolls
-                || self
-                    .spawn_packs
-                    .iter()
-                    .all(|sp| sp.spawns_counter >= sp.max_spawns)
-            {
-                return None;
-            }
+pub fn get_spawn_table(name: &str) -> Result<SpawnTable> {
+    SPAWN_TABLES.lock().unwrap().get_table(name)
+}
 
-            let index = rng::range(0, self.spawn_packs.len() as i32 - 1) as usize;
-            let chance = rng::random_perc() as usize;
+pub fn spawn_table_names() -> Vec<String> {
+    SPAWN_TABLES.lock().unwrap().table_names()
+}
 
-            if chance <= self.spawn_packs[index].chance_perc
-                && self.spawn_packs[index].spawns_counter < self.spawn_packs[index].max_spawns
-                && self.spawn_packs[index].min_area <= area
-            {
-                self.spawn_packs[index].spawns_counter += 1;
-                return Some(index);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spawner::Error;
 
-            i += 1;
-        }
+    fn spawn_tables(json: &str) -> SpawnTables {
+        raws::load_raws(raws::RAWS_DIR).unwrap();
+        serde_json::from_str(json).unwrap()
     }
 
-    pub fn empty() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::TestLevel,
-            spawn_packs: vec![],
+    #[test]
+    fn spawn_tables_file_is_valid() {
+        raws::load_raws(raws::RAWS_DIR).unwrap();
+        let spawn_tables = SpawnTables::load_from_file(SPAWN_TABLES_PATH).unwrap();
+        for name in spawn_tables.table_names() {
+            assert!(spawn_tables.get_table(&name).is_ok());
         }
     }
 
-    pub fn basic_dungeon() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::BasicDungeon,
-            spawn_packs: vec![
-                SpawnPack::small_slimes_pack().with_max_spawns(5),
-                SpawnPack::goblins_pack().with_max_spawns(2),
-                SpawnPack::goblins_with_orc_pack()
-                    .with_max_spawns(1)
-                    .with_chance_perc(10),
-                SpawnPack::armory_low_tier().with_max_spawns(1),
-            ],
-        }
+    #[test]
+    fn unknown_pack_in_table_is_reported() {
+        let spawn_tables =
+            spawn_tables(r#"{ "packs": {}, "tables": { "caves": [ { "pack": "orcs" } ] } }"#);
+
+        assert!(matches!(
+            spawn_tables.validate(),
+            Err(Error::UnknownSpawnPack { pack, table }) if pack == "orcs" && table == "caves"
+        ));
     }
 
-    pub fn bsp_dungeon() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::BasicDungeon,
-            spawn_packs: vec![
-                SpawnPack::goblins_pack().with_max_spawns(4),
-                SpawnPack::orcs_pack().with_max_spawns(3),
-                SpawnPack::goblins_with_orc_pack().with_max_spawns(2),
-                SpawnPack::knight_pack().with_max_spawns(1),
-                SpawnPack::armory_medium_tier().with_max_spawns(1),
-            ],
-        }
+    #[test]
+    fn unknown_entity_in_pack_is_reported() {
+        let spawn_tables = spawn_tables(
+            r#"{
+                "packs": { "orcs": { "entities": [ { "entity_name": "Ork", "rng_range": [1, 2] } ] } },
+                "tables": {}
+            }"#,
+        );
+
+        assert!(matches!(
+            spawn_tables.validate(),
+            Err(Error::UnknownEntityReference { name, .. }) if name == "Ork"
+        ));
     }
 
-    pub fn caves() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::Cave,
-            spawn_packs: vec![
-                SpawnPack::goblins_pack().with_max_spawns(10),
-                SpawnPack::orcs_pack().with_max_spawns(2),
-            ],
-        }
+    #[test]
+    fn unknown_table_is_reported() {
+        let spawn_tables = spawn_tables(r#"{ "packs": {}, "tables": {} }"#);
+
+        assert!(matches!(
+            spawn_tables.get_table("caves"),
+            Err(Error::UnknownSpawnTable { name }) if name == "caves"
+        ));
     }
 
-    pub fn drunkard_walk() -> SpawnTable {
+    fn table(spawn_packs: Vec<SpawnPack>) -> SpawnTable {
         SpawnTable {
-            level_type: LevelType::Cave,
-            spawn_packs: vec![
-                SpawnPack::small_slimes_pack().with_max_spawns(5),
-                SpawnPack::slime_pack().with_max_spawns(3),
-                SpawnPack::armory_high_tier().with_max_spawns(1),
-            ],
+            spawn_packs,
+            ..SpawnTable::empty()
         }
     }
 
-    pub fn bsp_interior() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::BasicDungeon,
-            spawn_packs: vec![
-                SpawnPack::knight_pack().with_max_spawns(6),
-                SpawnPack::rogues_pack().with_max_spawns(7),
-                SpawnPack::armory_highest_tier().with_max_spawns(1),
-            ],
+    #[test]
+    fn zero_weight_and_not_fitting_packs_are_never_rolled() {
+        let mut table = table(vec![
+            SpawnPack::default().with_weight(0),
+            SpawnPack {
+                min_area: 50,
+                ..SpawnPack::default()
+            },
+            SpawnPack::default().with_max_spawns(0),
+            SpawnPack::default().with_chance_perc(0),
+            SpawnPack::default().with_weight(2),
+        ]);
+
+        for seed in 0..100 {
+            rng::reseed(seed);
+            assert_eq!(table.roll_spawn_pack_index(10), Some(4));
         }
+        assert_eq!(table.spawn_packs[4].spawns_counter, 100);
+        assert_eq!(table.roll_spawn_pack_index(0), None);
+
+        table.spawn_packs[4].max_spawns = 100;
+        assert_eq!(table.roll_spawn_pack_index(10), None);
     }
-    pub fn boss_level() -> SpawnTable {
-        SpawnTable {
-            level_type: LevelType::TestLevel,
-            spawn_packs: vec![SpawnPack::boss().with_max_spawns(1)],
+
+    #[test]
+    fn boss_packs_take_the_farthest_areas_they_fit() {
+        let mut table = table(vec![
+            SpawnPack::default(),
+            SpawnPack {
+                min_area: 20,
+                ..SpawnPack::default()
+            }
+            .as_boss()
+            .with_max_spawns(1),
+            SpawnPack {
+                min_area: 8,
+                ..SpawnPack::default()
+            }
+            .with_min_spawns(1)
+            .with_max_spawns(1),
+            SpawnPack {
+                min_area: 100,
+                ..SpawnPack::default()
+            }
+            .as_boss(),
+        ]);
+
+        // areas are ordered from the farthest one, guaranteed pack takes the smallest it fits
+        let plan = table.plan_spawn_packs(&[5, 30, 30, 10]);
+
+        assert_eq!(plan, vec![Some(0), Some(1), Some(0), Some(2)]);
+        assert_eq!(table.spawn_packs[3].spawns_counter, 0);
+        assert!(table.missing_guaranteed_packs().is_empty());
+    }
+
+    #[test]
+    fn packs_are_rolled_proportionally_to_weight() {
+        rng::reseed(7);
+        let mut counts = [0; 3];
+        for _ in 0..6000 {
+            counts[roll_weighted_index(&[1, 0, 2]).unwrap()] += 1;
         }
+
+        assert_eq!(counts[1], 0);
+        assert!((1800..2200).contains(&counts[0]), "{:?}", counts);
+        assert!((3800..4200).contains(&counts[2]), "{:?}", counts);
+        assert_eq!(roll_weighted_index(&[0, 0]), None);
+        assert_eq!(roll_weighted_index(&[]), None);
+    }
+
+    #[test]
+    fn area_scale_is_bounded() {
+        let pack = SpawnPack {
+            min_area: 10,
+            scale_area: 5,
+            ..SpawnPack::default()
+        };
+        let not_scaled = SpawnPack {
+            min_area: 10,
+            ..SpawnPack::default()
+        };
+
+        assert_eq!(pack.area_scale(4), 1);
+        assert_eq!(pack.area_scale(14), 1);
+        assert_eq!(pack.area_scale(15), 2);
+        assert_eq!(pack.area_scale(1000), MAX_AREA_SCALE);
+        assert_eq!(not_scaled.area_scale(1000), 1);
+        assert_eq!(not_scaled.area_scale(0), 1);
+    }
+
+    #[test]
+    fn elite_chance_matches_elite_roll() {
+        let elite = |chance_perc| EliteModifier {
+            min_depth: 0,
+            chance_perc,
+            hp_bonus_perc: 0,
+            attack_bonus: 0,
+            defense_bonus: 0,
+        };
+        assert_eq!(elite(0).chance(), 0.0);
+        assert_eq!(elite(100).chance(), 1.0);
+        assert_eq!(elite(150).chance(), 1.0);
+
+        rng::reseed(3);
+        let rolls = 10000;
+        // 1% chance succeeds for 2 rolls of 101
+        let successes = (0..rolls).filter(|_| rng::test_perc(1)).count();
+        let expected = elite(1).chance() * rolls as f32;
+        assert!(
+            (successes as f32 - expected).abs() < rolls as f32 * 0.005,
+            "{} vs {}",
+            successes,
+            expected
+        );
     }
 }
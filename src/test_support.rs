@@ -0,0 +1,315 @@
+//! Helpers for gameplay tests. `TestWorld` is a `State` with single open level
+//! (no map generators, no rltk window), entities are spawned from raw files.
+
+use rltk::Point;
+use specs::prelude::*;
+
+use crate::ecs::{
+    components::{self, BodyPart},
+    game_state::{GameLog, RunState},
+    systems::{self, player::InputType},
+    State,
+};
+use crate::graphics::GuiDrawer;
+use crate::levels::{
+    dungeon_layout::LevelKey,
+    level::{Level, LevelExit},
+};
+use crate::maps::{Map, TileType};
+use crate::spawner::{self, player::spawn_player, raws, spawn_tables::SpawnTable, SpawnReport};
+use crate::CONSOLE_BOX_HEIGHT;
+
+pub struct TestWorld {
+    pub gs: State,
+}
+
+impl TestWorld {
+    /// Level is `width` x `height` floor surrounded by walls, player stands at (1, 1).
+    pub fn new(width: usize, height: usize) -> TestWorld {
+        raws::load_raws(raws::RAWS_DIR).expect("Unable to load raws");
+
+        // gui is not drawn, default window size is used so menus fit
+        let gui_drawer = GuiDrawer::new(80, 60, CONSOLE_BOX_HEIGHT);
+        let mut gs = State::new(80, 60, width, height, gui_drawer);
+        gs.register_all_components();
+
+        let mut map = Map::new(width, height).with_edges_solid();
+        map.update_blocked_with_blocking_tiles();
+        gs.level_manager.levels.push(Level {
+            map,
+            level_index: 0,
+            depth: 0,
+            branch: 0,
+            level_weight: 0,
+            dangerous: false,
+            spawn_report: SpawnReport::default(),
+            spawn_areas: vec![],
+            vault_areas: vec![],
+            exits: vec![],
+            spawn_table: String::new(),
+            last_active_turn: 0,
+            start: None,
+        });
+        gs.set_level_as_curent(0);
+
+        gs.ecs.insert(RunState::AwaitingInput);
+        gs.ecs.insert(GameLog { entries: vec![] });
+        gs.ecs.insert(Point::new(1, 1));
+        let player = spawn_player(&mut gs.ecs, 1, 1);
+        gs.ecs.insert(player);
+
+        let mut world = TestWorld { gs };
+        world.index_map();
+        world
+    }
+
+    pub fn player(&self) -> Entity {
+        *self.gs.ecs.fetch::<Entity>()
+    }
+
+    pub fn run_state(&self) -> RunState {
+        *self.gs.ecs.fetch::<RunState>()
+    }
+
+    /// Spawns entity defined in raws, panics if name is unknown.
+    pub fn spawn(&mut self, name: &str, x: usize, y: usize) -> Entity {
+        let entity = spawner::spawn_entity(&mut self.gs.ecs, name, x, y, 0)
+            .unwrap_or_else(|| panic!("Unable to spawn {}", name));
+        self.index_map();
+        entity
+    }
+
+    pub fn spawn_in_inventory(&mut self, owner: Entity, name: &str) -> Entity {
+        let pos = self.position(owner);
+        spawner::spawn_item_into_inventory(
+            &mut self.gs.ecs,
+            owner,
+            name.to_string(),
+            pos.0,
+            pos.1,
+            0,
+        )
+        .unwrap_or_else(|| panic!("Unable to spawn {} into inventory", name))
+    }
+
+    /// Spawns item into inventory and equips it (the same way spawner equips monsters).
+    pub fn spawn_equipped(&mut self, owner: Entity, name: &str) -> Entity {
+        let item = self.spawn_in_inventory(owner, name);
+        systems::inventory::insert_item_in_eq(&mut self.gs.ecs, owner, item);
+        item
+    }
+
+    pub fn want_melee(&mut self, attacker: Entity, target: Entity) {
+        self.gs
+            .ecs
+            .write_storage::<components::WantsToMeleeAtack>()
+            .insert(attacker, components::WantsToMeleeAtack { target })
+            .expect("Unable to insert melee intent");
+    }
+
+    pub fn want_use_item(&mut self, user: Entity, item: Entity, target: Option<Point>) {
+        self.gs
+            .ecs
+            .write_storage::<components::WantsToUseItem>()
+            .insert(user, components::WantsToUseItem { item, target })
+            .expect("Unable to insert use item intent");
+    }
+
+    pub fn want_equip(&mut self, owner: Entity, item: Entity, target_body_part: BodyPart) {
+        self.gs
+            .ecs
+            .write_storage::<components::WantsToEquip>()
+            .insert(
+                owner,
+                components::WantsToEquip {
+                    item,
+                    target_body_part,
+                },
+            )
+            .expect("Unable to insert equip intent");
+    }
+
+    pub fn want_unequip(&mut self, owner: Entity, item: Entity) {
+        self.gs
+            .ecs
+            .write_storage::<components::WantsToUnEquip>()
+            .insert(owner, components::WantsToUnEquip { item })
+            .expect("Unable to insert unequip intent");
+    }
+
+    pub fn set_hp(&mut self, entity: Entity, hp: i32) {
+        let mut hps = self.gs.ecs.write_storage::<components::Hp>();
+        hps.get_mut(entity).expect("Entity without hp").hp = hp;
+    }
+
+    /// Updates `tile_content` and `blocked` of level (needed by targeting and spawning).
+    pub fn index_map(&mut self) {
+        systems::map::MapIndexingSystem {}.run_now(&self.gs.ecs);
+        self.gs.ecs.maintain();
+    }
+
+    pub fn put_to_sleep(&mut self, entity: Entity, duration: usize) {
+        self.gs
+            .ecs
+            .write_storage::<components::SleepingEffect>()
+            .insert(entity, components::SleepingEffect { duration })
+            .expect("Unable to insert sleep");
+    }
+
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: TileType) {
+        let mut level = self.gs.ecs.fetch_mut::<Level>();
+        let index = level.map.xy_to_index(x, y);
+        level.map.tiles[index] = tile;
+    }
+
+    /// Spawns from spawn table with whole level as one spawn area.
+    pub fn spawn_from_table(&mut self, spawn_table: SpawnTable) -> SpawnReport {
+        let area = {
+            let map = &self.gs.ecs.fetch::<Level>().map;
+            (1..map.height_max())
+                .flat_map(|y| (1..map.width_max()).map(move |x| (x, y)))
+                .filter(|&pos| pos != (1, 1))
+                .collect()
+        };
+        self.spawn_from_table_in(vec![area], spawn_table)
+    }
+
+    pub fn spawn_from_table_in(
+        &mut self,
+        spawn_areas: Vec<Vec<(usize, usize)>>,
+        spawn_table: SpawnTable,
+    ) -> SpawnReport {
+        let mut level = (*self.gs.ecs.fetch::<Level>()).clone();
+        level.spawn_areas = spawn_areas;
+        let report = spawner::spawn_from_spawn_table(&mut self.gs.ecs, &level, spawn_table, &[]);
+        self.index_map();
+        report
+    }
+
+    /// Stairs or trapdoor leading to `destination` (arrival isn't known).
+    pub fn add_exit(&mut self, x: usize, y: usize, tile: TileType, destination: LevelKey) {
+        self.set_tile(x, y, tile);
+        self.gs.ecs.fetch_mut::<Level>().exits.push(LevelExit {
+            x,
+            y,
+            destination,
+            arrival: None,
+        });
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> TileType {
+        self.gs.ecs.fetch::<Level>().map.tile_at_xy(x, y)
+    }
+
+    /// Handles input of player, the same as pressed key. Returned state isn't run.
+    pub fn press(&mut self, input: InputType) -> RunState {
+        {
+            let mut players = self.gs.ecs.write_storage::<components::Player>();
+            players
+                .get_mut(self.player())
+                .expect("Player missing")
+                .input = Some(input);
+        }
+        self.index_map();
+        systems::player::input::try_handle_input(&mut self.gs)
+    }
+
+    /// Views and one monsters turn of AI.
+    pub fn run_ai(&mut self) {
+        self.index_map();
+        self.gs.ecs.insert(RunState::MonsterTurn);
+        systems::view_system::ViewSystem {}.run_now(&self.gs.ecs);
+        systems::ai::AISystem {}.run_now(&self.gs.ecs);
+        self.gs.ecs.maintain();
+    }
+
+    /// Melee attacks and damage.
+    pub fn run_combat(&mut self) {
+        systems::combat::melee::MeleeCombatSystem {}.run_now(&self.gs.ecs);
+        systems::combat::damage::DamageSystem {}.run_now(&self.gs.ecs);
+        self.gs.ecs.maintain();
+    }
+
+    /// Item usage, its effects and damage.
+    pub fn run_item_use(&mut self) {
+        self.index_map();
+        systems::inventory::UseItemSystem {}.run_now(&self.gs.ecs);
+        systems::inventory::DestroyUsedItems {}.run_now(&self.gs.ecs);
+        systems::combat::damage::DamageSystem {}.run_now(&self.gs.ecs);
+        self.gs.ecs.maintain();
+    }
+
+    pub fn run_equipment(&mut self) {
+        systems::inventory::ItemEquipSystem {}.run_now(&self.gs.ecs);
+        systems::inventory::ItemUnEquipSystem {}.run_now(&self.gs.ecs);
+        self.gs.ecs.maintain();
+    }
+
+    /// Spawns after death and removes dead, in the same order as during turn.
+    pub fn run_deaths(&mut self) {
+        self.index_map();
+        systems::combat::spawn_after_death::SpawnsAfterDeathSystem {}.run_now(&self.gs.ecs);
+        systems::spawn::spawn_system(&mut self.gs);
+        systems::combat::damage::delete_the_dead(&mut self.gs);
+        self.gs.ecs.maintain();
+        self.index_map();
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.gs.ecs.is_alive(entity)
+    }
+
+    pub fn hp(&self, entity: Entity) -> i32 {
+        self.gs
+            .ecs
+            .read_storage::<components::Hp>()
+            .get(entity)
+            .expect("Entity without hp")
+            .hp
+    }
+
+    pub fn position(&self, entity: Entity) -> (usize, usize) {
+        let positions = self.gs.ecs.read_storage::<components::Position>();
+        let pos = positions.get(entity).expect("Entity without position");
+        (pos.x, pos.y)
+    }
+
+    /// Entities with given name (items in inventories included).
+    pub fn named(&self, name: &str) -> Vec<Entity> {
+        let entities = self.gs.ecs.entities();
+        let names = self.gs.ecs.read_storage::<components::Name>();
+        (&entities, &names)
+            .join()
+            .filter(|(_e, n)| n.name == name)
+            .map(|(e, _n)| e)
+            .collect()
+    }
+
+    pub fn equipped_in(&self, owner: Entity, body_part: BodyPart) -> Option<Entity> {
+        let body_parts = self.gs.ecs.read_storage::<components::BodyParts>();
+        body_parts
+            .get(owner)
+            .and_then(|parts| parts.parts_with_equipped.get(&body_part).copied())
+            .flatten()
+    }
+
+    pub fn is_equipped(&self, item: Entity) -> bool {
+        self.gs
+            .ecs
+            .read_storage::<components::Equipped>()
+            .contains(item)
+    }
+
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.gs.ecs.read_storage::<T>().contains(entity)
+    }
+
+    pub fn log_contains(&self, text: &str) -> bool {
+        self.gs
+            .ecs
+            .fetch::<GameLog>()
+            .entries
+            .iter()
+            .any(|entry| entry.contains(text))
+    }
+}